@@ -1,31 +1,69 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use anyhow::{bail, Error, Result};
-use git2::{AutotagOption, BranchType, FetchOptions, FetchPrune, Oid, Repository, Sort};
+use git2::build::{CheckoutBuilder, RepoBuilder};
+use git2::{message_prettify, ApplyLocation, AutotagOption, BranchType, CherrypickOptions, Commit, Diff, Direction, ErrorCode, FetchOptions, FetchPrune, FileMode, IndexAddOption, Oid, PushOptions, Rebase, Reference, Remote, Repository, RepositoryInitOptions, RevertOptions, Signature, Sort, StashApplyOptions, StashFlags};
 use crate::backend::git_utils;
+use crate::backend::git_utils::{FileDiff, InteractiveRebase, RebaseAction, RebaseStepState, RebaseTodoItem, Sequence, SequenceKind};
+use crate::backend::progress::OperationProgress;
 
-pub fn git_revwalk(repo: &Repository) -> Result<Vec<Oid>> {
-    // First, we need to get the commits to start/include in the revwalk.
+// Which refs besides local branches and a detached HEAD to start the revwalk from.
+#[derive(Clone, Copy, PartialEq)]
+pub struct RevwalkRoots {
+    pub include_remote_branches: bool,
+    pub include_tags: bool,
+    pub include_stashes: bool,
+}
+
+impl Default for RevwalkRoots {
+    fn default() -> Self {
+        Self {
+            include_remote_branches: true,
+            include_tags: true,
+            include_stashes: true,
+        }
+    }
+}
+
+// Gets the commits to start/include in the revwalk, newest first.
+pub fn get_revwalk_root_oids(repo: &Repository, roots: RevwalkRoots) -> Result<Vec<Oid>> {
     let mut initial_oid_vec: Vec<Oid> = vec![];
-    for branch_result in repo.branches(None)? {
+    let branch_type_opt = if roots.include_remote_branches { None } else { Some(BranchType::Local) };
+    for branch_result in repo.branches(branch_type_opt)? {
         let (branch, _) = branch_result?;
-        match branch.get().target() {
-            Some(oid) => {
-                if !initial_oid_vec.contains(&oid) {
-                    initial_oid_vec.push(oid);
-                }
-            },
-            None => (),
-        };
+        if let Some(oid) = branch.get().target() {
+            if !initial_oid_vec.contains(&oid) {
+                initial_oid_vec.push(oid);
+            }
+        }
     };
 
-    if repo.head_detached()? {
-        match repo.head()?.target() {
-            Some(oid) => {
-                if !initial_oid_vec.contains(&oid) {
-                    initial_oid_vec.push(oid);
+    if roots.include_tags {
+        for reference_result in repo.references_glob("refs/tags/*")? {
+            // Tags can point at trees or blobs, which have no commits to show.
+            if let Ok(commit) = reference_result?.peel_to_commit() {
+                if !initial_oid_vec.contains(&commit.id()) {
+                    initial_oid_vec.push(commit.id());
                 }
-            },
-            None => (),
-        };
+            }
+        }
+    }
+
+    let stashes = if roots.include_stashes { git_utils::get_stashes(repo)? } else { vec![] };
+    for stash in stashes {
+        if !initial_oid_vec.contains(&stash.base_oid) {
+            initial_oid_vec.push(stash.base_oid);
+        }
+    }
+
+    if repo.head_detached()? {
+        if let Some(oid) = repo.head()?.target() {
+            if !initial_oid_vec.contains(&oid) {
+                initial_oid_vec.push(oid);
+            }
+        }
     }
 
     // Sort Oids by date first
@@ -33,88 +71,1542 @@ pub fn git_revwalk(repo: &Repository) -> Result<Vec<Oid>> {
         repo.find_commit(*b).unwrap().time().seconds().partial_cmp(&repo.find_commit(*a).unwrap().time().seconds()).unwrap()
     });
 
+    Ok(initial_oid_vec)
+}
+
+// Gets the commits that are reachable from root_oids but not from old_root_oids, i.e. the ones that are new since the
+// graph was walked from old_root_oids.
+pub fn git_revwalk_new_commits(repo: &Repository, root_oids: &[Oid], old_root_oids: &[Oid]) -> Result<Vec<Oid>> {
     let mut revwalk = repo.revwalk()?;
 
-    for oid in initial_oid_vec {
-        revwalk.push(oid)?;
+    for oid in root_oids {
+        revwalk.push(*oid)?;
+    }
+    for oid in old_root_oids {
+        revwalk.hide(*oid)?;
     }
     revwalk.set_sorting(Sort::TOPOLOGICAL)?;
 
-    let mut all_oids_vec: Vec<Oid> = vec![];
+    let mut new_oids_vec: Vec<Oid> = vec![];
     for commit_oid_result in revwalk {
-        all_oids_vec.push(commit_oid_result?);
+        new_oids_vec.push(commit_oid_result?);
     }
-    Ok(all_oids_vec)
+    Ok(new_oids_vec)
+}
+
+pub fn git_clone(url: &str, path: &Path, branch_opt: Option<&str>, depth_opt: Option<i32>, recurse_submodules: bool, progress: &Arc<Mutex<OperationProgress>>) -> Result<Repository> {
+    let repo = git_utils::with_certificate_prompt(progress, || {
+        let mut fetch_options = FetchOptions::new();
+        fetch_options.remote_callbacks(git_utils::get_remote_callbacks(progress, &format!("Cloning {}", url), url, Direction::Fetch));
+        if let Some(depth) = depth_opt {
+            fetch_options.depth(depth);
+        }
+
+        let mut repo_builder = RepoBuilder::new();
+        repo_builder.fetch_options(fetch_options);
+        if let Some(branch) = branch_opt {
+            repo_builder.branch(branch);
+        }
+        repo_builder.clone(url, path)
+    })?;
+
+    if recurse_submodules {
+        git_utils::update_submodules(&repo, progress)?;
+    }
+    Ok(repo)
+}
+
+pub fn git_init(path: &Path, initial_branch: &str, is_bare: bool, template_path_opt: Option<&Path>, create_initial_commit: bool, create_gitignore: bool) -> Result<Repository> {
+    if create_gitignore && is_bare && !create_initial_commit {
+        bail!("A bare repository has no working directory, so a starter .gitignore can only be added as part of an initial commit.");
+    }
+
+    let mut init_options = RepositoryInitOptions::new();
+    init_options.bare(is_bare);
+    init_options.initial_head(initial_branch);
+    init_options.mkpath(true);
+    if let Some(template_path) = template_path_opt {
+        init_options.external_template(true);
+        init_options.template_path(template_path);
+    }
+    let repo = Repository::init_opts(path, &init_options)?;
+
+    if create_gitignore && !is_bare {
+        std::fs::write(path.join(".gitignore"), git_utils::STARTER_GITIGNORE)?;
+    }
+
+    if create_initial_commit {
+        let mut tree_builder = repo.treebuilder(None)?;
+        if create_gitignore {
+            let blob_oid = repo.blob(git_utils::STARTER_GITIGNORE.as_bytes())?;
+            tree_builder.insert(".gitignore", blob_oid, FileMode::Blob.into())?;
+        }
+        let tree = repo.find_tree(tree_builder.write()?)?;
+        let signature = repo.signature()?;
+        repo.commit(Some("HEAD"), &signature, &signature, "Initial commit", &tree, &[])?;
+
+        // Make sure the index matches the new commit so the .gitignore doesn't show as a change.
+        if !is_bare {
+            let mut index = repo.index()?;
+            index.read_tree(&tree)?;
+            index.write()?;
+        }
+    }
+    Ok(repo)
+}
+
+pub fn git_stage(repo: &Repository, paths: &[String]) -> Result<()> {
+    let workdir = repo.workdir().ok_or(Error::msg("Repository has no working directory, cannot stage."))?;
+    let mut index = git_utils::get_index(repo)?;
+    for path in paths {
+        // Deleted files need to be removed from the index instead of added.
+        if workdir.join(path).symlink_metadata().is_ok() {
+            index.add_path(Path::new(path))?;
+        } else {
+            index.remove_path(Path::new(path))?;
+        }
+    }
+    index.write()?;
+    Ok(())
+}
+
+pub fn git_stage_all(repo: &Repository) -> Result<()> {
+    let mut index = git_utils::get_index(repo)?;
+    index.add_all(["*"].iter(), IndexAddOption::DEFAULT, None)?;
+    // add_all doesn't pick up deleted files.
+    index.update_all(["*"].iter(), None)?;
+    index.write()?;
+    Ok(())
 }
 
-pub fn git_fetch(repo: &Repository) -> Result<()> {
-    let remote_string_array = repo.remotes()?;
+pub fn git_unstage(repo: &Repository, paths: &[String]) -> Result<()> {
+    match repo.head() {
+        Ok(head_ref) => {
+            let head_commit = head_ref.peel_to_commit()?;
+            repo.reset_default(Some(head_commit.as_object()), paths)?;
+        },
+        Err(e) => {
+            if e.code() != ErrorCode::UnbornBranch {
+                return Err(e.into());
+            }
+            // With no HEAD commit, unstaging just means removing from the index.
+            let mut index = git_utils::get_index(repo)?;
+            for path in paths {
+                index.remove_path(Path::new(path))?;
+            }
+            index.write()?;
+        },
+    };
+    Ok(())
+}
+
+pub fn git_unstage_all(repo: &Repository) -> Result<()> {
+    let mut paths = vec![];
+    for file_change in git_utils::get_file_changes(&git_utils::get_staged_changes(repo)?)? {
+        paths.append(&mut file_change.get_all_paths());
+    }
+    if !paths.is_empty() {
+        git_unstage(repo, &paths)?;
+    }
+    Ok(())
+}
+
+pub fn git_commit(repo: &Repository, message: &str, is_amend: bool, author_opt: Option<(&str, &str)>, committer_opt: Option<(&str, &str)>) -> Result<Oid> {
+    let message = message_prettify(message, None)?;
+    if message.is_empty() {
+        bail!("Please enter a commit message.");
+    }
+
+    // Any other commit would be mixed in with the rebase's own steps, so those go through Continue instead.
+    if git_utils::is_rebasing(repo) && !(is_amend && git_utils::is_rebase_edit_stop(repo)?) {
+        bail!("A rebase is in progress. Only the commit it stopped to edit can be amended, use Continue for everything else.");
+    }
+
+    let mut index = git_utils::get_index(repo)?;
+    if index.has_conflicts() {
+        bail!("Please resolve all conflicts before committing.");
+    }
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let committer = git_utils::get_signature(repo, committer_opt)?;
+    let head_commit_opt = git_utils::get_head_commit(repo)?;
+
+    let merge_head_oids = git_utils::get_merge_head_oids(repo)?;
+    if is_amend {
+        if !merge_head_oids.is_empty() {
+            bail!("Can't amend while a merge is in progress.");
+        }
+        let head_commit = match head_commit_opt {
+            Some(c) => c,
+            None => bail!("There is no commit to amend yet."),
+        };
+        // Amending keeps the original author unless it's overridden, the same as git does.
+        let author = match author_opt {
+            Some(_) => git_utils::get_signature(repo, author_opt)?,
+            None => head_commit.author().to_owned(),
+        };
+        return Ok(head_commit.amend(Some("HEAD"), Some(&author), Some(&committer), None, Some(&message), Some(&tree))?);
+    }
+
+    let author = git_utils::get_signature(repo, author_opt)?;
+    let mut parents = vec![];
+    if let Some(head_commit) = head_commit_opt {
+        // A merge commit is allowed to have the same tree as HEAD (e.g. if all conflicts were resolved as ours).
+        if head_commit.tree_id() == tree.id() && merge_head_oids.is_empty() {
+            bail!("There are no staged changes to commit.");
+        }
+        parents.push(head_commit);
+    }
+    for oid in merge_head_oids {
+        parents.push(repo.find_commit(oid)?);
+    }
+    let parent_refs: Vec<&Commit> = parents.iter().collect();
+    // With no parents (i.e. an unborn branch), this creates the root commit and the branch with it.
+    let oid = repo.commit(Some("HEAD"), &author, &committer, &message, &tree, &parent_refs)?;
+    git_cleanup_merge_state(repo)?;
+    Ok(oid)
+}
+
+// libgit2's cleanup_state also deletes any rebase that's in progress, so only remove the files git commit does.
+fn git_cleanup_merge_state(repo: &Repository) -> Result<()> {
+    for file_name in ["MERGE_HEAD", "MERGE_MSG", "MERGE_MODE", "SQUASH_MSG", "CHERRY_PICK_HEAD", "REVERT_HEAD"] {
+        let path = repo.path().join(file_name);
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum MergeKind {
+    FastForwardIfPossible,
+    FastForwardOnly,
+    NoFastForward,
+    Squash,
+}
+
+fn git_fast_forward(repo: &Repository, target_oid: Oid, reflog_msg: &str) -> Result<()> {
+    let commit = repo.find_commit(target_oid)?;
+    // The default checkout strategy is safe, so this won't overwrite any local changes.
+    repo.checkout_tree(commit.as_object(), None)?;
+    let head_ref = repo.find_reference("HEAD")?;
+    match head_ref.symbolic_target() {
+        // This also covers an unborn branch, which only exists as the target of HEAD.
+        Some(branch_ref_name) => {
+            repo.reference(branch_ref_name, target_oid, true, reflog_msg)?;
+        },
+        None => repo.set_head_detached(target_oid)?,
+    };
+    Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum MergeOutcome {
+    UpToDate,
+    FastForwarded,
+    Merged,
+    Squashed,
+    Conflicts,
+}
+
+impl MergeOutcome {
+    pub fn get_message(&self) -> &'static str {
+        match self {
+            MergeOutcome::UpToDate => "Already up to date.",
+            MergeOutcome::FastForwarded => "Fast-forwarded.",
+            MergeOutcome::Merged => "Merged and committed.",
+            MergeOutcome::Squashed => "The squashed changes are staged and ready to commit.",
+            MergeOutcome::Conflicts => "Stopped because of conflicts. Resolve them and then finish the merge.",
+        }
+    }
+}
+
+pub fn git_merge(repo: &Repository, ref_name: &str, merge_kind: MergeKind) -> Result<MergeOutcome> {
+    if git_utils::is_operation_in_progress(repo)? || git_utils::has_conflicts(repo)? {
+        bail!("Please finish or abort the operation that's in progress first.");
+    }
+    if git_utils::has_staged_changes(repo)? {
+        bail!("Please commit or unstage your staged changes before merging.");
+    }
+
+    let reference = repo.find_reference(ref_name)?;
+    let shorthand = reference.shorthand().ok_or(Error::msg("Branch Name has invalid UTF-8!"))?;
+    let annotated_commit = repo.reference_to_annotated_commit(&reference)?;
+    let (ma, _) = repo.merge_analysis(&[&annotated_commit])?;
+
+    if ma.is_up_to_date() {
+        return Ok(MergeOutcome::UpToDate);
+    }
+    let can_fast_forward = ma.is_fast_forward() || ma.is_unborn();
+    if can_fast_forward && (merge_kind == MergeKind::FastForwardIfPossible || merge_kind == MergeKind::FastForwardOnly) {
+        git_fast_forward(repo, annotated_commit.id(), &format!("merge {}: Fast-forward", shorthand))?;
+        return Ok(MergeOutcome::FastForwarded);
+    }
+    if merge_kind == MergeKind::FastForwardOnly {
+        bail!("Can't fast-forward to {}, the branches have diverged.", shorthand);
+    }
+    if ma.is_unborn() {
+        bail!("The current branch has no commits yet, so it can only be fast-forwarded.");
+    }
+
+    // This leaves conflicts in the index and working directory for the user to resolve.
+    repo.merge(&[&annotated_commit], None, None)?;
+    let message = git_utils::get_merge_message(repo)?;
+    if merge_kind == MergeKind::Squash {
+        // Squashing only stages the changes, so drop the merge state and keep the message for the commit.
+        repo.cleanup_state()?;
+        std::fs::write(git_utils::get_squash_msg_path(repo), format!("Squashed commit of '{}'\n", shorthand))?;
+        return Ok(MergeOutcome::Squashed);
+    }
+    if git_utils::get_index(repo)?.has_conflicts() {
+        return Ok(MergeOutcome::Conflicts);
+    }
+    git_commit(repo, &message, false, None, None)?;
+    Ok(MergeOutcome::Merged)
+}
+
+#[derive(Clone, Copy)]
+pub enum ConflictSide {
+    Ours,
+    Theirs,
+}
+
+pub fn git_resolve_conflict(repo: &Repository, path: &str, side: ConflictSide) -> Result<()> {
+    let mut index = git_utils::get_index(repo)?;
+    let mut entry_opt = None;
+    for conflict in index.conflicts()? {
+        let conflict = conflict?;
+        let conflict_path = conflict.our.as_ref().or(conflict.their.as_ref()).or(conflict.ancestor.as_ref()).map(|e| e.path.clone());
+        if conflict_path.as_deref() == Some(path.as_bytes()) {
+            entry_opt = Some(match side {
+                ConflictSide::Ours => conflict.our,
+                ConflictSide::Theirs => conflict.their,
+            });
+            break;
+        }
+    }
+    let entry_opt = match entry_opt {
+        Some(e) => e,
+        None => bail!("{} is not conflicted.", path),
+    };
+
+    match entry_opt {
+        Some(_) => {
+            // Checking the side out (rather than writing its blob) goes through filters and line endings and keeps its mode.
+            let mut checkout_builder = CheckoutBuilder::new();
+            checkout_builder.force().path(path);
+            match side {
+                ConflictSide::Ours => checkout_builder.use_ours(true),
+                ConflictSide::Theirs => checkout_builder.use_theirs(true),
+            };
+            repo.checkout_index(Some(&mut index), Some(&mut checkout_builder))?;
+            // Adding the path also clears its conflict entries.
+            index.add_path(Path::new(path))?;
+        },
+        // The chosen side deleted the file.
+        None => {
+            let workdir = repo.workdir().ok_or(Error::msg("Repository has no working directory!"))?;
+            let file_path = workdir.join(path);
+            if file_path.exists() {
+                std::fs::remove_file(&file_path)?;
+            }
+            index.remove_path(Path::new(path))?;
+        },
+    };
+    index.write()?;
+    Ok(())
+}
+
+fn get_diff_paths(diff: &Diff) -> Result<Vec<String>> {
+    let mut paths = vec![];
+    for delta in diff.deltas() {
+        for path in [delta.old_file().path(), delta.new_file().path()].into_iter().flatten() {
+            paths.push(String::from(path.to_str().ok_or(Error::msg("File Path has invalid UTF-8!"))?));
+        }
+    }
+    Ok(paths)
+}
+
+// Gets the paths where the index differs from the commit, including any that are conflicted.
+fn get_paths_changed_in_index(repo: &Repository, commit: &Commit) -> Result<Vec<String>> {
+    let index = git_utils::get_index(repo)?;
+    let diff = repo.diff_tree_to_index(Some(&commit.tree()?), Some(&index), None)?;
+    let mut paths = get_diff_paths(&diff)?;
+    for conflict in index.conflicts()? {
+        let conflict = conflict?;
+        for entry in [conflict.ancestor, conflict.our, conflict.their].into_iter().flatten() {
+            paths.push(String::from_utf8(entry.path).map_err(|_| Error::msg("File Path has invalid UTF-8!"))?);
+        }
+    }
+    paths.sort();
+    paths.dedup();
+    Ok(paths)
+}
+
+// Puts the paths back to how they are in the commit in both the index and the working directory, and leaves everything
+// else alone so unrelated local changes survive.
+fn git_restore_paths(repo: &Repository, commit: &Commit, paths: &[String]) -> Result<()> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+    // This also drops any conflicts at the paths.
+    repo.reset_default(Some(commit.as_object()), paths)?;
+    // Files the commit doesn't have are untracked after the reset above, so they're removed here.
+    let mut checkout_builder = CheckoutBuilder::new();
+    checkout_builder.force().remove_untracked(true);
+    for path in paths {
+        checkout_builder.path(path);
+    }
+    repo.checkout_tree(commit.as_object(), Some(&mut checkout_builder))?;
+    Ok(())
+}
+
+// Like git merge --abort, this only undoes the paths the merge changed. The merge refuses to start with staged changes,
+// so everything that differs between HEAD and the index came from it.
+pub fn git_abort_merge(repo: &Repository) -> Result<()> {
+    if let Some(head_commit) = git_utils::get_head_commit(repo)? {
+        let paths = get_paths_changed_in_index(repo, &head_commit)?;
+        git_restore_paths(repo, &head_commit, &paths)?;
+    }
+    git_cleanup_merge_state(repo)
+}
+
+pub fn git_apply_patch(repo: &Repository, patch_text: &str, location: ApplyLocation) -> Result<()> {
+    let diff = Diff::from_buffer(patch_text.as_bytes())?;
+    repo.apply(&diff, location, None)?;
+    Ok(())
+}
+
+pub fn git_stage_lines(repo: &Repository, file_diff: &FileDiff, selected_lines: &HashSet<(usize, usize)>) -> Result<()> {
+    if let Some(patch_text) = git_utils::build_partial_patch(file_diff, selected_lines, false) {
+        git_apply_patch(repo, &patch_text, ApplyLocation::Index)?;
+    }
+    Ok(())
+}
+
+pub fn git_unstage_lines(repo: &Repository, file_diff: &FileDiff, selected_lines: &HashSet<(usize, usize)>) -> Result<()> {
+    if let Some(patch_text) = git_utils::build_partial_patch(file_diff, selected_lines, true) {
+        git_apply_patch(repo, &patch_text, ApplyLocation::Index)?;
+    }
+    Ok(())
+}
+
+pub fn git_discard_lines(repo: &Repository, file_diff: &FileDiff, selected_lines: &HashSet<(usize, usize)>) -> Result<()> {
+    if let Some(patch_text) = git_utils::build_partial_patch(file_diff, selected_lines, true) {
+        git_apply_patch(repo, &patch_text, ApplyLocation::WorkDir)?;
+    }
+    Ok(())
+}
+
+pub fn git_checkout_ref(repo: &Repository, ref_name: &str) -> Result<()> {
+    let reference = repo.find_reference(ref_name)?;
+    let commit = reference.peel_to_commit()?;
+    // The default checkout strategy is safe, so this won't overwrite any local changes.
+    repo.checkout_tree(commit.as_object(), None)?;
+    if reference.is_branch() {
+        repo.set_head(ref_name)?;
+    } else {
+        repo.set_head_detached(commit.id())?;
+    }
+    Ok(())
+}
+
+pub fn git_create_branch(repo: &Repository, branch_name: &str, start_ref_name: &str, checkout: bool) -> Result<()> {
+    let start_commit = repo.find_reference(start_ref_name)?.peel_to_commit()?;
+    let branch = repo.branch(branch_name, &start_commit, false)?;
+    if checkout {
+        let ref_name = branch.get().name().ok_or(Error::msg("Branch Name has invalid UTF-8!"))?;
+        git_checkout_ref(repo, ref_name)?;
+    }
+    Ok(())
+}
+
+pub fn git_rename_branch(repo: &Repository, ref_name: &str, new_branch_name: &str) -> Result<()> {
+    let mut branch = git_utils::get_local_branch(repo, ref_name)?;
+    branch.rename(new_branch_name, false)?;
+    Ok(())
+}
+
+pub fn git_delete_branch(repo: &Repository, ref_name: &str, force: bool) -> Result<()> {
+    let mut branch = git_utils::get_local_branch(repo, ref_name)?;
+    if branch.is_head() {
+        bail!("Can't delete the branch that's currently checked out.");
+    }
+    if !force {
+        // A branch is merged if its tip is reachable from HEAD.
+        let branch_oid = branch.get().peel_to_commit()?.id();
+        let is_merged = match git_utils::get_head_commit(repo)? {
+            Some(head_commit) => head_commit.id() == branch_oid || repo.graph_descendant_of(head_commit.id(), branch_oid)?,
+            None => false,
+        };
+        if !is_merged {
+            bail!("The branch is not fully merged into the current branch. Use Force Delete if you're sure you want to delete it.");
+        }
+    }
+    branch.delete()?;
+    Ok(())
+}
+
+pub fn git_set_upstream(repo: &Repository, ref_name: &str, upstream_shorthand_opt: Option<&str>) -> Result<()> {
+    let mut branch = git_utils::get_local_branch(repo, ref_name)?;
+    branch.set_upstream(upstream_shorthand_opt)?;
+    Ok(())
+}
+
+pub fn git_checkout_remote_branch(repo: &Repository, remote_ref_name: &str) -> Result<()> {
+    let remote_ref = repo.find_reference(remote_ref_name)?;
+    let remote_shorthand = remote_ref.shorthand().ok_or(Error::msg("Branch Name has invalid UTF-8!"))?;
+    let remote_name_buf = repo.branch_remote_name(remote_ref_name)?;
+    let remote_name = remote_name_buf.as_str().ok_or(Error::msg("Remote Name has invalid UTF-8!"))?;
+    // e.g. "origin/feature/thing" -> "feature/thing"
+    let local_branch_name = remote_shorthand.strip_prefix(&format!("{}/", remote_name)).unwrap_or(remote_shorthand);
+
+    let commit = remote_ref.peel_to_commit()?;
+    let mut branch = repo.branch(local_branch_name, &commit, false)?;
+    branch.set_upstream(Some(remote_shorthand))?;
+    let ref_name = branch.get().name().ok_or(Error::msg("Branch Name has invalid UTF-8!"))?;
+    git_checkout_ref(repo, ref_name)
+}
+
+// Pushes the refspecs as-is, failing if the remote rejects any of them.
+fn git_push_refspecs(remote: &mut Remote, refspecs: &[String], progress: &Arc<Mutex<OperationProgress>>) -> Result<()> {
+    let rejections: RefCell<Vec<String>> = RefCell::new(vec![]);
+    let operation_name = format!("Pushing To {}", remote.name().unwrap_or("(Invalid UTF-8 in Name)"));
+    let url = String::from(remote.pushurl().or(remote.url()).unwrap_or_default());
+    git_utils::with_certificate_prompt(progress, || {
+        let mut callbacks = git_utils::get_remote_callbacks(progress, &operation_name, &url, Direction::Push);
+        callbacks.push_update_reference(|ref_name, status_msg| {
+            if let Some(m) = status_msg {
+                rejections.borrow_mut().push(format!("{}: {}", ref_name, m));
+            }
+            Ok(())
+        });
+        let mut push_options = PushOptions::new();
+        push_options.remote_callbacks(callbacks);
+        remote.push(refspecs, Some(&mut push_options))
+    })?;
+    let rejections = rejections.borrow();
+    if !rejections.is_empty() {
+        bail!("Error(s) during push:\n{}", rejections.join("\n"));
+    }
+    Ok(())
+}
+
+pub fn git_delete_remote_branch(repo: &Repository, remote_ref_name: &str, progress: &Arc<Mutex<OperationProgress>>) -> Result<()> {
+    let remote_name_buf = repo.branch_remote_name(remote_ref_name)?;
+    let remote_name = remote_name_buf.as_str().ok_or(Error::msg("Remote Name has invalid UTF-8!"))?;
+    let mut remote = repo.find_remote(remote_name)?;
+
+    // Find which branch on the remote this tracks so it can be deleted there.
+    let mut remote_branch_ref_name_opt = None;
+    for refspec in remote.refspecs() {
+        if refspec.direction() == Direction::Fetch && refspec.dst_matches(remote_ref_name) {
+            let buf = refspec.rtransform(remote_ref_name)?;
+            remote_branch_ref_name_opt = Some(String::from(buf.as_str().ok_or(Error::msg("Branch Name has invalid UTF-8!"))?));
+        }
+    }
+    let remote_branch_ref_name = match remote_branch_ref_name_opt {
+        Some(r) => r,
+        None => bail!("Couldn't find which branch on the remote {} tracks.", remote_ref_name),
+    };
+
+    git_push_refspecs(&mut remote, &[format!(":{}", remote_branch_ref_name)], progress)?;
+
+    // The remote tracking branch should be gone after the push, but make sure.
+    if let Ok(mut remote_ref) = repo.find_reference(remote_ref_name) {
+        remote_ref.delete()?;
+    }
+    Ok(())
+}
+
+fn git_rebase_commit(rebase: &mut Rebase, committer: &Signature) -> Result<()> {
+    match rebase.commit(None, committer, None) {
+        Ok(_) => Ok(()),
+        // The step's changes are already upstream, so there's nothing to commit and it's just dropped.
+        Err(e) if e.code() == ErrorCode::Applied => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Applies and commits each remaining step, stopping at the first one that conflicts. Returns true if the rebase finished.
+fn git_rebase_run(repo: &Repository, rebase: &mut Rebase, is_current_step_applied: bool) -> Result<bool> {
+    let committer = git_utils::get_signature(repo, None)?;
+    if is_current_step_applied {
+        git_rebase_commit(rebase, &committer)?;
+    }
+    while let Some(operation) = rebase.next() {
+        operation?;
+        if git_utils::get_index(repo)?.has_conflicts() {
+            return Ok(false);
+        }
+        git_rebase_commit(rebase, &committer)?;
+    }
+    rebase.finish(Some(&committer))?;
+    Ok(true)
+}
+
+// Commits the step that's been applied to the index according to its action.
+fn git_interactive_rebase_commit(repo: &Repository, action: RebaseAction, oid: Oid) -> Result<()> {
+    let commit = repo.find_commit(oid)?;
+    let head_commit = git_utils::get_head_commit(repo)?.ok_or(Error::msg("There's no commit to apply onto!"))?;
+    let tree = repo.find_tree(git_utils::get_index(repo)?.write_tree()?)?;
+    let committer = git_utils::get_signature(repo, None)?;
+    match action {
+        RebaseAction::Squash | RebaseAction::Fixup => {
+            // Fold this step into the commit before it by amending HEAD with the applied changes.
+            let head_message = String::from_utf8_lossy(head_commit.message_bytes()).into_owned();
+            let message = match action {
+                RebaseAction::Squash => format!("{}\n\n{}", head_message.trim_end(), String::from_utf8_lossy(commit.message_bytes())),
+                _ => head_message,
+            };
+            head_commit.amend(Some("HEAD"), None, Some(&committer), None, Some(&message), Some(&tree))?;
+        },
+        _ => {
+            // Like git, the step is dropped if its changes are already there.
+            if tree.id() == head_commit.tree_id() {
+                return Ok(());
+            }
+            let message = match action {
+                RebaseAction::Reword => std::fs::read_to_string(InteractiveRebase::get_message_path(repo, oid))?,
+                _ => String::from_utf8_lossy(commit.message_bytes()).into_owned(),
+            };
+            repo.commit(Some("HEAD"), &commit.author(), &committer, &message, &tree, &[&head_commit])?;
+        },
+    };
+    Ok(())
+}
+
+// Applies and commits each remaining step, stopping at the first one that conflicts or needs editing. Returns true if
+// the rebase finished.
+fn git_interactive_rebase_run(repo: &Repository, mut rebase: InteractiveRebase) -> Result<bool> {
+    while let Some(&(action, oid)) = rebase.remaining_steps.first() {
+        match rebase.current_step_state {
+            RebaseStepState::Pending => {
+                // This is saved before each step so that if one fails, the ones before it can still be aborted.
+                rebase.write(repo)?;
+                let res = repo.cherrypick(&repo.find_commit(oid)?, None);
+                // The rebase keeps track of the step itself, so it shouldn't look like a cherry-pick is in progress.
+                // If nothing was applied (e.g. a local change was in the way), this also leaves the step to be tried again.
+                git_cleanup_merge_state(repo)?;
+                res?;
+                rebase.current_step_state = RebaseStepState::Applied;
+                if git_utils::get_index(repo)?.has_conflicts() {
+                    rebase.write(repo)?;
+                    return Ok(false);
+                }
+            },
+            RebaseStepState::Applied => {
+                git_interactive_rebase_commit(repo, action, oid)?;
+                rebase.current_step_state = RebaseStepState::Committed;
+                if action == RebaseAction::Edit {
+                    rebase.write(repo)?;
+                    return Ok(false);
+                }
+            },
+            RebaseStepState::Committed => {
+                rebase.remaining_steps.remove(0);
+                rebase.current_step_state = RebaseStepState::Pending;
+            },
+        };
+    }
+
+    // The branch is only moved now that everything's been applied.
+    let head_oid = repo.refname_to_id("HEAD")?;
+    if rebase.head_name != "HEAD" {
+        repo.reference(&rebase.head_name, head_oid, true, &format!("oxidized_git rebase (finish): {} onto {}", rebase.head_name, rebase.onto_oid))?;
+        repo.set_head(&rebase.head_name)?;
+    }
+    InteractiveRebase::remove(repo)?;
+    Ok(true)
+}
+
+pub fn git_interactive_rebase(repo: &Repository, onto_oid: Oid, todo: &[RebaseTodoItem]) -> Result<bool> {
+    if git_utils::is_operation_in_progress(repo)? {
+        bail!("Please finish or abort the operation that's in progress first.");
+    }
+    if git_utils::has_tracked_changes(repo)? {
+        bail!("Please commit or stash your changes before rebasing.");
+    }
+    let kept_items: Vec<&RebaseTodoItem> = todo.iter().filter(|item| item.action != RebaseAction::Drop).collect();
+    if let Some(first_item) = kept_items.first() {
+        if first_item.action == RebaseAction::Squash || first_item.action == RebaseAction::Fixup {
+            bail!("The first commit can't be squashed or fixed up since there's nothing before it to fold into.");
+        }
+    }
+    let head_ref = repo.find_reference("HEAD")?;
+    let head_name = String::from(head_ref.symbolic_target().unwrap_or("HEAD"));
+    let head_commit = git_utils::get_head_commit(repo)?.ok_or(Error::msg("The current branch doesn't have any commits yet!"))?;
+
+    // Each step is applied on top of a detached HEAD so the branch stays where it is until the rebase is done.
+    let onto_commit = repo.find_commit(onto_oid)?;
+    repo.checkout_tree(onto_commit.as_object(), None)?;
+    repo.set_head_detached(onto_oid)?;
+    let rebase = InteractiveRebase {
+        head_name,
+        orig_head_oid: head_commit.id(),
+        onto_oid,
+        total_steps: kept_items.len(),
+        remaining_steps: kept_items.iter().map(|item| (item.action, item.oid)).collect(),
+        current_step_state: RebaseStepState::Pending,
+    };
+    rebase.write(repo)?;
+    for item in kept_items.iter().filter(|item| item.action == RebaseAction::Reword) {
+        std::fs::write(InteractiveRebase::get_message_path(repo, item.oid), &item.message)?;
+    }
+    git_interactive_rebase_run(repo, rebase)
+}
+
+pub fn git_rebase_continue(repo: &Repository) -> Result<()> {
+    if git_utils::get_index(repo)?.has_conflicts() {
+        bail!("Please resolve all conflicts before continuing the rebase.");
+    }
+    if let Some(rebase) = InteractiveRebase::read(repo)? {
+        git_interactive_rebase_run(repo, rebase)?;
+        return Ok(());
+    }
+    let mut rebase = repo.open_rebase(None)?;
+    let is_current_step_applied = rebase.operation_current().is_some();
+    git_rebase_run(repo, &mut rebase, is_current_step_applied)?;
+    Ok(())
+}
+
+pub fn git_rebase_skip(repo: &Repository) -> Result<()> {
+    let rebase_opt = InteractiveRebase::read(repo)?;
+    // Throw away the current step's changes. Only the paths it touched are put back so anything else is left alone.
+    // An interactive rebase's step has nothing to throw away if it's already committed for editing or failed to apply.
+    let is_current_step_applied = rebase_opt.as_ref().is_none_or(|rebase| rebase.current_step_state == RebaseStepState::Applied);
+    if is_current_step_applied {
+        if let Some(head_commit) = git_utils::get_head_commit(repo)? {
+            let paths = get_paths_changed_in_index(repo, &head_commit)?;
+            git_restore_paths(repo, &head_commit, &paths)?;
+        }
+    }
+    match rebase_opt {
+        Some(mut rebase) => {
+            if !rebase.remaining_steps.is_empty() {
+                rebase.remaining_steps.remove(0);
+            }
+            rebase.current_step_state = RebaseStepState::Pending;
+            git_interactive_rebase_run(repo, rebase)?;
+        },
+        None => {
+            git_rebase_run(repo, &mut repo.open_rebase(None)?, false)?;
+        },
+    };
+    Ok(())
+}
+
+pub fn git_rebase_abort(repo: &Repository) -> Result<()> {
+    let rebase = match InteractiveRebase::read(repo)? {
+        Some(rebase) => rebase,
+        None => {
+            repo.open_rebase(None)?.abort()?;
+            return Ok(());
+        },
+    };
+    // The branch never moved, so go back to it and put back only what the rebase changed, the same way aborting a
+    // cherry-pick does.
+    let orig_head_commit = repo.find_commit(rebase.orig_head_oid)?;
+    if let Some(head_commit) = git_utils::get_head_commit(repo)? {
+        let diff = repo.diff_tree_to_tree(Some(&orig_head_commit.tree()?), Some(&head_commit.tree()?), None)?;
+        let mut paths = get_diff_paths(&diff)?;
+        paths.extend(get_paths_changed_in_index(repo, &head_commit)?);
+        paths.sort();
+        paths.dedup();
+        match rebase.head_name.as_str() {
+            "HEAD" => repo.set_head_detached(orig_head_commit.id())?,
+            head_name => repo.set_head(head_name)?,
+        };
+        git_restore_paths(repo, &orig_head_commit, &paths)?;
+    }
+    InteractiveRebase::remove(repo)
+}
+
+// Commits the cherry-pick or revert that's been applied to the index, using the message libgit2 prepared for it.
+fn git_sequence_commit(repo: &Repository, kind: SequenceKind, commit: &Commit) -> Result<()> {
+    let tree = repo.find_tree(git_utils::get_index(repo)?.write_tree()?)?;
+    let head_commit = git_utils::get_head_commit(repo)?.ok_or(Error::msg("There's no commit to apply onto!"))?;
+    // Like git, skip anything that ends up not changing anything.
+    if head_commit.tree_id() != tree.id() {
+        let message = repo.message()?;
+        let committer = git_utils::get_signature(repo, None)?;
+        // Cherry-picks keep the original author, but a revert is new work.
+        let author = match kind {
+            SequenceKind::CherryPick => commit.author().to_owned(),
+            SequenceKind::Revert => committer.clone(),
+        };
+        repo.commit(Some("HEAD"), &author, &committer, &message, &tree, &[&head_commit])?;
+    }
+    repo.cleanup_state()?;
+    Ok(())
+}
+
+// Applies each commit in order, stopping at the first one that conflicts. Returns true if everything was applied.
+fn git_sequence_run(repo: &Repository, mut sequence: Sequence) -> Result<bool> {
+    while let Some(&oid) = sequence.remaining_oids.first() {
+        // This is saved before each step so that if one fails, the ones before it can still be aborted.
+        sequence.write(repo)?;
+        let commit = repo.find_commit(oid)?;
+        // The mainline parent only applies to merge commits, libgit2 rejects it for anything else.
+        let mainline = if commit.parent_count() > 1 { sequence.mainline } else { 0 };
+        if commit.parent_count() > 1 && mainline == 0 {
+            bail!("{} is a merge commit, so a mainline parent has to be picked.", commit.id());
+        }
+        let res = match sequence.kind {
+            SequenceKind::CherryPick => repo.cherrypick(&commit, Some(CherrypickOptions::new().mainline(mainline))),
+            SequenceKind::Revert => repo.revert(&commit, Some(RevertOptions::new().mainline(mainline))),
+        };
+        if let Err(e) = res {
+            // Nothing was applied (e.g. a local change was in the way), so drop what libgit2 saved for it to leave the
+            // commit to be tried again when continuing.
+            git_cleanup_merge_state(repo)?;
+            return Err(e.into());
+        }
+        sequence.remaining_oids.remove(0);
+        if git_utils::get_index(repo)?.has_conflicts() {
+            sequence.write(repo)?;
+            return Ok(false);
+        }
+        git_sequence_commit(repo, sequence.kind, &commit)?;
+    }
+    Sequence::remove(repo)?;
+    Ok(true)
+}
+
+fn git_sequence_start(repo: &Repository, kind: SequenceKind, oids: &[Oid], mainline: u32) -> Result<bool> {
+    if git_utils::is_operation_in_progress(repo)? {
+        bail!("Please finish or abort the operation that's in progress first.");
+    }
+    if git_utils::has_staged_changes(repo)? {
+        bail!("Please commit or unstage your staged changes first.");
+    }
+    let head_commit = git_utils::get_head_commit(repo)?.ok_or(Error::msg("The current branch doesn't have any commits yet!"))?;
+    let sequence = Sequence {
+        kind,
+        mainline,
+        orig_head_oid: head_commit.id(),
+        remaining_oids: oids.to_vec(),
+    };
+    git_sequence_run(repo, sequence)
+}
+
+// The oids should be in the order to apply them, i.e. oldest first.
+pub fn git_cherry_pick(repo: &Repository, oids: &[Oid], mainline: u32) -> Result<bool> {
+    git_sequence_start(repo, SequenceKind::CherryPick, oids, mainline)
+}
+
+// The oids should be in the order to apply them, i.e. newest first.
+pub fn git_revert(repo: &Repository, oids: &[Oid], mainline: u32) -> Result<bool> {
+    git_sequence_start(repo, SequenceKind::Revert, oids, mainline)
+}
+
+pub fn git_sequence_continue(repo: &Repository) -> Result<bool> {
+    if git_utils::get_index(repo)?.has_conflicts() {
+        bail!("Please resolve all conflicts before continuing.");
+    }
+    let kind = git_utils::get_sequence_kind(repo)?.ok_or(Error::msg("There's no cherry-pick or revert in progress!"))?;
+    // This is empty if the user already committed the stopped commit themselves.
+    if let Some(oid) = git_utils::get_sequence_head_oid(repo, kind)? {
+        git_sequence_commit(repo, kind, &repo.find_commit(oid)?)?;
+    }
+    match Sequence::read(repo)? {
+        Some(sequence) => git_sequence_run(repo, sequence),
+        None => Ok(true),
+    }
+}
+
+// Goes back to where the branch was before any of the commits were applied. The sequence refuses to start with staged
+// changes and the commits can't be applied over unstaged ones, so only the paths they touched are put back.
+pub fn git_sequence_abort(repo: &Repository) -> Result<()> {
+    if let Some(head_commit) = git_utils::get_head_commit(repo)? {
+        let orig_head_commit = match Sequence::read(repo)? {
+            Some(sequence) => repo.find_commit(sequence.orig_head_oid)?,
+            None => head_commit.clone(),
+        };
+        let diff = repo.diff_tree_to_tree(Some(&orig_head_commit.tree()?), Some(&head_commit.tree()?), None)?;
+        let mut paths = get_diff_paths(&diff)?;
+        paths.extend(get_paths_changed_in_index(repo, &head_commit)?);
+        paths.sort();
+        paths.dedup();
+
+        // The branch is moved first so a failure part way through can't leave it pointing at the applied commits with
+        // their changes already gone from the working directory.
+        if orig_head_commit.id() != head_commit.id() {
+            let head_ref = repo.find_reference("HEAD")?;
+            let ref_name = head_ref.symbolic_target().unwrap_or("HEAD");
+            let reflog_msg = format!("oxidized_git {}: abort", git_utils::get_sequence_kind(repo)?.map(|kind| kind.get_label()).unwrap_or("sequence"));
+            repo.reference(ref_name, orig_head_commit.id(), true, &reflog_msg)?;
+        }
+        git_restore_paths(repo, &orig_head_commit, &paths)?;
+    }
+    repo.cleanup_state()?;
+    Sequence::remove(repo)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ResetMode {
+    Soft,
+    Mixed,
+    Hard,
+}
+
+impl ResetMode {
+    pub fn get_label(&self) -> &'static str {
+        match self {
+            ResetMode::Soft => "soft",
+            ResetMode::Mixed => "mixed",
+            ResetMode::Hard => "hard",
+        }
+    }
+}
+
+pub fn git_reset(repo: &Repository, oid: Oid, reset_mode: ResetMode) -> Result<()> {
+    let commit = repo.find_commit(oid)?;
+    // The ref is moved first so that if it can't be, the index and working directory haven't been touched yet.
+    let head_ref = repo.find_reference("HEAD")?;
+    let ref_name = head_ref.symbolic_target().unwrap_or("HEAD");
+    let reflog_msg = format!("oxidized_git reset --{}: moving {} to {}", reset_mode.get_label(), ref_name, oid);
+    let old_oid_opt = repo.refname_to_id(ref_name).ok();
+    repo.reference(ref_name, oid, true, &reflog_msg)?;
+    // libgit2 doesn't log anything if the ref doesn't move (e.g. a hard reset to HEAD), but git still does.
+    if old_oid_opt == Some(oid) {
+        let signature = git_utils::get_signature(repo, None)?;
+        let mut log_ref_names = vec![ref_name];
+        if ref_name != "HEAD" {
+            log_ref_names.push("HEAD");
+        }
+        for log_ref_name in log_ref_names {
+            let mut reflog = repo.reflog(log_ref_name)?;
+            reflog.append(oid, &signature, Some(&reflog_msg))?;
+            reflog.write()?;
+        }
+    }
+
+    if reset_mode == ResetMode::Hard {
+        // Forcing the checkout is what throws away the uncommitted changes. It goes by the index from before the reset,
+        // so files that were tracked but aren't in the commit get removed too.
+        repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().force()))?;
+    }
+    if reset_mode != ResetMode::Soft {
+        let mut index = git_utils::get_index(repo)?;
+        index.read_tree(&commit.tree()?)?;
+        index.write()?;
+        // Like git, a mixed or hard reset also cancels any merge, cherry-pick, etc. that's in progress, but not a rebase.
+        git_cleanup_merge_state(repo)?;
+    }
+    Ok(())
+}
+
+pub fn git_create_tag(repo: &Repository, oid: Oid, tag_name: &str, message_opt: Option<&str>, tagger_opt: Option<(&str, &str)>) -> Result<()> {
+    if !Reference::is_valid_name(&format!("refs/tags/{}", tag_name)) {
+        bail!("'{}' is not a valid tag name.", tag_name);
+    }
+    let target = repo.find_object(oid, None)?;
+    let res = match message_opt {
+        Some(message) => {
+            let tagger = git_utils::get_signature(repo, tagger_opt)?;
+            repo.tag(tag_name, &target, &tagger, &message_prettify(message, None)?, false)
+        },
+        None => repo.tag_lightweight(tag_name, &target, false),
+    };
+    match res {
+        Ok(_) => Ok(()),
+        Err(e) if e.code() == ErrorCode::Exists => bail!("A tag named '{}' already exists.", tag_name),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn git_delete_tag(repo: &Repository, ref_name: &str) -> Result<()> {
+    repo.find_reference(ref_name)?.delete()?;
+    Ok(())
+}
+
+// Pushes a single tag, or every local tag if ref_name_opt is None.
+pub fn git_push_tags(repo: &Repository, remote_name: &str, ref_name_opt: Option<&str>, progress: &Arc<Mutex<OperationProgress>>) -> Result<()> {
+    let tag_ref_names = match ref_name_opt {
+        Some(ref_name) => vec![String::from(ref_name)],
+        None => {
+            let mut tag_ref_names = vec![];
+            for tag_ref in &git_utils::get_all_refs(repo)?[2] {
+                tag_ref_names.push(String::from(tag_ref.name().ok_or(Error::msg("Tag Name has invalid UTF-8!"))?));
+            }
+            tag_ref_names
+        },
+    };
+    if tag_ref_names.is_empty() {
+        bail!("There are no tags to push.");
+    }
+    let refspecs: Vec<String> = tag_ref_names.iter().map(|ref_name| format!("{}:{}", ref_name, ref_name)).collect();
+    git_push_refspecs(&mut repo.find_remote(remote_name)?, &refspecs, progress)
+}
+
+pub fn git_delete_remote_tag(repo: &Repository, remote_name: &str, ref_name: &str, progress: &Arc<Mutex<OperationProgress>>) -> Result<()> {
+    git_push_refspecs(&mut repo.find_remote(remote_name)?, &[format!(":{}", ref_name)], progress)
+}
+
+// git2's stash functions need a mutable repo, so they're run on a separate handle to the same repo.
+fn open_stash_repo(repo: &Repository) -> Result<Repository> {
+    Ok(Repository::open(repo.path())?)
+}
+
+pub fn git_stash_save(repo: &Repository, message: &str, include_untracked: bool, keep_index: bool) -> Result<()> {
+    let mut stash_repo = open_stash_repo(repo)?;
+    let signature = git_utils::get_signature(repo, None)?;
+    let mut flags = StashFlags::DEFAULT;
+    if include_untracked {
+        flags |= StashFlags::INCLUDE_UNTRACKED;
+    }
+    if keep_index {
+        flags |= StashFlags::KEEP_INDEX;
+    }
+    let message_opt = if message.trim().is_empty() { None } else { Some(message.trim()) };
+    match stash_repo.stash_save2(&signature, message_opt, Some(flags)) {
+        Ok(_) => Ok(()),
+        Err(e) if e.code() == ErrorCode::NotFound => bail!("There are no changes to stash."),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn git_stash_apply_with_index(stash_repo: &mut Repository, stash_index: usize, is_pop: bool, reinstantiate_index: bool) -> Result<(), git2::Error> {
+    let mut apply_options = StashApplyOptions::new();
+    if reinstantiate_index {
+        apply_options.reinstantiate_index();
+    }
+    if is_pop {
+        stash_repo.stash_pop(stash_index, Some(&mut apply_options))
+    } else {
+        stash_repo.stash_apply(stash_index, Some(&mut apply_options))
+    }
+}
+
+pub fn git_stash_apply(repo: &Repository, stash_index: usize, is_pop: bool) -> Result<()> {
+    let mut stash_repo = open_stash_repo(repo)?;
+    // Try to restore what was staged too, the same as "git stash apply --index". If the staged changes don't apply
+    // cleanly, fall back to plain "git stash apply", which leaves all the changes unstaged.
+    let res = match git_stash_apply_with_index(&mut stash_repo, stash_index, is_pop, true) {
+        Err(e) if e.code() == ErrorCode::Conflict => git_stash_apply_with_index(&mut stash_repo, stash_index, is_pop, false),
+        res => res,
+    };
+    match res {
+        Ok(()) => Ok(()),
+        Err(e) if e.code() == ErrorCode::Conflict => bail!("The stash can't be applied because it conflicts with your changes. Commit or stash them first."),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn git_stash_drop(repo: &Repository, stash_index: usize) -> Result<()> {
+    open_stash_repo(repo)?.stash_drop(stash_index)?;
+    Ok(())
+}
+
+pub fn git_fetch(repo: &Repository, progress: &Arc<Mutex<OperationProgress>>) -> Result<()> {
+    for remote_name in git_utils::get_remote_names(repo)? {
+        git_fetch_remote(repo, &remote_name, progress)?;
+    }
+    Ok(())
+}
+
+pub fn git_fetch_remote(repo: &Repository, remote_name: &str, progress: &Arc<Mutex<OperationProgress>>) -> Result<()> {
+    let mut remote = repo.find_remote(remote_name)?;
+    let url = String::from(remote.url().unwrap_or_default());
     let empty_refspecs: &[String] = &[];
-    for remote_string_opt in remote_string_array.iter() {
-        let remote_string = remote_string_opt.ok_or(Error::msg("Remote Name has invalid UTF-8!"))?;
-        let mut remote = repo.find_remote(remote_string)?;
+    git_utils::with_certificate_prompt(progress, || {
         let mut fetch_options = FetchOptions::new();
         fetch_options.download_tags(AutotagOption::All);
         fetch_options.prune(FetchPrune::On);
-        fetch_options.remote_callbacks(git_utils::get_remote_callbacks());
-        remote.fetch(empty_refspecs, Some(&mut fetch_options), None)?;
+        fetch_options.remote_callbacks(git_utils::get_remote_callbacks(progress, &format!("Fetching {}", remote_name), &url, Direction::Fetch));
+        remote.fetch(empty_refspecs, Some(&mut fetch_options), None)
+    })
+}
+
+// Deletes remote tracking branches that no longer exist on the remote without fetching anything new.
+pub fn git_prune_remote(repo: &Repository, remote_name: &str, progress: &Arc<Mutex<OperationProgress>>) -> Result<()> {
+    let mut remote = repo.find_remote(remote_name)?;
+    let operation_name = format!("Pruning {}", remote_name);
+    let url = String::from(remote.url().unwrap_or_default());
+    git_utils::with_certificate_prompt(progress, || {
+        let mut connection = remote.connect_auth(Direction::Fetch, Some(git_utils::get_remote_callbacks(progress, &operation_name, &url, Direction::Fetch)), None)?;
+        connection.remote().prune(Some(git_utils::get_remote_callbacks(progress, &operation_name, &url, Direction::Fetch)))
+    })
+}
+
+pub fn git_add_remote(repo: &Repository, remote_name: &str, url: &str) -> Result<()> {
+    if !Remote::is_valid_name(remote_name) {
+        bail!("'{}' is not a valid remote name.", remote_name);
+    }
+    match repo.remote(remote_name, url) {
+        Ok(_) => Ok(()),
+        Err(e) if e.code() == ErrorCode::Exists => bail!("A remote named '{}' already exists.", remote_name),
+        Err(e) => Err(e.into()),
     }
+}
+
+pub fn git_rename_remote(repo: &Repository, remote_name: &str, new_remote_name: &str) -> Result<()> {
+    if !Remote::is_valid_name(new_remote_name) {
+        bail!("'{}' is not a valid remote name.", new_remote_name);
+    }
+    // This returns any non-default refspecs that couldn't be updated, which are left as they were, same as git.
+    repo.remote_rename(remote_name, new_remote_name)?;
     Ok(())
 }
 
-pub fn git_pull(repo: &Repository) -> Result<()> {
+pub fn git_remove_remote(repo: &Repository, remote_name: &str) -> Result<()> {
+    repo.remote_delete(remote_name)?;
+    Ok(())
+}
+
+// An empty push URL means pushes use the regular URL.
+pub fn git_set_remote_urls(repo: &Repository, remote_name: &str, url: &str, push_url: &str) -> Result<()> {
+    if url.is_empty() {
+        bail!("The remote URL can't be empty.");
+    }
+    repo.remote_set_url(remote_name, url)?;
+    repo.remote_set_pushurl(remote_name, if push_url.is_empty() { None } else { Some(push_url) })?;
+    Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PullStrategy {
+    Merge,
+    MergeNoFastForward,
+    Rebase,
+    FastForwardOnly,
+}
+
+pub enum PullOutcome {
+    Merge(MergeOutcome),
+    Rebased,
+    RebaseConflicts,
+}
+
+impl PullOutcome {
+    pub fn get_message(&self) -> &'static str {
+        match self {
+            PullOutcome::Merge(merge_outcome) => merge_outcome.get_message(),
+            PullOutcome::Rebased => "Rebased onto the upstream branch.",
+            PullOutcome::RebaseConflicts => "Stopped because of conflicts. Resolve them and then continue the rebase.",
+        }
+    }
+}
+
+fn is_config_value_true(value: &str) -> bool {
+    // Anything that isn't false means rebase, e.g. "merges" or "interactive".
+    !matches!(value.trim().to_lowercase().as_str(), "false" | "no" | "off" | "0" | "")
+}
+
+// Works out the strategy the same way git does: branch.<name>.rebase, then pull.rebase, then pull.ff and merge.ff.
+fn get_pull_strategy(repo: &Repository, branch_shorthand: &str) -> Result<PullStrategy> {
+    let config = repo.config()?.snapshot()?;
+    let rebase_value_opt = config.get_string(&format!("branch.{}.rebase", branch_shorthand)).or_else(|_| config.get_string("pull.rebase")).ok();
+    if let Some(rebase_value) = rebase_value_opt {
+        if is_config_value_true(&rebase_value) {
+            return Ok(PullStrategy::Rebase);
+        }
+    }
+    let ff_value_opt = config.get_string("pull.ff").or_else(|_| config.get_string("merge.ff")).ok();
+    match ff_value_opt.as_deref().map(|v| v.trim().to_lowercase()).as_deref() {
+        Some("only") => Ok(PullStrategy::FastForwardOnly),
+        Some(v) if !is_config_value_true(v) => Ok(PullStrategy::MergeNoFastForward),
+        _ => Ok(PullStrategy::Merge),
+    }
+}
+
+pub fn git_pull(repo: &Repository, strategy_opt: Option<PullStrategy>, progress: &Arc<Mutex<OperationProgress>>) -> Result<PullOutcome> {
+    if git_utils::is_operation_in_progress(repo)? {
+        bail!("Please finish or abort the operation that's in progress first.");
+    }
     // Fetch first to make sure everything's up to date.
-    git_fetch(repo)?;
+    git_fetch(repo, progress)?;
 
-    let mut local_ref = repo.head()?;
+    let local_ref = repo.head()?;
+    if !local_ref.is_branch() {
+        bail!("HEAD is not pointing to a local branch, cannot pull.");
+    }
     let local_shorthand = local_ref.shorthand().ok_or(Error::msg("Branch Name has invalid UTF-8!"))?;
     let local_branch = repo.find_branch(local_shorthand, BranchType::Local)?;
-
-    let remote_branch = local_branch.upstream()?;
+    let remote_branch = match local_branch.upstream() {
+        Ok(b) => b,
+        Err(e) if e.code() == ErrorCode::NotFound => bail!("{} has no upstream branch to pull from.", local_shorthand),
+        Err(e) => return Err(e.into()),
+    };
     let remote_ref = remote_branch.get();
-    let remote_target = match remote_ref.target() {
-        Some(oid) => oid,
-        None => bail!("Remote branch is not targeting a commit, cannot pull."),
+    let remote_refname = remote_ref.name().ok_or(Error::msg("Branch Name has invalid UTF-8!"))?;
+
+    let strategy = match strategy_opt {
+        Some(s) => s,
+        None => get_pull_strategy(repo, local_shorthand)?,
     };
-    let remote_ac = repo.find_annotated_commit(remote_target)?;
+    let merge_kind = match strategy {
+        PullStrategy::Merge => MergeKind::FastForwardIfPossible,
+        PullStrategy::MergeNoFastForward => MergeKind::NoFastForward,
+        PullStrategy::FastForwardOnly => MergeKind::FastForwardOnly,
+        PullStrategy::Rebase => {
+            let remote_ac = repo.reference_to_annotated_commit(remote_ref)?;
+            let (ma, _) = repo.merge_analysis(&[&remote_ac])?;
+            // There's nothing to replay if it's up to date or can fast-forward, so let merge handle those.
+            if !ma.is_normal() {
+                return Ok(PullOutcome::Merge(git_merge(repo, remote_refname, MergeKind::FastForwardOnly)?));
+            }
+            if git_utils::has_tracked_changes(repo)? {
+                bail!("Please commit or stash your changes before pulling with rebase.");
+            }
+            // This isn't in-memory, so if a step conflicts the rebase stays on disk for the user to continue.
+            let mut rebase = repo.rebase(None, None, Some(&remote_ac), None)?;
+            if git_rebase_run(repo, &mut rebase, false)? {
+                return Ok(PullOutcome::Rebased);
+            }
+            return Ok(PullOutcome::RebaseConflicts);
+        },
+    };
+    Ok(PullOutcome::Merge(git_merge(repo, remote_refname, merge_kind)?))
+}
 
-    let (ma, mp) = repo.merge_analysis(&[&remote_ac])?;
+pub fn git_push(repo: &Repository, force_with_lease: bool, progress: &Arc<Mutex<OperationProgress>>) -> Result<()> {
+    let head_ref = repo.head()?;
+    if !head_ref.is_branch() {
+        bail!("HEAD is not pointing to a local branch, cannot push.");
+    }
+    let local_refname = head_ref.name().ok_or(Error::msg("Branch Name has invalid UTF-8!"))?;
+    let local_shorthand = head_ref.shorthand().ok_or(Error::msg("Branch Name has invalid UTF-8!"))?;
+    if head_ref.target().is_none() {
+        bail!("Local branch is not targeting a commit, cannot push.");
+    }
 
-    if ma.is_none() {
-        bail!("Merge analysis indicates no merge is possible. If you're reading this, your repository may be corrupted.");
-    } else if ma.is_unborn() {
-        bail!("The HEAD of the current repository is “unborn” and does not point to a valid commit. No pull can be performed, but the caller may wish to simply set HEAD to the target commit(s).");
-    } else if ma.is_up_to_date() {
-        return Ok(());
-    } else if ma.is_fast_forward() && !mp.is_no_fast_forward() {
-        println!("Performing fast forward merge for pull!");
-        let commit = match remote_ref.target() {
-            Some(oid) => repo.find_commit(oid)?,
-            None => bail!("Remote branch has no target commit."),
-        };
-        let tree = commit.tree()?;
-        repo.checkout_tree(tree.as_object(), None)?;
-        local_ref.set_target(remote_target, "oxidized_git pull: setting new target for local ref")?;
-        return Ok(());
-    } else if ma.is_normal() && !mp.is_fastforward_only() {
-        println!("Performing rebase for pull!");
-        let mut rebase = repo.rebase(None, None, Some(&remote_ac), None)?;
-        let mut diff_in_branches = false;
-        for step in rebase.by_ref() {
-            step?;
-            if git_utils::has_conflicts(repo)? || git_utils::has_unstaged_changes(repo)? || git_utils::has_staged_changes(repo)? {
-                diff_in_branches = true;
-                break;
+    // Push to the upstream if there is one, otherwise push to a branch with the same name on the default remote.
+    let (remote_name, remote_refname, has_upstream) = match repo.branch_upstream_remote(local_refname) {
+        Ok(remote_name_buf) => {
+            let remote_name = remote_name_buf.as_str().ok_or(Error::msg("Remote Name has invalid UTF-8!"))?;
+            let remote_refname = repo.config()?.get_string(&format!("branch.{}.merge", local_shorthand))?;
+            (String::from(remote_name), remote_refname, true)
+        },
+        Err(e) if e.code() == ErrorCode::NotFound => (git_utils::get_default_remote_name(repo)?, String::from(local_refname), false),
+        Err(e) => return Err(e.into()),
+    };
+    let mut remote = repo.find_remote(&remote_name)?;
+
+    let mut refspec = format!("{}:{}", local_refname, remote_refname);
+    // The remote tracking branch holds where we last saw the remote ref. Zero means we've never seen it.
+    let mut expected_remote_oid = Oid::zero();
+    if force_with_lease {
+        refspec = format!("+{}", refspec);
+        if let Some(tracking_refname) = git_utils::get_remote_tracking_refname(&remote, &remote_refname)? {
+            if let Ok(tracking_ref) = repo.find_reference(&tracking_refname) {
+                if let Some(oid) = tracking_ref.target() {
+                    expected_remote_oid = oid;
+                }
             }
         }
-        if diff_in_branches {
-            rebase.abort()?;
-            bail!("Pull by rebase aborted because changes on local branch differ from remote branch!");
+    }
+
+    let url = String::from(remote.pushurl().or(remote.url()).unwrap_or_default());
+    let lease_error: RefCell<Option<String>> = RefCell::new(None);
+    let rejections: RefCell<Vec<String>> = RefCell::new(vec![]);
+    let push_res = git_utils::with_certificate_prompt(progress, || {
+        let mut callbacks = git_utils::get_remote_callbacks(progress, &format!("Pushing To {}", remote_name), &url, Direction::Push);
+        if force_with_lease {
+            callbacks.push_negotiation(|updates| {
+                for update in updates {
+                    // For each update, src is where the remote ref currently is.
+                    if update.src() != expected_remote_oid {
+                        let dst_refname = update.dst_refname().unwrap_or("(Invalid UTF-8 in Name)");
+                        *lease_error.borrow_mut() = Some(format!("Force push rejected: {} has moved on the remote since the last fetch (expected {}, found {}). Fetch and review the changes first.", dst_refname, expected_remote_oid, update.src()));
+                        return Err(git2::Error::from_str("Stale info for force push with lease."));
+                    }
+                }
+                Ok(())
+            });
         }
-        rebase.finish(None)?;
-        return Ok(());
-    } else if (ma.is_fast_forward() && mp.is_no_fast_forward()) || (ma.is_normal() && mp.is_fastforward_only()) {
-        bail!("It looks like a pull may be possible, but your MergePreference(s) are preventing it. If you have merge.ff or pull.ff set to 'only' or 'false', consider unsetting it by running 'git config --global --unset merge.ff' or 'git config --global --unset pull.ff'");
+        callbacks.push_update_reference(|ref_name, status_msg| {
+            if let Some(m) = status_msg {
+                rejections.borrow_mut().push(format!("{}: {}", ref_name, m));
+            }
+            Ok(())
+        });
+
+        let mut push_options = PushOptions::new();
+        push_options.remote_callbacks(callbacks);
+        remote.push(&[refspec.as_str()], Some(&mut push_options))
+    });
+    if let Some(m) = lease_error.borrow_mut().take() {
+        bail!(m);
+    }
+    push_res?;
+    let rejections = rejections.borrow();
+    if !rejections.is_empty() {
+        bail!("Error(s) during push:\n{}", rejections.join("\n"));
+    }
+
+    if !has_upstream {
+        let mut local_branch = repo.find_branch(local_shorthand, BranchType::Local)?;
+        let remote_branch_name = remote_refname.strip_prefix("refs/heads/").unwrap_or(&remote_refname);
+        local_branch.set_upstream(Some(&format!("{}/{}", remote_name, remote_branch_name)))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use git2::Oid;
+    use crate::backend::git_utils;
+    use crate::backend::git_utils::RebaseAction;
+    use crate::backend::test_repo::TestRepo;
+    use super::{git_abort_merge, git_rebase_abort, git_checkout_ref, git_cherry_pick, git_commit, git_interactive_rebase, git_merge, git_rebase_continue, git_rebase_run, git_rebase_skip, git_reset, git_resolve_conflict, git_sequence_abort, git_sequence_continue, git_stage, git_stash_apply, git_stash_save, ConflictSide, MergeKind, MergeOutcome, ResetMode};
+
+    #[test]
+    fn test_amend_at_rebase_edit_stop_and_continue() {
+        let fixture = TestRepo::new();
+        let c1 = fixture.commit_file("a.txt", "one\n", "c1");
+        let c2 = fixture.commit_file("b.txt", "two\n", "c2");
+        fixture.commit_file("c.txt", "three\n", "c3");
+        // Untracked files don't stop a rebase from starting.
+        fixture.write_file("untracked.txt", "untracked\n");
+
+        let mut todo = git_utils::get_rebase_todo(&fixture.repo, c2).unwrap();
+        todo[0].action = RebaseAction::Edit;
+        assert!(!git_interactive_rebase(&fixture.repo, c1, &todo).unwrap());
+        assert!(git_utils::get_rebase_status(&fixture.repo).unwrap().unwrap().is_edit_stop);
+
+        // Only amending is allowed, and trying anything else leaves the rebase alone.
+        fixture.write_file("b.txt", "two edited\n");
+        git_stage(&fixture.repo, &[String::from("b.txt")]).unwrap();
+        assert!(git_commit(&fixture.repo, "extra", false, None, None).is_err());
+        assert!(git_utils::is_rebase_edit_stop(&fixture.repo).unwrap());
+        git_commit(&fixture.repo, "c2 edited", true, None, None).unwrap();
+        assert!(git_utils::is_rebasing(&fixture.repo));
+
+        git_rebase_continue(&fixture.repo).unwrap();
+        assert!(!git_utils::is_rebasing(&fixture.repo));
+        assert_eq!(fixture.get_head_summaries(), vec!["c3", "c2 edited", "c1"]);
+        assert_eq!(fixture.read_head_file("b.txt"), "two edited\n");
+        assert_eq!(fixture.repo.head().unwrap().shorthand(), Some("main"));
+    }
+
+    #[test]
+    fn test_reset_keeps_rebase_in_progress() {
+        let fixture = TestRepo::new();
+        let c1 = fixture.commit_file("a.txt", "one\n", "c1");
+        let c2 = fixture.commit_file("b.txt", "two\n", "c2");
+        fixture.commit_file("c.txt", "three\n", "c3");
+        let mut todo = git_utils::get_rebase_todo(&fixture.repo, c2).unwrap();
+        todo[0].action = RebaseAction::Edit;
+        assert!(!git_interactive_rebase(&fixture.repo, c1, &todo).unwrap());
+
+        let head_oid = fixture.repo.head().unwrap().target().unwrap();
+        git_reset(&fixture.repo, head_oid, ResetMode::Mixed).unwrap();
+        git_reset(&fixture.repo, head_oid, ResetMode::Hard).unwrap();
+        assert!(git_utils::get_rebase_status(&fixture.repo).unwrap().unwrap().is_edit_stop);
+        git_rebase_continue(&fixture.repo).unwrap();
+        assert_eq!(fixture.get_head_summaries(), vec!["c3", "c2", "c1"]);
+    }
+
+    #[test]
+    fn test_stash_apply_falls_back_when_staged_changes_conflict() {
+        let fixture = TestRepo::new();
+        fixture.commit_file("a.txt", "one\n", "c1");
+        fixture.commit_file("b.txt", "b\n", "c2");
+        // The staged change conflicts with the next commit, but the working tree ends up the same as HEAD.
+        fixture.write_file("a.txt", "staged\n");
+        git_stage(&fixture.repo, &[String::from("a.txt")]).unwrap();
+        fixture.write_file("a.txt", "one\n");
+        fixture.write_file("b.txt", "unstaged\n");
+        git_stash_save(&fixture.repo, "", false, false).unwrap();
+        fixture.commit_file("a.txt", "other\n", "c3");
+
+        git_stash_apply(&fixture.repo, 0, true).unwrap();
+        assert_eq!(std::fs::read_to_string(fixture.repo.workdir().unwrap().join("b.txt")).unwrap(), "unstaged\n");
+        assert_eq!(std::fs::read_to_string(fixture.repo.workdir().unwrap().join("a.txt")).unwrap(), "other\n");
+        assert!(git_utils::get_stashes(&fixture.repo).unwrap().is_empty());
+    }
+
+    // Leaves main and feature having both changed a.txt, with feature also adding c.txt.
+    fn setup_conflicting_branches(fixture: &TestRepo) {
+        fixture.commit_file("a.txt", "base\n", "base");
+        fixture.commit_file("b.txt", "b\n", "b");
+        let head_commit = git_utils::get_head_commit(&fixture.repo).unwrap().unwrap();
+        fixture.repo.branch("feature", &head_commit, false).unwrap();
+        fixture.commit_file("a.txt", "main\n", "main");
+        git_checkout_ref(&fixture.repo, "refs/heads/feature").unwrap();
+        fixture.commit_file("a.txt", "feature\n", "feature a");
+        fixture.commit_file("c.txt", "c\n", "feature c");
+        git_checkout_ref(&fixture.repo, "refs/heads/main").unwrap();
+    }
+
+    #[test]
+    fn test_abort_merge_keeps_unrelated_changes() {
+        let fixture = TestRepo::new();
+        setup_conflicting_branches(&fixture);
+        fixture.write_file("b.txt", "unstaged\n");
+        assert!(git_merge(&fixture.repo, "refs/heads/feature", MergeKind::NoFastForward).unwrap() == MergeOutcome::Conflicts);
+
+        git_abort_merge(&fixture.repo).unwrap();
+        assert!(!git_utils::is_operation_in_progress(&fixture.repo).unwrap());
+        assert!(!git_utils::has_conflicts(&fixture.repo).unwrap());
+        assert!(!git_utils::has_staged_changes(&fixture.repo).unwrap());
+        assert_eq!(fixture.read_file("a.txt"), "main\n");
+        assert!(!fixture.repo.workdir().unwrap().join("c.txt").exists());
+        assert_eq!(fixture.read_file("b.txt"), "unstaged\n");
+    }
+
+    #[test]
+    fn test_resolve_conflict_with_either_side() {
+        let fixture = TestRepo::new();
+        setup_conflicting_branches(&fixture);
+        assert!(git_merge(&fixture.repo, "refs/heads/feature", MergeKind::NoFastForward).unwrap() == MergeOutcome::Conflicts);
+
+        git_resolve_conflict(&fixture.repo, "a.txt", ConflictSide::Theirs).unwrap();
+        assert_eq!(fixture.read_file("a.txt"), "feature\n");
+        assert!(!git_utils::has_conflicts(&fixture.repo).unwrap());
+        assert!(git_resolve_conflict(&fixture.repo, "a.txt", ConflictSide::Ours).is_err());
+        git_commit(&fixture.repo, "merge", false, None, None).unwrap();
+        assert_eq!(fixture.read_head_file("a.txt"), "feature\n");
+    }
+
+    // Leaves feature with two commits that main doesn't have, the second of which changes b.txt.
+    fn setup_commits_to_pick(fixture: &TestRepo) -> (Oid, Oid) {
+        fixture.commit_file("a.txt", "a\n", "base");
+        fixture.commit_file("b.txt", "b\n", "b");
+        let head_commit = git_utils::get_head_commit(&fixture.repo).unwrap().unwrap();
+        fixture.repo.branch("feature", &head_commit, false).unwrap();
+        git_checkout_ref(&fixture.repo, "refs/heads/feature").unwrap();
+        let c1 = fixture.commit_file("c.txt", "c\n", "add c");
+        let c2 = fixture.commit_file("b.txt", "feature\n", "change b");
+        git_checkout_ref(&fixture.repo, "refs/heads/main").unwrap();
+        (c1, c2)
+    }
+
+    #[test]
+    fn test_cherry_pick_stopped_by_local_change_can_continue() {
+        let fixture = TestRepo::new();
+        let (c1, c2) = setup_commits_to_pick(&fixture);
+        // The second commit can't be applied over this, but the first one is committed before that's found out.
+        fixture.write_file("b.txt", "unstaged\n");
+        assert!(git_cherry_pick(&fixture.repo, &[c1, c2], 0).is_err());
+        assert_eq!(fixture.get_head_summaries(), vec!["add c", "b", "base"]);
+        assert_eq!(git_utils::get_sequence_status(&fixture.repo).unwrap().unwrap().remaining_count, 1);
+
+        fixture.write_file("b.txt", "b\n");
+        assert!(git_sequence_continue(&fixture.repo).unwrap());
+        assert!(!git_utils::is_operation_in_progress(&fixture.repo).unwrap());
+        assert_eq!(fixture.get_head_summaries(), vec!["change b", "add c", "b", "base"]);
+        assert_eq!(fixture.read_file("b.txt"), "feature\n");
+    }
+
+    #[test]
+    fn test_cherry_pick_abort_keeps_unrelated_changes() {
+        let fixture = TestRepo::new();
+        let (c1, c2) = setup_commits_to_pick(&fixture);
+        fixture.write_file("b.txt", "unstaged\n");
+        assert!(git_cherry_pick(&fixture.repo, &[c1, c2], 0).is_err());
+
+        git_sequence_abort(&fixture.repo).unwrap();
+        assert!(!git_utils::is_operation_in_progress(&fixture.repo).unwrap());
+        assert_eq!(fixture.get_head_summaries(), vec!["b", "base"]);
+        assert!(!fixture.repo.workdir().unwrap().join("c.txt").exists());
+        assert_eq!(fixture.read_file("b.txt"), "unstaged\n");
+        assert!(!git_utils::has_staged_changes(&fixture.repo).unwrap());
+    }
+
+    #[test]
+    fn test_rebase_skip_keeps_unrelated_changes() {
+        let fixture = TestRepo::new();
+        setup_conflicting_branches(&fixture);
+        git_checkout_ref(&fixture.repo, "refs/heads/feature").unwrap();
+        let main_ac = fixture.repo.find_annotated_commit(fixture.repo.refname_to_id("refs/heads/main").unwrap()).unwrap();
+        let mut rebase = fixture.repo.rebase(None, Some(&main_ac), None, None).unwrap();
+        assert!(!git_rebase_run(&fixture.repo, &mut rebase, false).unwrap());
+
+        // Changes made while the rebase is stopped aren't part of the step being skipped, so they're kept even though
+        // libgit2 won't apply the next step until they're dealt with.
+        fixture.write_file("b.txt", "unstaged\n");
+        assert!(git_rebase_skip(&fixture.repo).is_err());
+        assert_eq!(fixture.read_file("b.txt"), "unstaged\n");
+        assert_eq!(fixture.read_file("a.txt"), "main\n");
+        assert!(git_utils::is_rebasing(&fixture.repo));
+
+        fixture.write_file("b.txt", "b\n");
+        git_rebase_continue(&fixture.repo).unwrap();
+        assert!(!git_utils::is_rebasing(&fixture.repo));
+        assert_eq!(fixture.get_head_summaries(), vec!["feature c", "main", "b", "base"]);
+    }
+
+    #[test]
+    fn test_interactive_rebase_reorders_and_folds_commits() {
+        let fixture = TestRepo::new();
+        fixture.commit_file("a.txt", "a\n", "base");
+        let c1 = fixture.commit_file("b.txt", "b\n", "c1");
+        fixture.commit_file("c.txt", "c\n", "c2");
+        fixture.commit_file("d.txt", "d\n", "c3");
+        fixture.commit_file("e.txt", "e\n", "c4");
+        let base_oid = fixture.repo.find_commit(c1).unwrap().parent_id(0).unwrap();
+
+        let mut todo = git_utils::get_rebase_todo(&fixture.repo, c1).unwrap();
+        todo.swap(0, 1);
+        todo[0].action = RebaseAction::Reword;
+        todo[0].message = String::from("c2 reworded\n");
+        todo[1].action = RebaseAction::Squash;
+        todo[2].action = RebaseAction::Drop;
+        todo[3].action = RebaseAction::Fixup;
+        assert!(git_interactive_rebase(&fixture.repo, base_oid, &todo).unwrap());
+
+        assert!(!git_utils::is_operation_in_progress(&fixture.repo).unwrap());
+        assert_eq!(fixture.repo.head().unwrap().shorthand(), Some("main"));
+        assert_eq!(fixture.get_head_summaries(), vec!["c2 reworded", "base"]);
+        let head_commit = git_utils::get_head_commit(&fixture.repo).unwrap().unwrap();
+        assert_eq!(head_commit.message().unwrap(), "c2 reworded\n\nc1");
+        assert_eq!(fixture.read_head_file("e.txt"), "e\n");
+        assert!(head_commit.tree().unwrap().get_name("d.txt").is_none());
+        // libgit2's own rebase state is never touched.
+        assert!(!fixture.repo.path().join("rebase-merge").exists());
+    }
+
+    #[test]
+    fn test_interactive_rebase_abort_keeps_branch_and_unrelated_changes() {
+        let fixture = TestRepo::new();
+        fixture.commit_file("a.txt", "a\n", "base");
+        fixture.commit_file("b.txt", "b\n", "b");
+        let c1 = fixture.commit_file("a.txt", "one\n", "c1");
+        let c2 = fixture.commit_file("a.txt", "two\n", "c2");
+        let base_oid = fixture.repo.find_commit(c1).unwrap().parent_id(0).unwrap();
+
+        // Picking c2 before c1 conflicts.
+        let mut todo = git_utils::get_rebase_todo(&fixture.repo, c1).unwrap();
+        todo.swap(0, 1);
+        assert!(!git_interactive_rebase(&fixture.repo, base_oid, &todo).unwrap());
+        assert!(git_utils::has_conflicts(&fixture.repo).unwrap());
+        assert_eq!(fixture.repo.refname_to_id("refs/heads/main").unwrap(), c2);
+        let rebase_status = git_utils::get_rebase_status(&fixture.repo).unwrap().unwrap();
+        assert_eq!((rebase_status.current_step, rebase_status.total_steps), (1, 2));
+        assert!(git_utils::get_sequence_kind(&fixture.repo).unwrap().is_none());
+
+        fixture.write_file("b.txt", "unstaged\n");
+        git_rebase_abort(&fixture.repo).unwrap();
+        assert!(!git_utils::is_operation_in_progress(&fixture.repo).unwrap());
+        assert_eq!(fixture.repo.head().unwrap().shorthand(), Some("main"));
+        assert_eq!(fixture.get_head_summaries(), vec!["c2", "c1", "b", "base"]);
+        assert_eq!(fixture.read_file("a.txt"), "two\n");
+        assert_eq!(fixture.read_file("b.txt"), "unstaged\n");
+    }
+
+    #[test]
+    fn test_hard_reset_leaves_everything_alone_if_the_branch_cant_move() {
+        let fixture = TestRepo::new();
+        let c1 = fixture.commit_file("a.txt", "one\n", "c1");
+        fixture.commit_file("b.txt", "two\n", "c2");
+        fixture.write_file("a.txt", "unstaged\n");
+
+        // Another git process holding the branch's lock stops it from being moved.
+        let lock_path = fixture.repo.path().join("refs/heads/main.lock");
+        std::fs::write(&lock_path, "").unwrap();
+        assert!(git_reset(&fixture.repo, c1, ResetMode::Hard).is_err());
+        assert_eq!(fixture.get_head_summaries(), vec!["c2", "c1"]);
+        assert_eq!(fixture.read_file("a.txt"), "unstaged\n");
+        assert!(fixture.repo.workdir().unwrap().join("b.txt").exists());
+
+        std::fs::remove_file(&lock_path).unwrap();
+        git_reset(&fixture.repo, c1, ResetMode::Hard).unwrap();
+        assert_eq!(fixture.get_head_summaries(), vec!["c1"]);
+        assert_eq!(fixture.read_file("a.txt"), "one\n");
+        assert!(!fixture.repo.workdir().unwrap().join("b.txt").exists());
+        assert!(!git_utils::has_tracked_changes(&fixture.repo).unwrap());
     }
-    bail!("Merge analysis failed to make any determination on how to proceed with the pull. If you're reading this, your repository may be corrupted.")
 }
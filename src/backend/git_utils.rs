@@ -1,26 +1,31 @@
-use std::path::PathBuf;
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use anyhow::{bail, Error, Result};
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
 use directories::UserDirs;
-use git2::{Config, Cred, CredentialHelper, Delta, Diff, DiffFindOptions, DiffOptions, ErrorCode, Reference, RemoteCallbacks, Repository};
+use git2::{message_prettify, Branch, CertificateCheckStatus, Commit, Config, Cred, CredentialHelper, CredentialType, Delta, Diff, DiffFindOptions, DiffOptions, Direction, ErrorCode, FetchOptions, Index, Oid, Patch, Reference, Remote, RemoteCallbacks, Repository, RepositoryState, Signature, StatusOptions, SubmoduleUpdateOptions, Time};
+use git2::cert::{Cert, CertHostkey};
 use rfd::FileDialog;
+use crate::backend::known_hosts::{self, HostKeyStatus};
+use crate::backend::progress::{OperationProgress, PromptAnswer, PromptRequest};
+
+pub const STARTER_GITIGNORE: &str = "# OS files
+.DS_Store
+Thumbs.db
+
+# Editor files
+.idea/
+.vscode/
+*.swp
+*~
+";
 
 pub fn open_repo() -> Result<Option<(String, Repository)>> {
-    let start_dir = match UserDirs::new() {
-        Some(ud) => PathBuf::from(ud.home_dir()),
-        None => PathBuf::from("/"),
-    };
-    let folder = FileDialog::new()
-        .set_directory(start_dir)
-        .pick_folder();
+    let folder = pick_folder();
     if let Some(pf) = folder {
-        // Get the name of the repo from the path.
-        let mut name = String::from("(Invalid UTF-8 in Name)");
-        if let Some(os_s) = pf.file_name() {
-            if let Some(s) = os_s.to_str() {
-                name = String::from(s);
-            }
-        }
-
+        let name = get_repo_name(&pf);
         match Repository::open(pf) {
             Ok(repo) => return Ok(Some((name, repo))),
             Err(e) => bail!(e),
@@ -29,7 +34,129 @@ pub fn open_repo() -> Result<Option<(String, Repository)>> {
     Ok(None)
 }
 
-pub fn get_all_refs(repo: &Repository) -> Result<[Vec<Reference>; 3]> {
+pub fn get_repo_name(path: &Path) -> String {
+    // Get the name of the repo from the path.
+    let mut name = String::from("(Invalid UTF-8 in Name)");
+    if let Some(os_s) = path.file_name() {
+        if let Some(s) = os_s.to_str() {
+            name = String::from(s);
+        }
+    }
+    name
+}
+
+pub fn get_default_branch_name() -> String {
+    if let Ok(config) = Config::open_default() {
+        if let Ok(branch_name) = config.get_string("init.defaultBranch") {
+            return branch_name;
+        }
+    }
+    String::from("master")
+}
+
+pub fn get_signature(repo: &Repository, name_email_opt: Option<(&str, &str)>) -> Result<Signature<'static>> {
+    match name_email_opt {
+        Some((name, email)) => Ok(Signature::now(name, email)?),
+        None => match repo.signature() {
+            Ok(signature) => Ok(signature),
+            Err(_) => bail!("Couldn't get a name and email from your git config! Please set user.name and user.email, or fill in the override fields."),
+        },
+    }
+}
+
+pub fn format_signature(signature: &Signature) -> String {
+    format!("{} <{}>", String::from_utf8_lossy(signature.name_bytes()), String::from_utf8_lossy(signature.email_bytes()))
+}
+
+pub fn format_time(time: &Time) -> String {
+    // Convert to the commit's own timezone, then to a civil date (see Howard Hinnant's days_from_civil algorithm).
+    let local_seconds = time.seconds() + time.offset_minutes() as i64 * 60;
+    let days = local_seconds.div_euclid(86400);
+    let seconds_of_day = local_seconds.rem_euclid(86400);
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let offset_sign = if time.offset_minutes() < 0 { '-' } else { '+' };
+    let offset_abs = time.offset_minutes().abs();
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} {}{:02}{:02}",
+        year, month, day,
+        seconds_of_day / 3600, (seconds_of_day % 3600) / 60, seconds_of_day % 60,
+        offset_sign, offset_abs / 60, offset_abs % 60
+    )
+}
+
+pub fn get_local_branch<'a>(repo: &'a Repository, ref_name: &str) -> Result<Branch<'a>> {
+    let reference = repo.find_reference(ref_name)?;
+    if !reference.is_branch() {
+        bail!("{} is not a local branch.", ref_name);
+    }
+    Ok(Branch::wrap(reference))
+}
+
+pub fn get_index(repo: &Repository) -> Result<Index> {
+    let mut index = repo.index()?;
+    // The index is cached, so make sure it picks up any changes made outside of this app.
+    index.read(false)?;
+    Ok(index)
+}
+
+pub fn get_head_commit(repo: &Repository) -> Result<Option<Commit<'_>>> {
+    match repo.head() {
+        Ok(head_ref) => Ok(Some(head_ref.peel_to_commit()?)),
+        Err(e) => {
+            if e.code() != ErrorCode::UnbornBranch {
+                return Err(e.into());
+            }
+            Ok(None)
+        },
+    }
+}
+
+pub fn get_repo_name_from_url(url: &str) -> String {
+    // Take the last path segment of the url, e.g. "https://host/user/repo.git" -> "repo".
+    let trimmed_url = url.trim_end_matches(['/', '\\']);
+    let last_segment = trimmed_url.rsplit(['/', '\\', ':']).next().unwrap_or(trimmed_url);
+    String::from(last_segment.strip_suffix(".git").unwrap_or(last_segment))
+}
+
+pub fn pick_folder() -> Option<PathBuf> {
+    let start_dir = match UserDirs::new() {
+        Some(ud) => PathBuf::from(ud.home_dir()),
+        None => PathBuf::from("/"),
+    };
+    FileDialog::new()
+        .set_directory(start_dir)
+        .pick_folder()
+}
+
+pub fn update_submodules(repo: &Repository, progress: &Arc<Mutex<OperationProgress>>) -> Result<()> {
+    for mut submodule in repo.submodules()? {
+        let operation_name = format!("Updating Submodule {}", submodule.name().unwrap_or("(Invalid UTF-8 in Name)"));
+        let url = String::from(submodule.url().unwrap_or_default());
+        with_certificate_prompt(progress, || {
+            let mut fetch_options = FetchOptions::new();
+            fetch_options.remote_callbacks(get_remote_callbacks(progress, &operation_name, &url, Direction::Fetch));
+            let mut update_options = SubmoduleUpdateOptions::new();
+            update_options.fetch(fetch_options);
+            submodule.update(true, Some(&mut update_options))
+        })?;
+
+        // Submodules can have their own submodules.
+        let submodule_repo = submodule.open()?;
+        update_submodules(&submodule_repo, progress)?;
+    }
+    Ok(())
+}
+
+pub fn get_all_refs(repo: &Repository) -> Result<[Vec<Reference<'_>>; 3]> {
     let mut local_ref_shorthands = vec![];
     let mut remote_ref_shorthands = vec![];
     let mut tag_ref_shorthands = vec![];
@@ -49,6 +176,39 @@ pub fn get_all_refs(repo: &Repository) -> Result<[Vec<Reference>; 3]> {
     Ok([local_ref_shorthands, remote_ref_shorthands, tag_ref_shorthands])
 }
 
+pub struct StashEntry {
+    pub index: usize,
+    pub oid: Oid,
+    pub base_oid: Oid,
+    pub message: String,
+}
+
+impl StashEntry {
+    pub fn get_name(&self) -> String {
+        format!("stash@{{{}}}", self.index)
+    }
+}
+
+pub fn get_stashes(repo: &Repository) -> Result<Vec<StashEntry>> {
+    // Repository::stash_foreach needs a mutable repo, but the stash list is just the reflog of refs/stash.
+    let reflog = match repo.reflog("refs/stash") {
+        Ok(r) => r,
+        Err(e) if e.code() == ErrorCode::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    let mut stashes = vec![];
+    for (index, entry) in reflog.iter().enumerate() {
+        let oid = entry.id_new();
+        stashes.push(StashEntry {
+            index,
+            oid,
+            base_oid: repo.find_commit(oid)?.parent_id(0)?,
+            message: String::from(entry.message().unwrap_or_default()),
+        });
+    }
+    Ok(stashes)
+}
+
 fn set_diff_find_similar(diff: &mut Diff) -> Result<()> {
     let mut opts = DiffFindOptions::new();
     opts.renames(true);
@@ -58,7 +218,19 @@ fn set_diff_find_similar(diff: &mut Diff) -> Result<()> {
     Ok(())
 }
 
-pub fn get_unstaged_changes(repo: &Repository) -> Result<Diff> {
+pub fn get_commit_diff<'a>(repo: &'a Repository, commit: &Commit) -> Result<Diff<'a>> {
+    // Merge commits are shown against their first parent, the same as most git tools.
+    let parent_tree = match commit.parents().next() {
+        Some(parent) => Some(parent.tree()?),
+        None => None,
+    };
+    let mut diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+    set_diff_find_similar(&mut diff)?;
+
+    Ok(diff)
+}
+
+pub fn get_unstaged_changes(repo: &Repository) -> Result<Diff<'_>> {
     let mut diff_options = DiffOptions::new();
     diff_options.include_untracked(true);
     diff_options.recurse_untracked_dirs(true);
@@ -70,7 +242,7 @@ pub fn get_unstaged_changes(repo: &Repository) -> Result<Diff> {
     Ok(diff)
 }
 
-pub fn get_staged_changes(repo: &Repository) -> Result<Diff> {
+pub fn get_staged_changes(repo: &Repository) -> Result<Diff<'_>> {
     let mut tree = None;
     match repo.head() {
         Ok(head_ref) => {
@@ -96,6 +268,287 @@ pub fn get_staged_changes(repo: &Repository) -> Result<Diff> {
     Ok(diff)
 }
 
+pub struct FileChange {
+    pub old_path: Option<String>,
+    pub path: String,
+    pub status: Delta,
+}
+
+impl FileChange {
+    pub fn get_display_path(&self) -> String {
+        match &self.old_path {
+            Some(old_path) => format!("{} → {}", old_path, self.path),
+            None => self.path.clone(),
+        }
+    }
+
+    pub fn get_all_paths(&self) -> Vec<String> {
+        let mut paths = vec![self.path.clone()];
+        if let Some(old_path) = &self.old_path {
+            paths.push(old_path.clone());
+        }
+        paths
+    }
+}
+
+pub fn get_delta_label(delta: Delta) -> &'static str {
+    match delta {
+        Delta::Added => "Added",
+        Delta::Deleted => "Deleted",
+        Delta::Modified => "Modified",
+        Delta::Renamed => "Renamed",
+        Delta::Copied => "Copied",
+        Delta::Ignored => "Ignored",
+        Delta::Untracked => "Untracked",
+        Delta::Typechange => "Type Changed",
+        Delta::Unreadable => "Unreadable",
+        Delta::Conflicted => "Conflicted",
+        Delta::Unmodified => "Unmodified",
+    }
+}
+
+pub fn get_file_changes(diff: &Diff) -> Result<Vec<FileChange>> {
+    let mut file_changes = vec![];
+    for delta in diff.deltas() {
+        let new_path = delta.new_file().path().ok_or(Error::msg("File path has invalid UTF-8!"))?;
+        let old_path = delta.old_file().path().ok_or(Error::msg("File path has invalid UTF-8!"))?;
+        let path = String::from(new_path.to_str().ok_or(Error::msg("File path has invalid UTF-8!"))?);
+        let mut old_path_opt = None;
+        if (delta.status() == Delta::Renamed || delta.status() == Delta::Copied) && old_path != new_path {
+            old_path_opt = Some(String::from(old_path.to_str().ok_or(Error::msg("File path has invalid UTF-8!"))?));
+        }
+        file_changes.push(FileChange {
+            old_path: old_path_opt,
+            path,
+            status: delta.status(),
+        });
+    }
+    Ok(file_changes)
+}
+
+pub fn get_status_changes(repo: &Repository) -> Result<[Vec<FileChange>; 3]> {
+    let mut unstaged_changes = vec![];
+    let mut untracked_changes = vec![];
+    for file_change in get_file_changes(&get_unstaged_changes(repo)?)? {
+        if file_change.status == Delta::Untracked {
+            untracked_changes.push(file_change);
+        } else {
+            unstaged_changes.push(file_change);
+        }
+    }
+
+    // Conflicts show up in both diffs, so only list them once with the unstaged changes.
+    let staged_changes = get_file_changes(&get_staged_changes(repo)?)?.into_iter().filter(|file_change| {
+        file_change.status != Delta::Conflicted
+    }).collect();
+    Ok([unstaged_changes, untracked_changes, staged_changes])
+}
+
+pub struct FileDiffLine {
+    pub origin: char,
+    pub content: String,
+    pub old_lineno: Option<u32>,
+    pub new_lineno: Option<u32>,
+}
+
+impl FileDiffLine {
+    pub fn is_change(&self) -> bool {
+        self.origin == '+' || self.origin == '-'
+    }
+}
+
+pub struct FileDiffHunk {
+    pub header: String,
+    pub old_start: u32,
+    pub new_start: u32,
+    pub lines: Vec<FileDiffLine>,
+}
+
+pub struct FileDiff {
+    pub old_path: String,
+    pub path: String,
+    pub status: Delta,
+    pub mode: i32,
+    pub is_binary: bool,
+    pub hunks: Vec<FileDiffHunk>,
+}
+
+impl FileDiff {
+    pub fn get_display_path(&self) -> String {
+        if self.old_path != self.path {
+            format!("{} → {}", self.old_path, self.path)
+        } else {
+            self.path.clone()
+        }
+    }
+
+    pub fn get_change_lines(&self, hunk_index: usize) -> HashSet<(usize, usize)> {
+        let mut change_lines = HashSet::new();
+        if let Some(hunk) = self.hunks.get(hunk_index) {
+            for (line_index, line) in hunk.lines.iter().enumerate() {
+                if line.is_change() {
+                    change_lines.insert((hunk_index, line_index));
+                }
+            }
+        }
+        change_lines
+    }
+}
+
+pub fn get_file_diff(diff: &Diff, path: &str) -> Result<Option<FileDiff>> {
+    for (i, delta) in diff.deltas().enumerate() {
+        let new_path = delta.new_file().path().ok_or(Error::msg("File path has invalid UTF-8!"))?;
+        let old_path = delta.old_file().path().ok_or(Error::msg("File path has invalid UTF-8!"))?;
+        if new_path != Path::new(path) && old_path != Path::new(path) {
+            continue;
+        }
+
+        let mut mode = delta.new_file().mode();
+        if delta.status() == Delta::Deleted {
+            mode = delta.old_file().mode();
+        }
+        let mut file_diff = FileDiff {
+            old_path: String::from(old_path.to_str().ok_or(Error::msg("File path has invalid UTF-8!"))?),
+            path: String::from(new_path.to_str().ok_or(Error::msg("File path has invalid UTF-8!"))?),
+            status: delta.status(),
+            mode: mode.into(),
+            is_binary: delta.flags().is_binary(),
+            hunks: vec![],
+        };
+
+        if let Some(patch) = Patch::from_diff(diff, i)? {
+            for hunk_index in 0..patch.num_hunks() {
+                let (hunk, line_count) = patch.hunk(hunk_index)?;
+                let mut file_diff_hunk = FileDiffHunk {
+                    header: String::from_utf8_lossy(hunk.header()).trim_end().to_string(),
+                    old_start: hunk.old_start(),
+                    new_start: hunk.new_start(),
+                    lines: vec![],
+                };
+                for line_index in 0..line_count {
+                    let line = patch.line_in_hunk(hunk_index, line_index)?;
+                    // The "No newline at end of file" markers are rebuilt from the content when needed.
+                    if !matches!(line.origin(), ' ' | '+' | '-') {
+                        continue;
+                    }
+                    file_diff_hunk.lines.push(FileDiffLine {
+                        origin: line.origin(),
+                        content: String::from_utf8_lossy(line.content()).into_owned(),
+                        old_lineno: line.old_lineno(),
+                        new_lineno: line.new_lineno(),
+                    });
+                }
+                file_diff.hunks.push(file_diff_hunk);
+            }
+        }
+        return Ok(Some(file_diff));
+    }
+    Ok(None)
+}
+
+fn push_patch_line(patch_text: &mut String, origin: char, content: &str) {
+    patch_text.push(origin);
+    patch_text.push_str(content);
+    if !content.ends_with('\n') {
+        patch_text.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+pub fn build_partial_patch(file_diff: &FileDiff, selected_lines: &HashSet<(usize, usize)>, is_reverse: bool) -> Option<String> {
+    // Unselected additions are dropped and unselected deletions become context. When reversing, the sides of the diff
+    // are swapped first so the same rules can be applied.
+    let mut old_path = &file_diff.old_path;
+    let mut new_path = &file_diff.path;
+    let mut old_exists = file_diff.status != Delta::Added && file_diff.status != Delta::Untracked;
+    let mut new_exists = file_diff.status != Delta::Deleted;
+    if is_reverse {
+        std::mem::swap(&mut old_path, &mut new_path);
+        std::mem::swap(&mut old_exists, &mut new_exists);
+    }
+
+    let mut hunks_text = String::new();
+    let mut is_everything_selected = true;
+    let mut line_offset: i64 = 0;
+    for (hunk_index, hunk) in file_diff.hunks.iter().enumerate() {
+        let old_start = if is_reverse { hunk.new_start } else { hunk.old_start };
+        let mut hunk_text = String::new();
+        let mut old_count: i64 = 0;
+        let mut new_count: i64 = 0;
+        let mut has_change = false;
+        let get_origin = |line: &FileDiffLine| match (line.origin, is_reverse) {
+            ('+', true) => '-',
+            ('-', true) => '+',
+            (o, _) => o,
+        };
+        let last_selected_addition_opt = hunk.lines.iter().enumerate().rposition(|(line_index, line)| {
+            get_origin(line) == '+' && selected_lines.contains(&(hunk_index, line_index))
+        });
+        for (line_index, line) in hunk.lines.iter().enumerate() {
+            let origin = get_origin(line);
+            let is_selected = selected_lines.contains(&(hunk_index, line_index));
+            match origin {
+                // A line without a newline can't have anything added after it, so it gets one as part of the change.
+                '-' if !is_selected && !line.content.ends_with('\n') && last_selected_addition_opt.is_some_and(|i| i > line_index) => {
+                    push_patch_line(&mut hunk_text, '-', &line.content);
+                    push_patch_line(&mut hunk_text, '+', &format!("{}\n", line.content));
+                    old_count += 1;
+                    new_count += 1;
+                    has_change = true;
+                    is_everything_selected = false;
+                },
+                '+' if is_selected => {
+                    push_patch_line(&mut hunk_text, '+', &line.content);
+                    new_count += 1;
+                    has_change = true;
+                },
+                '+' => is_everything_selected = false,
+                '-' if is_selected => {
+                    push_patch_line(&mut hunk_text, '-', &line.content);
+                    old_count += 1;
+                    has_change = true;
+                },
+                _ => {
+                    if origin == '-' {
+                        is_everything_selected = false;
+                    }
+                    push_patch_line(&mut hunk_text, ' ', &line.content);
+                    old_count += 1;
+                    new_count += 1;
+                },
+            }
+        }
+        if !has_change {
+            continue;
+        }
+
+        // Hunks that add or remove everything refer to the line before the change.
+        let mut new_start = old_start as i64 + line_offset;
+        if old_count == 0 {
+            new_start += 1;
+        }
+        if new_count == 0 {
+            new_start -= 1;
+        }
+        hunks_text.push_str(&format!("@@ -{},{} +{},{} @@\n", old_start, old_count, new_start, new_count));
+        hunks_text.push_str(&hunk_text);
+        line_offset += new_count - old_count;
+    }
+    if hunks_text.is_empty() {
+        return None;
+    }
+
+    let mut patch_text = format!("diff --git a/{} b/{}\n", old_path, new_path);
+    if !old_exists {
+        patch_text.push_str(&format!("new file mode {:o}\n--- /dev/null\n+++ b/{}\n", file_diff.mode, new_path));
+    } else if !new_exists && is_everything_selected {
+        patch_text.push_str(&format!("deleted file mode {:o}\n--- a/{}\n+++ /dev/null\n", file_diff.mode, old_path));
+    } else {
+        patch_text.push_str(&format!("--- a/{}\n+++ b/{}\n", old_path, new_path));
+    }
+    patch_text.push_str(&hunks_text);
+    Some(patch_text)
+}
+
 pub fn has_conflicts(repo: &Repository) -> Result<bool> {
     let unstaged_diff = get_unstaged_changes(repo)?;
     let staged_diff = get_staged_changes(repo)?;
@@ -115,14 +568,452 @@ pub fn has_conflicts(repo: &Repository) -> Result<bool> {
     Ok(false)
 }
 
-pub fn has_unstaged_changes(repo: &Repository) -> Result<bool> {
-    let diff = get_unstaged_changes(repo)?;
+pub struct ConflictEntry {
+    pub path: String,
+    pub has_ours: bool,
+    pub has_theirs: bool,
+}
 
-    if diff.stats()?.files_changed() > 0 {
-        Ok(true)
-    } else {
-        Ok(false)
+impl ConflictEntry {
+    pub fn get_description(&self) -> &'static str {
+        match (self.has_ours, self.has_theirs) {
+            (true, true) => "Both Modified",
+            (true, false) => "Deleted By Them",
+            (false, true) => "Deleted By Us",
+            (false, false) => "Both Deleted",
+        }
+    }
+}
+
+pub fn get_conflicts(repo: &Repository) -> Result<Vec<ConflictEntry>> {
+    let index = get_index(repo)?;
+    let mut conflicts = vec![];
+    for conflict in index.conflicts()? {
+        let conflict = conflict?;
+        // Whichever sides exist will have the path, so just take the first one.
+        let entry = match conflict.our.as_ref().or(conflict.their.as_ref()).or(conflict.ancestor.as_ref()) {
+            Some(e) => e,
+            None => continue,
+        };
+        conflicts.push(ConflictEntry {
+            path: String::from(std::str::from_utf8(&entry.path)?),
+            has_ours: conflict.our.is_some(),
+            has_theirs: conflict.their.is_some(),
+        });
+    }
+    Ok(conflicts)
+}
+
+pub fn is_merging(repo: &Repository) -> bool {
+    repo.state() == RepositoryState::Merge
+}
+
+pub fn get_merge_head_oids(repo: &Repository) -> Result<Vec<Oid>> {
+    if !is_merging(repo) {
+        return Ok(vec![]);
+    }
+    // Repository::mergehead_foreach needs a mutable repo, so just read the file it reads.
+    let merge_head = std::fs::read_to_string(repo.path().join("MERGE_HEAD"))?;
+    let mut oids = vec![];
+    for line in merge_head.lines().filter(|line| !line.trim().is_empty()) {
+        oids.push(Oid::from_str(line.trim())?);
+    }
+    Ok(oids)
+}
+
+pub fn is_rebasing(repo: &Repository) -> bool {
+    matches!(repo.state(), RepositoryState::Rebase | RepositoryState::RebaseInteractive | RepositoryState::RebaseMerge) || InteractiveRebase::get_dir(repo).exists()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum RebaseAction {
+    Pick,
+    Reword,
+    Edit,
+    Squash,
+    Fixup,
+    Drop,
+}
+
+impl RebaseAction {
+    pub const ALL: [RebaseAction; 6] = [RebaseAction::Pick, RebaseAction::Reword, RebaseAction::Edit, RebaseAction::Squash, RebaseAction::Fixup, RebaseAction::Drop];
+
+    pub fn get_label(&self) -> &'static str {
+        match self {
+            RebaseAction::Pick => "pick",
+            RebaseAction::Reword => "reword",
+            RebaseAction::Edit => "edit",
+            RebaseAction::Squash => "squash",
+            RebaseAction::Fixup => "fixup",
+            RebaseAction::Drop => "drop",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        RebaseAction::ALL.into_iter().find(|action| action.get_label() == label)
+    }
+}
+
+#[derive(Clone)]
+pub struct RebaseTodoItem {
+    pub oid: Oid,
+    pub summary: String,
+    pub message: String,
+    pub action: RebaseAction,
+}
+
+// Gets the commits from base_oid up to HEAD (oldest first) for an interactive rebase onto base_oid's parent.
+pub fn get_rebase_todo(repo: &Repository, base_oid: Oid) -> Result<Vec<RebaseTodoItem>> {
+    let mut commit = match get_head_commit(repo)? {
+        Some(c) => c,
+        None => bail!("The current branch doesn't have any commits to rebase."),
+    };
+    let mut todo = vec![];
+    loop {
+        if commit.parent_count() > 1 {
+            bail!("Merge commits can't be rebased interactively, please pick a range without any.");
+        }
+        let message = String::from_utf8_lossy(commit.message_bytes()).into_owned();
+        todo.push(RebaseTodoItem {
+            oid: commit.id(),
+            summary: String::from(message.lines().next().unwrap_or_default()),
+            message,
+            action: RebaseAction::Pick,
+        });
+        if commit.id() == base_oid {
+            break;
+        }
+        commit = match commit.parents().next() {
+            Some(c) => c,
+            None => bail!("The selected commit isn't part of the current branch's history."),
+        };
+    }
+    if commit.parent_count() == 0 {
+        bail!("The root commit can't be rebased, please pick a newer commit.");
+    }
+    todo.reverse();
+    Ok(todo)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum RebaseStepState {
+    Pending,
+    // Stopped because of conflicts.
+    Applied,
+    // Stopped to edit the commit.
+    Committed,
+}
+
+impl RebaseStepState {
+    pub fn get_label(&self) -> &'static str {
+        match self {
+            RebaseStepState::Pending => "pending",
+            RebaseStepState::Applied => "applied",
+            RebaseStepState::Committed => "committed",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        [RebaseStepState::Pending, RebaseStepState::Applied, RebaseStepState::Committed].into_iter().find(|state| state.get_label() == label)
+    }
+}
+
+// libgit2 doesn't support interactive rebases, so they're run by cherry-picking each step onto a detached HEAD, with
+// their progress saved here rather than in libgit2's own rebase directory.
+pub struct InteractiveRebase {
+    // The branch that's moved once the rebase is done, or HEAD if it was already detached.
+    pub head_name: String,
+    pub orig_head_oid: Oid,
+    pub onto_oid: Oid,
+    pub total_steps: usize,
+    // The first step is the one the rebase is at.
+    pub remaining_steps: Vec<(RebaseAction, Oid)>,
+    pub current_step_state: RebaseStepState,
+}
+
+impl InteractiveRebase {
+    pub fn get_dir(repo: &Repository) -> PathBuf {
+        repo.path().join("og2-rebase")
+    }
+
+    // The new message for a reworded commit.
+    pub fn get_message_path(repo: &Repository, oid: Oid) -> PathBuf {
+        InteractiveRebase::get_dir(repo).join(format!("message.{}", oid))
+    }
+
+    pub fn read(repo: &Repository) -> Result<Option<Self>> {
+        let contents = match std::fs::read_to_string(InteractiveRebase::get_dir(repo).join("state")) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut lines = contents.lines();
+        let header = lines.next().unwrap_or_default();
+        let header_parts: Vec<&str> = header.split(' ').collect();
+        if header_parts.len() != 5 {
+            bail!("The saved interactive rebase is corrupted.");
+        }
+        let mut remaining_steps = vec![];
+        for line in lines {
+            let (label, oid) = line.split_once(' ').ok_or(Error::msg("The saved interactive rebase is corrupted."))?;
+            let action = RebaseAction::from_label(label).ok_or(Error::msg("The saved interactive rebase is corrupted."))?;
+            remaining_steps.push((action, Oid::from_str(oid)?));
+        }
+        Ok(Some(Self {
+            head_name: String::from(header_parts[0]),
+            orig_head_oid: Oid::from_str(header_parts[1])?,
+            onto_oid: Oid::from_str(header_parts[2])?,
+            total_steps: header_parts[3].parse()?,
+            remaining_steps,
+            current_step_state: RebaseStepState::from_label(header_parts[4]).ok_or(Error::msg("The saved interactive rebase is corrupted."))?,
+        }))
+    }
+
+    pub fn write(&self, repo: &Repository) -> Result<()> {
+        let mut contents = format!("{} {} {} {} {}\n", self.head_name, self.orig_head_oid, self.onto_oid, self.total_steps, self.current_step_state.get_label());
+        for (action, oid) in &self.remaining_steps {
+            contents.push_str(&format!("{} {}\n", action.get_label(), oid));
+        }
+        std::fs::create_dir_all(InteractiveRebase::get_dir(repo))?;
+        std::fs::write(InteractiveRebase::get_dir(repo).join("state"), contents)?;
+        Ok(())
     }
+
+    pub fn remove(repo: &Repository) -> Result<()> {
+        let dir = InteractiveRebase::get_dir(repo);
+        if dir.exists() {
+            std::fs::remove_dir_all(dir)?;
+        }
+        Ok(())
+    }
+}
+
+// True while an interactive rebase is stopped at an "edit" step that's already been committed.
+pub fn is_rebase_edit_stop(repo: &Repository) -> Result<bool> {
+    Ok(InteractiveRebase::read(repo)?.is_some_and(|rebase| rebase.current_step_state == RebaseStepState::Committed))
+}
+
+pub struct RebaseStatus {
+    pub branch_name: String,
+    pub onto_name: String,
+    pub current_step: usize,
+    pub total_steps: usize,
+    pub current_summary: String,
+    pub is_edit_stop: bool,
+}
+
+pub fn get_rebase_status(repo: &Repository) -> Result<Option<RebaseStatus>> {
+    if let Some(rebase) = InteractiveRebase::read(repo)? {
+        let head_name = rebase.head_name.as_str();
+        let (current_step, current_summary) = match rebase.remaining_steps.first() {
+            Some((_, oid)) => (rebase.total_steps - rebase.remaining_steps.len() + 1, String::from(repo.find_commit(*oid)?.summary().unwrap_or_default())),
+            None => (rebase.total_steps, String::new()),
+        };
+        return Ok(Some(RebaseStatus {
+            branch_name: String::from(head_name.strip_prefix("refs/heads/").unwrap_or(head_name)),
+            onto_name: rebase.onto_oid.to_string().chars().take(7).collect(),
+            current_step,
+            total_steps: rebase.total_steps,
+            current_summary,
+            is_edit_stop: rebase.current_step_state == RebaseStepState::Committed,
+        }));
+    }
+    if !is_rebasing(repo) {
+        return Ok(None);
+    }
+    let mut rebase = repo.open_rebase(None)?;
+    let mut current_step = 0;
+    let mut current_summary = String::new();
+    if let Some(i) = rebase.operation_current() {
+        current_step = i + 1;
+        if let Some(operation) = rebase.nth(i) {
+            current_summary = String::from(repo.find_commit(operation.id())?.summary().unwrap_or_default());
+        }
+    }
+    let branch_name = rebase.orig_head_name().unwrap_or("HEAD");
+    // git2 doesn't expose what's being rebased onto, so read it from the rebase's state directory instead.
+    let rebase_dir = repo.path().join("rebase-merge");
+    let onto_name = match std::fs::read_to_string(rebase_dir.join("onto_name")) {
+        Ok(onto_name) => {
+            let onto_name = onto_name.trim();
+            String::from(onto_name.strip_prefix("refs/remotes/").or(onto_name.strip_prefix("refs/heads/")).unwrap_or(onto_name))
+        },
+        Err(_) => std::fs::read_to_string(rebase_dir.join("onto")).unwrap_or_default().trim().chars().take(7).collect(),
+    };
+    Ok(Some(RebaseStatus {
+        branch_name: String::from(branch_name.strip_prefix("refs/heads/").unwrap_or(branch_name)),
+        onto_name,
+        current_step,
+        total_steps: rebase.len(),
+        current_summary,
+        is_edit_stop: false,
+    }))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SequenceKind {
+    CherryPick,
+    Revert,
+}
+
+impl SequenceKind {
+    pub fn get_label(&self) -> &'static str {
+        match self {
+            SequenceKind::CherryPick => "cherry-pick",
+            SequenceKind::Revert => "revert",
+        }
+    }
+
+    pub fn get_title(&self) -> &'static str {
+        match self {
+            SequenceKind::CherryPick => "Cherry-pick",
+            SequenceKind::Revert => "Revert",
+        }
+    }
+
+    fn from_label(label: &str) -> Option<Self> {
+        [SequenceKind::CherryPick, SequenceKind::Revert].into_iter().find(|kind| kind.get_label() == label)
+    }
+
+    // This is where libgit2 keeps the commit that's currently being applied.
+    fn get_head_file_name(&self) -> &'static str {
+        match self {
+            SequenceKind::CherryPick => "CHERRY_PICK_HEAD",
+            SequenceKind::Revert => "REVERT_HEAD",
+        }
+    }
+}
+
+// A cherry-pick or revert of several commits, saved so it can carry on after conflicts are resolved.
+pub struct Sequence {
+    pub kind: SequenceKind,
+    pub mainline: u32,
+    pub orig_head_oid: Oid,
+    pub remaining_oids: Vec<Oid>,
+}
+
+impl Sequence {
+    fn get_path(repo: &Repository) -> PathBuf {
+        repo.path().join("og2-sequence")
+    }
+
+    pub fn read(repo: &Repository) -> Result<Option<Self>> {
+        let contents = match std::fs::read_to_string(Sequence::get_path(repo)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut lines = contents.lines();
+        let header = lines.next().unwrap_or_default();
+        let header_parts: Vec<&str> = header.split(' ').collect();
+        if header_parts.len() != 3 {
+            bail!("The saved cherry-pick or revert is corrupted.");
+        }
+        let kind = SequenceKind::from_label(header_parts[0]).ok_or(Error::msg("The saved cherry-pick or revert is corrupted."))?;
+        let mut remaining_oids = vec![];
+        for line in lines {
+            remaining_oids.push(Oid::from_str(line)?);
+        }
+        Ok(Some(Self {
+            kind,
+            mainline: header_parts[1].parse()?,
+            orig_head_oid: Oid::from_str(header_parts[2])?,
+            remaining_oids,
+        }))
+    }
+
+    pub fn write(&self, repo: &Repository) -> Result<()> {
+        let mut contents = format!("{} {} {}\n", self.kind.get_label(), self.mainline, self.orig_head_oid);
+        for oid in &self.remaining_oids {
+            contents.push_str(&format!("{}\n", oid));
+        }
+        std::fs::write(Sequence::get_path(repo), contents)?;
+        Ok(())
+    }
+
+    pub fn remove(repo: &Repository) -> Result<()> {
+        let path = Sequence::get_path(repo);
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn get_sequence_kind(repo: &Repository) -> Result<Option<SequenceKind>> {
+    match repo.state() {
+        RepositoryState::CherryPick | RepositoryState::CherryPickSequence => Ok(Some(SequenceKind::CherryPick)),
+        RepositoryState::Revert | RepositoryState::RevertSequence => Ok(Some(SequenceKind::Revert)),
+        _ => Ok(Sequence::read(repo)?.map(|sequence| sequence.kind)),
+    }
+}
+
+// Gets the commit that's currently being cherry-picked or reverted, if it stopped because of conflicts.
+pub fn get_sequence_head_oid(repo: &Repository, kind: SequenceKind) -> Result<Option<Oid>> {
+    match std::fs::read_to_string(repo.path().join(kind.get_head_file_name())) {
+        Ok(contents) => Ok(Some(Oid::from_str(contents.trim())?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub struct SequenceStatus {
+    pub kind: SequenceKind,
+    pub current_summary: String,
+    pub remaining_count: usize,
+}
+
+pub fn get_sequence_status(repo: &Repository) -> Result<Option<SequenceStatus>> {
+    let kind = match get_sequence_kind(repo)? {
+        Some(k) => k,
+        None => return Ok(None),
+    };
+    let current_summary = match get_sequence_head_oid(repo, kind)? {
+        Some(oid) => String::from(repo.find_commit(oid)?.summary().unwrap_or_default()),
+        None => String::new(),
+    };
+    let remaining_count = Sequence::read(repo)?.map(|sequence| sequence.remaining_oids.len()).unwrap_or(0);
+    Ok(Some(SequenceStatus {
+        kind,
+        current_summary,
+        remaining_count,
+    }))
+}
+
+// Gets the parent summaries of the first merge commit in oids, if there is one.
+pub fn get_merge_parent_summaries(repo: &Repository, oids: &[Oid]) -> Result<Option<Vec<String>>> {
+    for oid in oids {
+        let commit = repo.find_commit(*oid)?;
+        if commit.parent_count() > 1 {
+            return Ok(Some(commit.parents().map(|p| String::from(p.summary().unwrap_or_default())).collect()));
+        }
+    }
+    Ok(None)
+}
+
+pub fn is_operation_in_progress(repo: &Repository) -> Result<bool> {
+    Ok(repo.state() != RepositoryState::Clean || is_rebasing(repo) || get_sequence_kind(repo)?.is_some())
+}
+
+pub fn get_squash_msg_path(repo: &Repository) -> PathBuf {
+    repo.path().join("SQUASH_MSG")
+}
+
+pub fn get_merge_message(repo: &Repository) -> Result<String> {
+    let message = match repo.message() {
+        Ok(message) => message,
+        Err(e) if e.code() == ErrorCode::NotFound => {
+            // A squash merge doesn't leave the repo in a merging state, so its message is kept separately.
+            match std::fs::read_to_string(get_squash_msg_path(repo)) {
+                Ok(message) => message,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+                Err(e) => return Err(e.into()),
+            }
+        },
+        Err(e) => return Err(e.into()),
+    };
+    // libgit2 lists the conflicted files as comments at the end of the message.
+    Ok(message_prettify(message, Some(b'#'))?)
 }
 
 pub fn has_staged_changes(repo: &Repository) -> Result<bool> {
@@ -135,28 +1026,465 @@ pub fn has_staged_changes(repo: &Repository) -> Result<bool> {
     }
 }
 
-pub fn get_remote_callbacks() -> RemoteCallbacks<'static> {
-    let mut callbacks = RemoteCallbacks::new();
-    callbacks.credentials(|url, _username_from_url, _allowed_types| {
-        let default_git_config = match Config::open_default() {
-            Ok(c) => c,
-            Err(e) => return Err(e),
-        };
-        let user_pass_opt = CredentialHelper::new(url).config(&default_git_config).execute();
-        match user_pass_opt {
-            Some((username, password)) => {
-                Cred::userpass_plaintext(username.as_str(), password.as_str())
-            },
-            None => {
-                Err(git2::Error::from_str("Error: Can't retrieve username and password from credential helper! Maybe you need to set a credential helper in your git config?"))
+// Like git, untracked files don't count since they don't get in the way of a rebase.
+pub fn has_tracked_changes(repo: &Repository) -> Result<bool> {
+    let mut status_options = StatusOptions::new();
+    status_options.include_untracked(false);
+    status_options.include_ignored(false);
+    status_options.exclude_submodules(true);
+    Ok(!repo.statuses(Some(&mut status_options))?.is_empty())
+}
+
+pub fn get_remote_names(repo: &Repository) -> Result<Vec<String>> {
+    let mut remote_names = vec![];
+    for remote_name_opt in repo.remotes()?.iter() {
+        remote_names.push(String::from(remote_name_opt.ok_or(Error::msg("Remote Name has invalid UTF-8!"))?));
+    }
+    Ok(remote_names)
+}
+
+#[derive(Clone)]
+pub struct RemoteInfo {
+    pub name: String,
+    pub url: String,
+    pub push_url_opt: Option<String>,
+    pub fetch_refspecs: Vec<String>,
+    pub push_refspecs: Vec<String>,
+}
+
+pub fn get_remote_infos(repo: &Repository) -> Result<Vec<RemoteInfo>> {
+    let mut remote_infos = vec![];
+    for remote_name in get_remote_names(repo)? {
+        let remote = repo.find_remote(&remote_name)?;
+        let mut fetch_refspecs = vec![];
+        let mut push_refspecs = vec![];
+        for refspec in remote.refspecs() {
+            let refspec_str = String::from(refspec.str().ok_or(Error::msg("Refspec has invalid UTF-8!"))?);
+            match refspec.direction() {
+                Direction::Fetch => fetch_refspecs.push(refspec_str),
+                Direction::Push => push_refspecs.push(refspec_str),
+            };
+        }
+        remote_infos.push(RemoteInfo {
+            url: String::from(remote.url().ok_or(Error::msg("Remote URL has invalid UTF-8!"))?),
+            push_url_opt: remote.pushurl().map(String::from),
+            name: remote_name,
+            fetch_refspecs,
+            push_refspecs,
+        });
+    }
+    Ok(remote_infos)
+}
+
+pub fn get_default_remote_name(repo: &Repository) -> Result<String> {
+    if let Ok(remote_name) = repo.config()?.get_string("remote.pushDefault") {
+        return Ok(remote_name);
+    }
+    let remote_string_array = repo.remotes()?;
+    if remote_string_array.iter().any(|remote_string_opt| remote_string_opt == Some("origin")) {
+        return Ok(String::from("origin"));
+    }
+    if remote_string_array.len() == 1 {
+        if let Some(remote_string) = remote_string_array.get(0) {
+            return Ok(String::from(remote_string));
+        }
+    }
+    bail!("Couldn't determine which remote to use! Please set remote.pushDefault in your git config or add a remote named 'origin'.")
+}
+
+pub fn get_remote_tracking_refname(remote: &Remote, remote_refname: &str) -> Result<Option<String>> {
+    for refspec in remote.refspecs() {
+        if refspec.direction() == Direction::Fetch && refspec.src_matches(remote_refname) {
+            let tracking_buf = refspec.transform(remote_refname)?;
+            let tracking_refname = tracking_buf.as_str().ok_or(Error::msg("Remote tracking ref name has invalid UTF-8!"))?;
+            return Ok(Some(String::from(tracking_refname)));
+        }
+    }
+    Ok(None)
+}
+
+// libgit2 keeps calling the credentials callback until authentication works, so this tracks what's been tried
+// to make sure each way of authenticating is only tried once instead of looping forever.
+struct CredentialAttempts {
+    has_tried_agent: bool,
+    remaining_ssh_key_paths: VecDeque<PathBuf>,
+    has_tried_helper: bool,
+    has_tried_prompt: bool,
+}
+
+impl CredentialAttempts {
+    fn new() -> Self {
+        Self {
+            has_tried_agent: false,
+            remaining_ssh_key_paths: get_ssh_key_paths().into(),
+            has_tried_helper: false,
+            has_tried_prompt: false,
+        }
+    }
+}
+
+// Gets the IdentityFiles from ~/.ssh/config followed by the default key files, same as ssh would try them.
+fn get_ssh_key_paths() -> Vec<PathBuf> {
+    let home_dir = match UserDirs::new() {
+        Some(ud) => PathBuf::from(ud.home_dir()),
+        None => return vec![],
+    };
+    let ssh_dir = home_dir.join(".ssh");
+    let mut key_paths = vec![];
+    if let Ok(ssh_config) = std::fs::read_to_string(ssh_dir.join("config")) {
+        for line in ssh_config.lines() {
+            let mut words = line.split_whitespace();
+            if words.next().is_some_and(|keyword| keyword.eq_ignore_ascii_case("IdentityFile")) {
+                if let Some(path_str) = words.next() {
+                    let path_str = path_str.trim_matches('"');
+                    key_paths.push(match path_str.strip_prefix("~/") {
+                        Some(relative_path) => home_dir.join(relative_path),
+                        None => PathBuf::from(path_str),
+                    });
+                }
+            }
+        }
+    }
+    for key_name in ["id_ed25519", "id_ecdsa", "id_rsa", "id_dsa"] {
+        key_paths.push(ssh_dir.join(key_name));
+    }
+    let mut seen_key_paths = HashSet::new();
+    key_paths.retain(|key_path| key_path.is_file() && seen_key_paths.insert(key_path.clone()));
+    key_paths
+}
+
+fn is_ssh_key_encrypted(key_path: &Path) -> bool {
+    let key_text = match std::fs::read_to_string(key_path) {
+        Ok(t) => t,
+        Err(_) => return false,
+    };
+    // PEM keys say so in their header.
+    if key_text.contains("ENCRYPTED") {
+        return true;
+    }
+    // OpenSSH keys start with "openssh-key-v1\0" followed by the cipher name, which is "none" if they aren't
+    // encrypted. This is that in base64.
+    if key_text.contains("BEGIN OPENSSH PRIVATE KEY") {
+        let body: String = key_text.lines().filter(|line| !line.starts_with("-----")).collect();
+        return !body.starts_with("b3BlbnNzaC1rZXktdjEAAAAABG5vbmU");
+    }
+    false
+}
+
+fn get_default_ssh_username() -> String {
+    std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_else(|_| String::from("git"))
+}
+
+fn get_credentials(progress: &Arc<Mutex<OperationProgress>>, attempts: &mut CredentialAttempts, url: &str, username_from_url: Option<&str>, allowed_types: CredentialType) -> Result<Cred, git2::Error> {
+    let username = username_from_url.map(String::from).unwrap_or_else(get_default_ssh_username);
+    if allowed_types.contains(CredentialType::USERNAME) {
+        return Cred::username(&username);
+    }
+
+    if allowed_types.contains(CredentialType::SSH_KEY) {
+        if !attempts.has_tried_agent {
+            attempts.has_tried_agent = true;
+            return Cred::ssh_key_from_agent(&username);
+        }
+        if let Some(key_path) = attempts.remaining_ssh_key_paths.pop_front() {
+            let mut passphrase_opt = None;
+            if is_ssh_key_encrypted(&key_path) {
+                match OperationProgress::prompt(progress, PromptRequest::SshPassphrase(key_path.to_string_lossy().into_owned())) {
+                    Some(PromptAnswer::Credentials { secret, .. }) => passphrase_opt = Some(secret),
+                    _ => return Err(git2::Error::from_str("Authentication was cancelled.")),
+                };
+            }
+            let public_key_path = key_path.with_extension("pub");
+            let public_key_path_opt = if public_key_path.is_file() { Some(public_key_path.as_path()) } else { None };
+            return Cred::ssh_key(&username, public_key_path_opt, &key_path, passphrase_opt.as_deref());
+        }
+    }
+
+    if allowed_types.contains(CredentialType::USER_PASS_PLAINTEXT) {
+        if !attempts.has_tried_helper {
+            attempts.has_tried_helper = true;
+            if let Ok(default_git_config) = Config::open_default() {
+                if let Some((helper_username, password)) = CredentialHelper::new(url).config(&default_git_config).username(username_from_url).execute() {
+                    return Cred::userpass_plaintext(&helper_username, &password);
+                }
+            }
+        }
+        if !attempts.has_tried_prompt {
+            attempts.has_tried_prompt = true;
+            let request = PromptRequest::UserPass { url: String::from(url), username_opt: username_from_url.map(String::from) };
+            return match OperationProgress::prompt(progress, request) {
+                Some(PromptAnswer::Credentials { username, secret }) => Cred::userpass_plaintext(&username, &secret),
+                _ => Err(git2::Error::from_str("Authentication was cancelled.")),
+            };
+        }
+    }
+
+    Err(git2::Error::from_str(&format!("Authentication failed for {}! Every available credential was rejected.", url)))
+}
+
+fn check_ssh_host_key(progress: &Arc<Mutex<OperationProgress>>, host_key: &CertHostkey, host: &str, port: u16) -> Result<CertificateCheckStatus, git2::Error> {
+    let (key, key_type) = match (host_key.hostkey(), host_key.hostkey_type()) {
+        (Some(key), Some(key_type)) => (key, key_type.name()),
+        _ => return Err(git2::Error::from_str(&format!("Couldn't get the host key for {} to check it!", host))),
+    };
+    let fingerprint = match host_key.hash_sha256() {
+        Some(hash) => format!("SHA256:{}", STANDARD_NO_PAD.encode(hash)),
+        None => format!("SHA1:{}", known_hosts::sha1_fingerprint(key)),
+    };
+    let to_git2_error = |e: Error| git2::Error::from_str(&e.to_string());
+    match known_hosts::check_host_key(host, port, key_type, key).map_err(to_git2_error)? {
+        HostKeyStatus::Known => Ok(CertificateCheckStatus::CertificateOk),
+        HostKeyStatus::Changed => Err(git2::Error::from_str(&format!(
+            "The host key for {} doesn't match the one in ~/.ssh/known_hosts! Someone could be impersonating the host, or its key may have been changed. If you're sure it's safe, remove the old key from known_hosts and try again.",
+            host
+        ))),
+        HostKeyStatus::Revoked => Err(git2::Error::from_str(&format!(
+            "The host key for {} has been revoked in ~/.ssh/known_hosts, so it can't be trusted!",
+            host
+        ))),
+        HostKeyStatus::Unknown => {
+            if progress.lock().unwrap().is_fingerprint_trusted(&fingerprint) {
+                return Ok(CertificateCheckStatus::CertificateOk);
+            }
+            let request = PromptRequest::UnknownHostKey { host: String::from(host), key_type: String::from(key_type), fingerprint: fingerprint.clone() };
+            match OperationProgress::prompt(progress, request) {
+                Some(PromptAnswer::AcceptAlways) => known_hosts::add_host_key(host, port, key_type, key).map_err(to_git2_error)?,
+                Some(_) => progress.lock().unwrap().trust_fingerprint(fingerprint),
+                None => return Err(git2::Error::from_str(&format!("The host key for {} was rejected.", host))),
+            };
+            Ok(CertificateCheckStatus::CertificateOk)
+        },
+    }
+}
+
+fn check_certificate(progress: &Arc<Mutex<OperationProgress>>, cert: &Cert, host: &str, port: u16) -> Result<CertificateCheckStatus, git2::Error> {
+    if let Some(host_key) = cert.as_hostkey() {
+        return check_ssh_host_key(progress, host_key, host, port);
+    }
+    if let Some(x509) = cert.as_x509() {
+        let fingerprint = known_hosts::sha1_fingerprint(x509.data());
+        let mut progress = progress.lock().unwrap();
+        if progress.is_fingerprint_trusted(&fingerprint) {
+            return Ok(CertificateCheckStatus::CertificateOk);
+        }
+        // Remember it in case libgit2 rejects it, so the user can be asked whether to trust it.
+        progress.set_last_certificate(host, fingerprint);
+    }
+    Ok(CertificateCheckStatus::CertificatePassthrough)
+}
+
+// git2 doesn't tell the certificate callback whether libgit2 could verify a TLS certificate, so they're left for
+// libgit2 to check. If it rejects one, this asks the user whether to trust it and runs network_fn again.
+pub fn with_certificate_prompt<T, F>(progress: &Arc<Mutex<OperationProgress>>, mut network_fn: F) -> Result<T>
+where
+    F: FnMut() -> Result<T, git2::Error>
+{
+    loop {
+        match network_fn() {
+            Err(e) if e.code() == ErrorCode::Certificate => {
+                let last_certificate_opt = progress.lock().unwrap().take_last_certificate();
+                let (host, fingerprint) = match last_certificate_opt {
+                    Some(last_certificate) => last_certificate,
+                    None => return Err(e.into()),
+                };
+                let request = PromptRequest::UntrustedCertificate { host: host.clone(), fingerprint: fingerprint.clone() };
+                match OperationProgress::prompt(progress, request) {
+                    Some(_) => progress.lock().unwrap().trust_fingerprint(fingerprint),
+                    None => bail!("The TLS certificate for {} was rejected.", host),
+                };
             },
+            res => return Ok(res?),
         }
+    }
+}
+
+// The url is only used to find which port to look up SSH host keys for, since libgit2 only gives the host.
+pub fn get_remote_callbacks<'a>(progress: &Arc<Mutex<OperationProgress>>, operation_name: &str, url: &str, direction: Direction) -> RemoteCallbacks<'a> {
+    progress.lock().unwrap().start_transfer(operation_name, matches!(direction, Direction::Push));
+    let mut callbacks = RemoteCallbacks::new();
+    // Returning false from these aborts the transfer, which is how it gets cancelled.
+    let progress_c = progress.clone();
+    callbacks.transfer_progress(move |stats| {
+        let mut progress = progress_c.lock().unwrap();
+        progress.update_transfer(&stats);
+        !progress.is_cancelled()
     });
-    callbacks.push_update_reference(|_ref_name, status_msg| {
-        match status_msg {
-            Some(m) => Err(git2::Error::from_str(&*format!("Error(s) during push: {}", m))),
-            None => Ok(()),
-        }
+    let progress_c = progress.clone();
+    callbacks.sideband_progress(move |data| {
+        let mut progress = progress_c.lock().unwrap();
+        progress.set_remote_msg(data);
+        !progress.is_cancelled()
+    });
+    let progress_c = progress.clone();
+    callbacks.push_transfer_progress(move |current, total, bytes| {
+        progress_c.lock().unwrap().update_push_transfer(current, total, bytes);
     });
+    let progress_c = progress.clone();
+    let mut attempts = CredentialAttempts::new();
+    callbacks.credentials(move |url, username_from_url, allowed_types| {
+        get_credentials(&progress_c, &mut attempts, url, username_from_url, allowed_types)
+    });
+    let progress_c = progress.clone();
+    let port = known_hosts::get_ssh_port(url);
+    callbacks.certificate_check(move |cert, host| check_certificate(&progress_c, cert, host, port));
     callbacks
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::path::Path;
+    use crate::backend::git_functions::{git_stage_lines, git_unstage_lines};
+    use crate::backend::test_repo::TestRepo;
+    use super::{get_file_diff, get_staged_changes, get_unstaged_changes, has_tracked_changes, FileDiff};
+
+    const FILE_NAME: &str = "file.txt";
+
+    struct Fixture {
+        test_repo: TestRepo,
+    }
+
+    impl Fixture {
+        // Commits the file with committed_content and then leaves working_content in the working tree.
+        fn new(committed_content: &str, working_content: &str) -> Self {
+            let test_repo = TestRepo::new();
+            test_repo.commit_file(FILE_NAME, committed_content, "Initial commit");
+            test_repo.write_file(FILE_NAME, working_content);
+            Self {
+                test_repo,
+            }
+        }
+
+        fn get_unstaged_file_diff(&self) -> FileDiff {
+            get_file_diff(&get_unstaged_changes(&self.test_repo.repo).unwrap(), FILE_NAME).unwrap().unwrap()
+        }
+
+        fn get_staged_file_diff(&self) -> FileDiff {
+            get_file_diff(&get_staged_changes(&self.test_repo.repo).unwrap(), FILE_NAME).unwrap().unwrap()
+        }
+
+        fn get_index_content(&self) -> String {
+            let index = self.test_repo.repo.index().unwrap();
+            let entry = index.get_path(Path::new(FILE_NAME), 0).unwrap();
+            String::from_utf8(self.test_repo.repo.find_blob(entry.id).unwrap().content().to_vec()).unwrap()
+        }
+
+        // Stages the lines in the unstaged diff whose content (with its origin in front) is in selected_lines.
+        fn stage(&self, selected_lines: &[&str]) {
+            let file_diff = self.get_unstaged_file_diff();
+            git_stage_lines(&self.test_repo.repo, &file_diff, &select(&file_diff, selected_lines)).unwrap();
+        }
+    }
+
+    // Selecting by content keeps the tests readable, so each selected line has to be unique in the diff.
+    fn select(file_diff: &FileDiff, selected_lines: &[&str]) -> HashSet<(usize, usize)> {
+        let mut selection = HashSet::new();
+        for selected_line in selected_lines {
+            let mut matches = vec![];
+            for (hunk_index, hunk) in file_diff.hunks.iter().enumerate() {
+                for (line_index, line) in hunk.lines.iter().enumerate() {
+                    if format!("{}{}", line.origin, line.content.trim_end_matches('\n')) == *selected_line {
+                        matches.push((hunk_index, line_index));
+                    }
+                }
+            }
+            assert_eq!(matches.len(), 1, "Expected exactly one line matching {:?}", selected_line);
+            selection.extend(matches);
+        }
+        selection
+    }
+
+    fn numbered_lines(count: usize) -> Vec<String> {
+        (1..=count).map(|i| format!("line {}\n", i)).collect()
+    }
+
+    #[test]
+    fn test_stage_added_lines_only() {
+        let fixture = Fixture::new("one\ntwo\nthree\n", "one\nnew a\ntwo\nthree\nnew b\n");
+        fixture.stage(&["+new b"]);
+        assert_eq!(fixture.get_index_content(), "one\ntwo\nthree\nnew b\n");
+    }
+
+    #[test]
+    fn test_stage_removed_lines_only() {
+        // Leaving out the addition that replaces "four" turns its removal into context.
+        let fixture = Fixture::new("one\ntwo\nthree\nfour\n", "one\nthree\nFOUR\n");
+        fixture.stage(&["-two"]);
+        assert_eq!(fixture.get_index_content(), "one\nthree\nfour\n");
+        fixture.stage(&["-four"]);
+        assert_eq!(fixture.get_index_content(), "one\nthree\n");
+    }
+
+    #[test]
+    fn test_stage_across_multiple_hunks() {
+        let committed_lines = numbered_lines(20);
+        let mut working_lines = committed_lines.clone();
+        working_lines.insert(1, String::from("inserted\n"));
+        working_lines[18] = String::from("changed 18\n");
+        let fixture = Fixture::new(&committed_lines.concat(), &working_lines.concat());
+        assert_eq!(fixture.get_unstaged_file_diff().hunks.len(), 2);
+
+        // The second hunk has to be shifted by the line added in the first.
+        fixture.stage(&["+inserted", "-line 18", "+changed 18"]);
+        assert_eq!(fixture.get_index_content(), working_lines.concat());
+    }
+
+    #[test]
+    fn test_stage_second_hunk_only() {
+        let committed_lines = numbered_lines(20);
+        let mut working_lines = committed_lines.clone();
+        working_lines.insert(1, String::from("inserted\n"));
+        working_lines.remove(18);
+        let fixture = Fixture::new(&committed_lines.concat(), &working_lines.concat());
+
+        fixture.stage(&["-line 18"]);
+        let mut expected_lines = committed_lines.clone();
+        expected_lines.remove(17);
+        assert_eq!(fixture.get_index_content(), expected_lines.concat());
+    }
+
+    #[test]
+    fn test_stage_line_without_newline_at_end_of_file() {
+        let fixture = Fixture::new("one\ntwo", "one\ntwo\nthree");
+        fixture.stage(&["-two", "+two"]);
+        assert_eq!(fixture.get_index_content(), "one\ntwo\n");
+        fixture.stage(&["+three"]);
+        assert_eq!(fixture.get_index_content(), "one\ntwo\nthree");
+    }
+
+    #[test]
+    fn test_stage_line_added_after_line_without_newline() {
+        // The last line gets its newline too since "three" couldn't be added after it otherwise.
+        let fixture = Fixture::new("one\ntwo", "one\ntwo\nthree");
+        fixture.stage(&["+three"]);
+        assert_eq!(fixture.get_index_content(), "one\ntwo\nthree");
+    }
+
+    #[test]
+    fn test_stage_line_without_newline_kept_as_context() {
+        // Only the added line is staged, so the old last line has to stay as it was without a newline.
+        let fixture = Fixture::new("one\ntwo", "zero\none\ntwo\nthree");
+        fixture.stage(&["+zero"]);
+        assert_eq!(fixture.get_index_content(), "zero\none\ntwo");
+    }
+
+    #[test]
+    fn test_unstage_some_lines() {
+        let fixture = Fixture::new("one\ntwo\nthree\n", "one\nTWO\nthree\nfour\n");
+        fixture.stage(&["-two", "+TWO", "+four"]);
+        assert_eq!(fixture.get_index_content(), "one\nTWO\nthree\nfour\n");
+
+        let file_diff = fixture.get_staged_file_diff();
+        git_unstage_lines(&fixture.test_repo.repo, &file_diff, &select(&file_diff, &["+four"])).unwrap();
+        assert_eq!(fixture.get_index_content(), "one\nTWO\nthree\n");
+    }
+
+    #[test]
+    fn test_has_tracked_changes_ignores_untracked_files() {
+        let fixture = Fixture::new("one\n", "one\n");
+        fixture.test_repo.write_file("untracked.txt", "untracked\n");
+        assert!(!has_tracked_changes(&fixture.test_repo.repo).unwrap());
+        fixture.test_repo.write_file(FILE_NAME, "two\n");
+        assert!(has_tracked_changes(&fixture.test_repo.repo).unwrap());
+    }
+}
@@ -0,0 +1,923 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use anyhow::{Error, Result};
+use git2::{BranchType, Oid, Reference, Repository, Sort};
+use crate::backend::git_functions::{get_revwalk_root_oids, git_revwalk_new_commits, RevwalkRoots};
+use crate::backend::git_utils;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GraphRefKind {
+    LocalBranch,
+    RemoteBranch,
+    Tag,
+    Stash,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraphRef {
+    pub kind: GraphRefKind,
+    pub shorthand: String,
+    pub is_head: bool,
+    // Annotated tags show their tagger and message when hovered.
+    pub tooltip_opt: Option<String>,
+}
+
+impl GraphRef {
+    fn new(kind: GraphRefKind, shorthand: String, is_head: bool) -> Self {
+        Self {
+            kind,
+            shorthand,
+            is_head,
+            tooltip_opt: None,
+        }
+    }
+
+    fn get_tag_tooltip(repo: &Repository, reference: &Reference) -> Option<String> {
+        let tag = repo.find_tag(reference.target()?).ok()?;
+        let tagger = match tag.tagger() {
+            Some(signature) => git_utils::format_signature(&signature),
+            None => String::from("Unknown Tagger"),
+        };
+        Some(format!("{}\n\n{}", tagger, String::from_utf8_lossy(tag.message_bytes().unwrap_or_default()).trim_end()))
+    }
+}
+
+// Refs whose commits aren't in the graph never get used since there's no row to put them on.
+fn get_commit_ref_map(repo: &Repository) -> Result<HashMap<Oid, Vec<GraphRef>>> {
+    let mut commit_ref_map: HashMap<Oid, Vec<GraphRef>> = HashMap::new();
+    for ref_result in repo.references()? {
+        let reference = ref_result?;
+        let shorthand = reference.shorthand().ok_or(Error::msg("Branch Shorthand has invalid UTF-8!"))?;
+
+        let mut is_head = false;
+        let kind;
+        if reference.is_branch() {
+            kind = GraphRefKind::LocalBranch;
+            if repo.find_branch(shorthand, BranchType::Local)?.is_head() {
+                is_head = true;
+            }
+        } else if reference.is_remote() && !shorthand.ends_with("/HEAD") {
+            kind = GraphRefKind::RemoteBranch;
+        } else if reference.is_tag() {
+            kind = GraphRefKind::Tag;
+        } else {
+            continue;
+        }
+
+        let target_oid = match reference.peel_to_commit() {
+            Ok(commit) => commit.id(),
+            Err(_) => continue,
+        };
+        let mut graph_ref = GraphRef::new(kind, String::from(shorthand), is_head);
+        if reference.is_tag() {
+            graph_ref.tooltip_opt = GraphRef::get_tag_tooltip(repo, &reference);
+        }
+        commit_ref_map.entry(target_oid).or_default().push(graph_ref);
+    }
+    // Only the latest stash has a ref, so label all of them from the stash list instead.
+    for stash in git_utils::get_stashes(repo)? {
+        commit_ref_map.entry(stash.oid).or_default().push(GraphRef::new(GraphRefKind::Stash, stash.get_name(), false));
+    }
+    Ok(commit_ref_map)
+}
+
+// Each walk gets its own id so rows loaded from a walk that's since been replaced by a rebuild can be ignored.
+static NEXT_WALK_ID: AtomicUsize = AtomicUsize::new(0);
+// The walk sends the commits it looks up in batches so the threads don't have to hand off to each other for every one,
+// and only stays this many batches ahead of the rows that have been loaded.
+const WALK_BATCH_SIZE: usize = 200;
+const WALK_LOOKAHEAD_BATCHES: usize = 10;
+
+type LoadedCommitReceiver = Receiver<Result<Vec<LoadedCommit>>>;
+
+// A commit that's been looked up for a row but not laid out yet.
+pub struct LoadedCommit {
+    oid: Oid,
+    summary: String,
+    // Only the parents that are in the graph.
+    parent_oids: Vec<Oid>,
+    first_parent_oid_opt: Option<Oid>,
+}
+
+impl LoadedCommit {
+    // Parents that aren't in the graph (e.g. past the end of a shallow clone) are left out.
+    fn new(repo: &Repository, oid: Oid, is_stash: bool, is_in_graph: impl Fn(Oid) -> bool) -> Result<Self> {
+        let commit = repo.find_commit(oid)?;
+        let mut parent_oids: Vec<Oid> = if is_stash {
+            // Only draw a stash's base commit as its parent since the others are never in the graph.
+            vec![commit.parent_id(0)?]
+        } else {
+            commit.parent_ids().collect()
+        };
+        parent_oids.retain(|parent_oid| is_in_graph(*parent_oid));
+        Ok(Self {
+            oid,
+            summary: String::from(commit.summary().ok_or(Error::msg("Commit summary has invalid UTF-8!"))?),
+            first_parent_oid_opt: commit.parent_id(0).ok().filter(|parent_oid| parent_oids.contains(parent_oid)),
+            parent_oids,
+        })
+    }
+}
+
+// Walks every commit reachable from root_oids in topological order and sends them once they've been looked up,
+// stopping early if the receiver is dropped. Stashes aren't walked since their other parents (the index and untracked
+// files) would clutter the graph, so each one is sent right before the commit it's based on instead.
+fn send_topological_walk(repo_path: &Path, root_oids: &[Oid], mut stash_base_map: HashMap<Oid, Vec<Oid>>, counted_total_arc: &Arc<Mutex<Option<usize>>>, commit_sender: &SyncSender<Result<Vec<LoadedCommit>>>) -> Result<()> {
+    let repo = Repository::open(repo_path)?;
+    let mut revwalk = repo.revwalk()?;
+    for oid in root_oids {
+        revwalk.push(*oid)?;
+    }
+    revwalk.set_sorting(Sort::TOPOLOGICAL)?;
+    let oids = revwalk.collect::<Result<Vec<Oid>, git2::Error>>()?;
+    let walked_oids: HashSet<Oid> = oids.iter().copied().collect();
+    let is_walked = |oid| walked_oids.contains(&oid);
+    let stash_count: usize = stash_base_map.values().map(|stash_oids| stash_oids.len()).sum();
+    *counted_total_arc.lock().unwrap() = Some(oids.len() + stash_count);
+
+    for oid_batch in oids.chunks(WALK_BATCH_SIZE) {
+        let mut loaded_commits = vec![];
+        for oid in oid_batch {
+            for stash_oid in stash_base_map.remove(oid).unwrap_or_default() {
+                loaded_commits.push(LoadedCommit::new(&repo, stash_oid, true, is_walked)?);
+            }
+            loaded_commits.push(LoadedCommit::new(&repo, *oid, false, is_walked)?);
+        }
+        if commit_sender.send(Ok(loaded_commits)).is_err() {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+// libgit2 has to walk the whole graph before it can give the first commit in topological order, so this is done on
+// its own thread and none of it holds up the UI. The same thread looks up the commits since its repo has them cached
+// from the walk. The total is set as soon as the walk is done.
+fn walk_topologically_in_background(repo: &Repository, root_oids: &[Oid], stash_base_map: HashMap<Oid, Vec<Oid>>) -> (LoadedCommitReceiver, Arc<Mutex<Option<usize>>>) {
+    let (commit_sender, commit_receiver) = mpsc::sync_channel(WALK_LOOKAHEAD_BATCHES);
+    let counted_total_arc = Arc::new(Mutex::new(None));
+    let counted_total_c = counted_total_arc.clone();
+    let repo_path = repo.path().to_path_buf();
+    let root_oids = root_oids.to_vec();
+    thread::spawn(move || {
+        if let Err(e) = send_topological_walk(&repo_path, &root_oids, stash_base_map, &counted_total_c, &commit_sender) {
+            // If this fails then the layout was dropped and there's no one to tell.
+            let _ = commit_sender.send(Err(e));
+        }
+    });
+    (commit_receiver, counted_total_arc)
+}
+
+// Takes the commits for more rows from the walk, which can mean waiting for it. This can be taken out of the layout
+// so the waiting is done without holding onto it, and GraphLayout::add_rows lays the commits out afterwards.
+pub struct GraphRowLoader {
+    walk_id: usize,
+    commit_receiver: LoadedCommitReceiver,
+    // What's left of the last batch after loading as many commits as were asked for.
+    leftover_commits: VecDeque<LoadedCommit>,
+    root_oids: Vec<Oid>,
+    include_stashes: bool,
+    stash_oids: HashSet<Oid>,
+    is_finished: bool,
+}
+
+impl GraphRowLoader {
+    // Loads the next count commits, then keeps going until through_oid_opt's commit is loaded if it's in the graph.
+    // Whether it's in the graph is checked first since the whole graph would have to be loaded to find out.
+    pub fn load_commits(&mut self, repo: &Repository, count: usize, through_oid_opt: Option<Oid>) -> Result<Vec<LoadedCommit>> {
+        let mut through_oid_opt = through_oid_opt.filter(|oid| self.is_in_graph(repo, *oid));
+        let mut loaded_commits = vec![];
+        while loaded_commits.len() < count || through_oid_opt.is_some() {
+            if self.leftover_commits.is_empty() {
+                match self.commit_receiver.recv() {
+                    Ok(loaded_commits_result) => self.leftover_commits.extend(loaded_commits_result?),
+                    Err(_) => {
+                        self.is_finished = true;
+                        break;
+                    },
+                }
+            }
+            while let Some(loaded_commit) = self.leftover_commits.pop_front() {
+                if through_oid_opt == Some(loaded_commit.oid) {
+                    through_oid_opt = None;
+                }
+                loaded_commits.push(loaded_commit);
+                if loaded_commits.len() >= count && through_oid_opt.is_none() {
+                    break;
+                }
+            }
+        }
+        Ok(loaded_commits)
+    }
+
+    fn is_in_graph(&self, repo: &Repository, oid: Oid) -> bool {
+        if self.stash_oids.contains(&oid) {
+            return self.include_stashes;
+        }
+        self.root_oids.iter().any(|root_oid| *root_oid == oid || repo.graph_descendant_of(*root_oid, oid).unwrap_or(false))
+    }
+}
+
+// A line from a lane in one row to a lane in the row below it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GraphEdge {
+    pub from_lane: usize,
+    pub to_lane: usize,
+}
+
+impl GraphEdge {
+    fn new(from_lane: usize, to_lane: usize) -> Self {
+        Self {
+            from_lane,
+            to_lane,
+        }
+    }
+}
+
+pub struct GraphLayoutRow {
+    pub oid: Oid,
+    pub lane: usize,
+    // The first lane that's clear of everything drawn in this row, which is where the refs and summary go.
+    pub summary_lane: usize,
+    pub refs: Vec<GraphRef>,
+    pub summary: String,
+    // These are the edges that start in this row.
+    pub edges: Vec<GraphEdge>,
+    // Only the parents that are in the graph.
+    parent_oids: Vec<Oid>,
+    first_parent_oid_opt: Option<Oid>,
+}
+
+// Lays out the graph from top to bottom, keeping track of which lanes have a line going through them. Each row only
+// has to look at the lanes in use instead of everything above it.
+#[derive(Default)]
+struct ActiveLanes {
+    // The commit each lane's line is going to, or None if the lane is free.
+    lanes: Vec<Option<Oid>>,
+    // The edges leaving the last row placed, as (from lane, lane the edge is in when it reaches the next row).
+    pending_edges: Vec<(usize, usize)>,
+}
+
+impl ActiveLanes {
+    fn get_free_lane(&mut self) -> usize {
+        match self.lanes.iter().position(|lane| lane.is_none()) {
+            Some(lane) => lane,
+            None => {
+                self.lanes.push(None);
+                self.lanes.len() - 1
+            },
+        }
+    }
+
+    // Returns the commit's lane and the edges from the row above into this one. Commits must be placed in
+    // topological order and parent_oids must only have parents that come later in the graph.
+    fn place_commit(&mut self, oid: Oid, parent_oids: &[Oid]) -> (usize, Vec<GraphEdge>) {
+        // Use the leftmost lane coming into this commit, or start a new one if this is a branch tip.
+        let commit_lane = match self.lanes.iter().position(|lane| *lane == Some(oid)) {
+            Some(lane) => lane,
+            None => self.get_free_lane(),
+        };
+        let mut edges_in = vec![];
+        for (from_lane, lane) in self.pending_edges.drain(..) {
+            let to_lane = if self.lanes[lane] == Some(oid) { commit_lane } else { lane };
+            edges_in.push(GraphEdge::new(from_lane, to_lane));
+        }
+        for lane in &mut self.lanes {
+            if *lane == Some(oid) {
+                *lane = None;
+            }
+        }
+
+        // The first parent continues straight down and any others branch off to the side.
+        let mut edges_out_of_commit = vec![];
+        let mut started_lanes = vec![];
+        for (i, parent_oid) in parent_oids.iter().enumerate() {
+            if i == 0 {
+                self.lanes[commit_lane] = Some(*parent_oid);
+            } else if let Some(parent_lane) = self.lanes.iter().position(|lane| *lane == Some(*parent_oid)) {
+                edges_out_of_commit.push((commit_lane, parent_lane));
+            } else {
+                let parent_lane = self.get_free_lane();
+                self.lanes[parent_lane] = Some(*parent_oid);
+                started_lanes.push(parent_lane);
+                edges_out_of_commit.push((commit_lane, parent_lane));
+            }
+        }
+        while self.lanes.last().is_some_and(|lane| lane.is_none()) {
+            self.lanes.pop();
+        }
+
+        for (i, lane) in self.lanes.iter().enumerate() {
+            // Lanes that were just started for this commit's other parents start at the commit instead.
+            if lane.is_some() && !started_lanes.contains(&i) {
+                self.pending_edges.push((i, i));
+            }
+        }
+        self.pending_edges.extend(edges_out_of_commit);
+        (commit_lane, edges_in)
+    }
+}
+
+// The commits are looked up and laid out a page at a time as they're needed, since doing it for every commit in a huge
+// history is slow.
+pub struct GraphLayout {
+    revwalk_roots: RevwalkRoots,
+    root_oids: Vec<Oid>,
+    walk_id: usize,
+    // This is None while it's been taken to load more rows.
+    row_loader_opt: Option<GraphRowLoader>,
+    row_index_map: HashMap<Oid, usize>,
+    stash_oids: HashSet<Oid>,
+    commit_ref_map: HashMap<Oid, Vec<GraphRef>>,
+    active_lanes: ActiveLanes,
+    rows: Vec<GraphLayoutRow>,
+    counted_total_arc: Arc<Mutex<Option<usize>>>,
+    // Commits that refreshes have added on top since the walk was started.
+    added_count: usize,
+}
+
+impl GraphLayout {
+    pub fn new(repo: &Repository, revwalk_roots: RevwalkRoots) -> Result<Self> {
+        let root_oids = get_revwalk_root_oids(repo, revwalk_roots)?;
+        let stashes = git_utils::get_stashes(repo)?;
+        let mut stash_base_map: HashMap<Oid, Vec<Oid>> = HashMap::new();
+        if revwalk_roots.include_stashes {
+            for stash in &stashes {
+                stash_base_map.entry(stash.base_oid).or_default().push(stash.oid);
+            }
+        }
+        let stash_oids: HashSet<Oid> = stashes.iter().map(|stash| stash.oid).collect();
+        let (commit_receiver, counted_total_arc) = walk_topologically_in_background(repo, &root_oids, stash_base_map);
+        let walk_id = NEXT_WALK_ID.fetch_add(1, Ordering::Relaxed);
+        Ok(Self {
+            revwalk_roots,
+            row_loader_opt: Some(GraphRowLoader {
+                walk_id,
+                commit_receiver,
+                leftover_commits: VecDeque::new(),
+                root_oids: root_oids.clone(),
+                include_stashes: revwalk_roots.include_stashes,
+                stash_oids: stash_oids.clone(),
+                is_finished: false,
+            }),
+            root_oids,
+            walk_id,
+            row_index_map: HashMap::new(),
+            stash_oids,
+            commit_ref_map: get_commit_ref_map(repo)?,
+            active_lanes: ActiveLanes::default(),
+            rows: vec![],
+            counted_total_arc,
+            added_count: 0,
+        })
+    }
+
+    pub fn get_rows(&self) -> &[GraphLayoutRow] {
+        &self.rows
+    }
+
+    // This is None until the background walk finishes, and doesn't know about anything that's changed since then.
+    pub fn get_estimated_total_count(&self) -> Option<usize> {
+        let counted_total = (*self.counted_total_arc.lock().unwrap())?;
+        Some((counted_total + self.added_count).max(self.rows.len()))
+    }
+
+    pub fn is_fully_loaded(&self) -> bool {
+        self.row_loader_opt.as_ref().is_some_and(|row_loader| row_loader.is_finished)
+    }
+
+    // Only commits that have been laid out have an index.
+    pub fn get_row_index(&self, oid: Oid) -> Option<usize> {
+        self.row_index_map.get(&oid).copied()
+    }
+
+    // Returns None if it's already been taken, in which case it'll be given back with add_rows.
+    pub fn take_row_loader(&mut self) -> Option<GraphRowLoader> {
+        self.row_loader_opt.take()
+    }
+
+    // Lays out the commits the row loader loaded and gives it back. Rows that are already laid out never change
+    // lanes, but the last one only gets its edges out once the row after it is laid out. If the layout was rebuilt
+    // since the row loader was taken, the commits are from the old walk and are thrown away along with it.
+    pub fn add_rows(&mut self, row_loader: GraphRowLoader, loaded_commits: Vec<LoadedCommit>) {
+        if row_loader.walk_id != self.walk_id {
+            return;
+        }
+        for loaded_commit in loaded_commits {
+            self.push_loaded_commit(loaded_commit);
+        }
+        self.row_loader_opt = Some(row_loader);
+    }
+
+    // Loads and lays out the next count rows all at once, for when the layout is already locked anyway.
+    pub fn load_rows(&mut self, repo: &Repository, count: usize) -> Result<()> {
+        if let Some(mut row_loader) = self.take_row_loader() {
+            let loaded_commits_res = row_loader.load_commits(repo, count, None);
+            match loaded_commits_res {
+                Ok(loaded_commits) => self.add_rows(row_loader, loaded_commits),
+                Err(e) => {
+                    self.row_loader_opt = Some(row_loader);
+                    return Err(e);
+                },
+            }
+        }
+        Ok(())
+    }
+
+    // Follows first parents from the row at start_index down to the row at end_index, leaving out stashes. This skips
+    // any commits from other branches that are drawn in between. Returns None if end_index isn't on that line.
+    pub fn get_first_parent_line(&self, start_index: usize, end_index: usize) -> Option<Vec<Oid>> {
+        let mut oids = vec![];
+        let mut i = start_index;
+        loop {
+            let layout_row = self.rows.get(i)?;
+            if !self.stash_oids.contains(&layout_row.oid) {
+                oids.push(layout_row.oid);
+            }
+            if i == end_index {
+                return Some(oids);
+            }
+            i = self.get_row_index(layout_row.first_parent_oid_opt?).filter(|j| *j <= end_index)?;
+        }
+    }
+
+    fn push_loaded_commit(&mut self, loaded_commit: LoadedCommit) {
+        let layout_row = GraphLayoutRow {
+            oid: loaded_commit.oid,
+            lane: 0,
+            summary_lane: 0,
+            refs: self.commit_ref_map.remove(&loaded_commit.oid).unwrap_or_default(),
+            summary: loaded_commit.summary,
+            edges: vec![],
+            parent_oids: loaded_commit.parent_oids,
+            first_parent_oid_opt: loaded_commit.first_parent_oid_opt,
+        };
+        self.push_row(layout_row);
+    }
+
+    fn push_row(&mut self, mut layout_row: GraphLayoutRow) {
+        let (lane, edges_in) = self.active_lanes.place_commit(layout_row.oid, &layout_row.parent_oids);
+        layout_row.lane = lane;
+        layout_row.edges = vec![];
+        self.row_index_map.insert(layout_row.oid, self.rows.len());
+        self.rows.push(layout_row);
+        let i = self.rows.len() - 1;
+        if i > 0 {
+            self.rows[i - 1].edges = edges_in;
+            self.set_summary_lane(i - 1);
+        }
+        self.set_summary_lane(i);
+    }
+
+    // Only walks and looks up commits that are new since the last refresh (e.g. from a fetch), which go on top. The
+    // rows that were already loaded keep their commit info, but lines from the new commits can push them into other
+    // lanes, so all of them are placed again under the new ones. That doesn't touch the repo but does go over every
+    // loaded row. If nothing new was added (e.g. a tag was created), only the ref labels are updated. Anything that
+    // could remove commits rebuilds the whole layout.
+    pub fn refresh(&mut self, repo: &Repository, revwalk_roots: RevwalkRoots) -> Result<()> {
+        let loaded_count = self.rows.len();
+        let root_oids = get_revwalk_root_oids(repo, revwalk_roots)?;
+        let stash_oids: HashSet<Oid> = git_utils::get_stashes(repo)?.iter().map(|stash| stash.oid).collect();
+        if revwalk_roots != self.revwalk_roots || stash_oids != self.stash_oids || !self.is_graph_still_reachable(repo, &root_oids) {
+            *self = GraphLayout::new(repo, revwalk_roots)?;
+            return self.load_rows(repo, loaded_count);
+        }
+
+        let new_oids = git_revwalk_new_commits(repo, &root_oids, &self.root_oids)?;
+        // Each new commit's parents are either new too or already in the graph, as long as they're in the repo.
+        let odb = repo.odb()?;
+        let new_commits = new_oids.into_iter().map(|oid| LoadedCommit::new(repo, oid, false, |parent_oid| odb.exists(parent_oid))).collect::<Result<Vec<LoadedCommit>>>()?;
+        self.root_oids = root_oids;
+        let mut commit_ref_map = get_commit_ref_map(repo)?;
+        if new_commits.is_empty() {
+            for layout_row in &mut self.rows {
+                layout_row.refs = commit_ref_map.remove(&layout_row.oid).unwrap_or_default();
+            }
+            self.commit_ref_map = commit_ref_map;
+            return Ok(());
+        }
+
+        // New commits can't be ancestors of anything already in the graph, so they can all go above it.
+        self.added_count += new_commits.len();
+        self.commit_ref_map = commit_ref_map;
+        self.row_index_map.clear();
+        self.active_lanes = ActiveLanes::default();
+        let old_rows = std::mem::take(&mut self.rows);
+        for loaded_commit in new_commits {
+            self.push_loaded_commit(loaded_commit);
+        }
+        for mut layout_row in old_rows {
+            layout_row.refs = self.commit_ref_map.remove(&layout_row.oid).unwrap_or_default();
+            self.push_row(layout_row);
+        }
+        Ok(())
+    }
+
+    // Every commit in the graph is still reachable if each old root either is still a root or is an ancestor of one.
+    fn is_graph_still_reachable(&self, repo: &Repository, root_oids: &[Oid]) -> bool {
+        self.root_oids.iter().all(|old_root_oid| {
+            root_oids.contains(old_root_oid) || root_oids.iter().any(|root_oid| repo.graph_descendant_of(*root_oid, *old_root_oid).unwrap_or(false))
+        })
+    }
+
+    // Puts the summary to the right of anything drawn in the row, including edges coming in from above.
+    fn set_summary_lane(&mut self, i: usize) {
+        let get_edges_max_lane = |row: &GraphLayoutRow| row.edges.iter().map(|edge| edge.from_lane.max(edge.to_lane)).max().unwrap_or(0);
+        let previous_max_lane = if i > 0 { get_edges_max_lane(&self.rows[i - 1]) } else { 0 };
+        let layout_row = &mut self.rows[i];
+        layout_row.summary_lane = layout_row.lane.max(get_edges_max_lane(layout_row)).max(previous_max_lane) + 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use git2::{Commit, Oid, Repository, Signature, StashFlags, Time};
+    use crate::backend::git_functions::RevwalkRoots;
+    use crate::backend::test_repo::{TestRepo, TEST_EMAIL, TEST_NAME};
+    use super::{GraphLayout, GraphLayoutRow, GraphRefKind};
+
+    struct Fixture {
+        test_repo: TestRepo,
+        time: i64,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            Self {
+                test_repo: TestRepo::new(),
+                time: 1_700_000_000,
+            }
+        }
+
+        fn get_signature(&self) -> Signature<'static> {
+            Signature::new(TEST_NAME, TEST_EMAIL, &Time::new(self.time, 0)).unwrap()
+        }
+
+        // Each commit is a minute after the last so the revwalk order doesn't depend on timing.
+        fn commit(&mut self, message: &str, parent_oids: &[Oid]) -> Oid {
+            self.time += 60;
+            let signature = self.get_signature();
+            let tree = self.test_repo.repo.find_tree(self.test_repo.repo.treebuilder(None).unwrap().write().unwrap()).unwrap();
+            let parents: Vec<Commit> = parent_oids.iter().map(|oid| self.test_repo.repo.find_commit(*oid).unwrap()).collect();
+            let parent_refs: Vec<&Commit> = parents.iter().collect();
+            self.test_repo.repo.commit(None, &signature, &signature, message, &tree, &parent_refs).unwrap()
+        }
+
+        fn set_ref(&self, ref_name: &str, oid: Oid) {
+            self.test_repo.repo.reference(ref_name, oid, true, "").unwrap();
+        }
+
+        fn get_layout(&self, revwalk_roots: RevwalkRoots) -> Vec<GraphLayoutRow> {
+            let mut graph_layout = GraphLayout::new(&self.test_repo.repo, revwalk_roots).unwrap();
+            graph_layout.load_rows(&self.test_repo.repo, usize::MAX).unwrap();
+            assert!(graph_layout.is_fully_loaded());
+            assert_edges_reach_parents(&self.test_repo.repo, graph_layout.get_rows());
+            graph_layout.rows
+        }
+    }
+
+    // A row as (summary, lane, edges as (from lane, to lane)) so expected layouts are easy to read.
+    type RowDescription<'a> = (&'a str, usize, Vec<(usize, usize)>);
+
+    fn describe(layout_rows: &[GraphLayoutRow]) -> Vec<RowDescription<'_>> {
+        layout_rows.iter().map(|row| {
+            (row.summary.as_str(), row.lane, row.edges.iter().map(|edge| (edge.from_lane, edge.to_lane)).collect())
+        }).collect()
+    }
+
+    // Checks that following the edges down from each commit leads to each of its parents that's in the graph.
+    fn assert_edges_reach_parents(repo: &Repository, layout_rows: &[GraphLayoutRow]) {
+        for (i, layout_row) in layout_rows.iter().enumerate() {
+            let commit = repo.find_commit(layout_row.oid).unwrap();
+            let is_stash = layout_row.refs.iter().any(|graph_ref| graph_ref.kind == GraphRefKind::Stash);
+            for (parent_index, parent_oid) in commit.parent_ids().enumerate() {
+                let j = match layout_rows.iter().position(|row| row.oid == parent_oid) {
+                    Some(j) if !is_stash || parent_index == 0 => j,
+                    _ => continue,
+                };
+                let mut reachable_lanes = vec![layout_row.lane];
+                for row in &layout_rows[i..j] {
+                    reachable_lanes = row.edges.iter().filter(|edge| reachable_lanes.contains(&edge.from_lane)).map(|edge| edge.to_lane).collect();
+                }
+                assert!(reachable_lanes.contains(&layout_rows[j].lane), "No edges from {} to its parent {}", layout_row.summary, layout_rows[j].summary);
+            }
+        }
+    }
+
+    #[test]
+    fn test_linear_history() {
+        let mut fixture = Fixture::new();
+        let c1 = fixture.commit("c1", &[]);
+        let c2 = fixture.commit("c2", &[c1]);
+        let c3 = fixture.commit("c3", &[c2]);
+        fixture.set_ref("refs/heads/main", c3);
+
+        let layout_rows = fixture.get_layout(RevwalkRoots::default());
+        assert_eq!(describe(&layout_rows), vec![
+            ("c3", 0, vec![(0, 0)]),
+            ("c2", 0, vec![(0, 0)]),
+            ("c1", 0, vec![]),
+        ]);
+        assert!(layout_rows.iter().all(|row| row.summary_lane == 1));
+        assert_eq!(layout_rows[0].refs.len(), 1);
+        assert_eq!(layout_rows[0].refs[0].shorthand, "main");
+        assert!(layout_rows[0].refs[0].is_head);
+    }
+
+    #[test]
+    fn test_branchy_history() {
+        let mut fixture = Fixture::new();
+        let base = fixture.commit("base", &[]);
+        let m1 = fixture.commit("m1", &[base]);
+        let f1 = fixture.commit("f1", &[base]);
+        let f2 = fixture.commit("f2", &[f1]);
+        let t1 = fixture.commit("t1", &[m1]);
+        let merge = fixture.commit("merge feature", &[m1, f2]);
+        fixture.set_ref("refs/heads/main", merge);
+        fixture.set_ref("refs/heads/topic", t1);
+
+        let layout_rows = fixture.get_layout(RevwalkRoots::default());
+        assert_eq!(describe(&layout_rows), vec![
+            ("t1", 0, vec![(0, 0)]),
+            ("merge feature", 1, vec![(0, 0), (1, 1), (1, 2)]),
+            ("f2", 2, vec![(0, 0), (1, 1), (2, 2)]),
+            ("f1", 2, vec![(0, 0), (1, 0), (2, 2)]),
+            ("m1", 0, vec![(0, 0), (2, 0)]),
+            ("base", 0, vec![]),
+        ]);
+        let summary_lanes: Vec<usize> = layout_rows.iter().map(|row| row.summary_lane).collect();
+        assert_eq!(summary_lanes, vec![1, 3, 3, 3, 3, 3]);
+    }
+
+    #[test]
+    fn test_criss_cross_merges() {
+        let mut fixture = Fixture::new();
+        let base = fixture.commit("base", &[]);
+        let a1 = fixture.commit("a1", &[base]);
+        let b1 = fixture.commit("b1", &[base]);
+        let a2 = fixture.commit("a2", &[a1, b1]);
+        let b2 = fixture.commit("b2", &[b1, a1]);
+        fixture.set_ref("refs/heads/main", a2);
+        fixture.set_ref("refs/heads/b", b2);
+
+        assert_eq!(describe(&fixture.get_layout(RevwalkRoots::default())), vec![
+            ("a2", 0, vec![(0, 0), (0, 1)]),
+            ("b2", 2, vec![(0, 0), (1, 1), (2, 2), (2, 0)]),
+            ("a1", 0, vec![(0, 0), (1, 1), (2, 1)]),
+            ("b1", 1, vec![(0, 0), (1, 0)]),
+            ("base", 0, vec![]),
+        ]);
+    }
+
+    #[test]
+    fn test_octopus_merge() {
+        let mut fixture = Fixture::new();
+        let base = fixture.commit("base", &[]);
+        let x = fixture.commit("x", &[base]);
+        let y = fixture.commit("y", &[base]);
+        let z = fixture.commit("z", &[base]);
+        let octopus = fixture.commit("octopus", &[base, x, y, z]);
+        fixture.set_ref("refs/heads/main", octopus);
+
+        assert_eq!(describe(&fixture.get_layout(RevwalkRoots::default())), vec![
+            ("octopus", 0, vec![(0, 0), (0, 1), (0, 2), (0, 3)]),
+            ("z", 3, vec![(0, 0), (1, 1), (2, 2), (3, 3)]),
+            ("y", 2, vec![(0, 0), (1, 1), (2, 2), (3, 3)]),
+            ("x", 1, vec![(0, 0), (1, 0), (2, 0), (3, 0)]),
+            ("base", 0, vec![]),
+        ]);
+    }
+
+    #[test]
+    fn test_refs_only_label_commits_in_graph() {
+        let mut fixture = Fixture::new();
+        let base = fixture.commit("base", &[]);
+        let tagged = fixture.commit("tagged", &[base]);
+        let remote = fixture.commit("remote", &[base]);
+        fixture.set_ref("refs/heads/main", base);
+        fixture.test_repo.repo.tag("v1", &fixture.test_repo.repo.find_object(tagged, None).unwrap(), &fixture.get_signature(), "Release", false).unwrap();
+        fixture.set_ref("refs/remotes/origin/main", remote);
+
+        let layout_rows = fixture.get_layout(RevwalkRoots::default());
+        assert_eq!(describe(&layout_rows), vec![
+            ("tagged", 0, vec![(0, 0)]),
+            ("remote", 1, vec![(0, 0), (1, 0)]),
+            ("base", 0, vec![]),
+        ]);
+        assert_eq!(layout_rows[0].refs[0].kind, GraphRefKind::Tag);
+        assert_eq!(layout_rows[1].refs[0].kind, GraphRefKind::RemoteBranch);
+        assert_eq!(layout_rows[0].refs[0].tooltip_opt.as_deref(), Some("Test <test@example.com>\n\nRelease"));
+
+        let revwalk_roots = RevwalkRoots { include_remote_branches: false, include_tags: false, include_stashes: true };
+        let layout_rows = fixture.get_layout(revwalk_roots);
+        assert_eq!(describe(&layout_rows), vec![("base", 0, vec![])]);
+        assert_eq!(layout_rows[0].refs.len(), 1);
+        assert_eq!(layout_rows[0].refs[0].kind, GraphRefKind::LocalBranch);
+    }
+
+    #[test]
+    fn test_stash_only_links_to_base_commit() {
+        let mut fixture = Fixture::new();
+        let c1 = fixture.commit("c1", &[]);
+        fixture.set_ref("refs/heads/main", c1);
+        fixture.test_repo.write_file("file.txt", "changes");
+        let signature = fixture.get_signature();
+        fixture.test_repo.repo.stash_save(&signature, "wip", Some(StashFlags::INCLUDE_UNTRACKED)).unwrap();
+
+        let layout_rows = fixture.get_layout(RevwalkRoots::default());
+        assert_eq!(describe(&layout_rows), vec![
+            ("On main: wip", 0, vec![(0, 0)]),
+            ("c1", 0, vec![]),
+        ]);
+        assert_eq!(layout_rows[0].refs[0].kind, GraphRefKind::Stash);
+        assert_eq!(layout_rows[0].refs[0].shorthand, "stash@{0}");
+
+        let revwalk_roots = RevwalkRoots { include_stashes: false, ..RevwalkRoots::default() };
+        assert_eq!(describe(&fixture.get_layout(revwalk_roots)), vec![("c1", 0, vec![])]);
+    }
+
+    #[test]
+    fn test_first_parent_line_skips_other_branches_and_stashes() {
+        let mut fixture = Fixture::new();
+        let base = fixture.commit("base", &[]);
+        let m1 = fixture.commit("m1", &[base]);
+        let f1 = fixture.commit("f1", &[base]);
+        let m2 = fixture.commit("m2", &[m1]);
+        let f2 = fixture.commit("f2", &[f1]);
+        fixture.set_ref("refs/heads/main", m2);
+        fixture.set_ref("refs/heads/feature", f2);
+
+        let mut graph_layout = GraphLayout::new(&fixture.test_repo.repo, RevwalkRoots::default()).unwrap();
+        graph_layout.load_rows(&fixture.test_repo.repo, usize::MAX).unwrap();
+        assert_eq!(graph_layout.get_rows().iter().map(|row| row.oid).collect::<Vec<Oid>>(), vec![m2, m1, f2, f1, base]);
+        // The feature commits drawn between main's commits and their base aren't part of main's line.
+        assert_eq!(graph_layout.get_first_parent_line(0, 4), Some(vec![m2, m1, base]));
+        assert_eq!(graph_layout.get_first_parent_line(2, 4), Some(vec![f2, f1, base]));
+        assert_eq!(graph_layout.get_first_parent_line(0, 3), None);
+
+        fixture.test_repo.write_file("file.txt", "changes");
+        let signature = fixture.get_signature();
+        let stash = fixture.test_repo.repo.stash_save(&signature, "wip", Some(StashFlags::INCLUDE_UNTRACKED)).unwrap();
+        let mut graph_layout = GraphLayout::new(&fixture.test_repo.repo, RevwalkRoots::default()).unwrap();
+        graph_layout.load_rows(&fixture.test_repo.repo, usize::MAX).unwrap();
+        let get_index = |oid| graph_layout.get_row_index(oid).unwrap();
+        assert_eq!(get_index(stash), 0);
+        assert_eq!(graph_layout.get_first_parent_line(get_index(stash), get_index(m1)), Some(vec![m2, m1]));
+    }
+
+    #[test]
+    fn test_loading_in_pages_matches_loading_all_at_once() {
+        let mut fixture = Fixture::new();
+        let base = fixture.commit("base", &[]);
+        let a1 = fixture.commit("a1", &[base]);
+        let b1 = fixture.commit("b1", &[base]);
+        let a2 = fixture.commit("a2", &[a1, b1]);
+        let b2 = fixture.commit("b2", &[b1, a1]);
+        let c1 = fixture.commit("c1", &[b2]);
+        fixture.set_ref("refs/heads/main", a2);
+        fixture.set_ref("refs/heads/c", c1);
+        let expected_rows = fixture.get_layout(RevwalkRoots::default());
+
+        let mut graph_layout = GraphLayout::new(&fixture.test_repo.repo, RevwalkRoots::default()).unwrap();
+        assert!(!graph_layout.is_fully_loaded());
+        let mut previous_lanes = vec![];
+        while !graph_layout.is_fully_loaded() {
+            graph_layout.load_rows(&fixture.test_repo.repo, 2).unwrap();
+            // Rows that were already laid out have to stay put.
+            let lanes: Vec<usize> = graph_layout.get_rows().iter().map(|row| row.lane).collect();
+            assert_eq!(lanes[..previous_lanes.len()], previous_lanes);
+            previous_lanes = lanes;
+        }
+        assert_eq!(describe(graph_layout.get_rows()), describe(&expected_rows));
+        let summary_lanes: Vec<usize> = graph_layout.get_rows().iter().map(|row| row.summary_lane).collect();
+        let expected_summary_lanes: Vec<usize> = expected_rows.iter().map(|row| row.summary_lane).collect();
+        assert_eq!(summary_lanes, expected_summary_lanes);
+        assert_eq!(graph_layout.get_row_index(base), Some(expected_rows.len() - 1));
+        // The walk sets the total before it sends anything.
+        assert_eq!(graph_layout.get_estimated_total_count(), Some(expected_rows.len()));
+    }
+
+    #[test]
+    fn test_loading_through_a_commit() {
+        let mut fixture = Fixture::new();
+        let c1 = fixture.commit("c1", &[]);
+        let c2 = fixture.commit("c2", &[c1]);
+        let c3 = fixture.commit("c3", &[c2]);
+        let c4 = fixture.commit("c4", &[c3]);
+        let remote = fixture.commit("remote", &[c4]);
+        fixture.set_ref("refs/heads/main", c4);
+        fixture.set_ref("refs/remotes/origin/main", remote);
+        let revwalk_roots = RevwalkRoots { include_remote_branches: false, ..RevwalkRoots::default() };
+
+        let mut graph_layout = GraphLayout::new(&fixture.test_repo.repo, revwalk_roots).unwrap();
+        let mut row_loader = graph_layout.take_row_loader().unwrap();
+        assert!(graph_layout.take_row_loader().is_none());
+        // This would have to load everything to find out it's not in the graph.
+        let loaded_commits = row_loader.load_commits(&fixture.test_repo.repo, 1, Some(remote)).unwrap();
+        graph_layout.add_rows(row_loader, loaded_commits);
+        assert_eq!(graph_layout.get_rows().len(), 1);
+
+        let mut row_loader = graph_layout.take_row_loader().unwrap();
+        let loaded_commits = row_loader.load_commits(&fixture.test_repo.repo, 1, Some(c2)).unwrap();
+        graph_layout.add_rows(row_loader, loaded_commits);
+        assert_eq!(graph_layout.get_row_index(c2), Some(2));
+        assert_eq!(graph_layout.get_rows().len(), 3);
+        assert!(!graph_layout.is_fully_loaded());
+    }
+
+    #[test]
+    fn test_rows_from_before_a_rebuild_are_thrown_away() {
+        let mut fixture = Fixture::new();
+        let c1 = fixture.commit("c1", &[]);
+        let c2 = fixture.commit("c2", &[c1]);
+        let feature = fixture.commit("feature", &[c1]);
+        fixture.set_ref("refs/heads/main", c2);
+        fixture.set_ref("refs/heads/feature", feature);
+        let mut graph_layout = GraphLayout::new(&fixture.test_repo.repo, RevwalkRoots::default()).unwrap();
+        let mut row_loader = graph_layout.take_row_loader().unwrap();
+        let loaded_commits = row_loader.load_commits(&fixture.test_repo.repo, usize::MAX, None).unwrap();
+
+        fixture.test_repo.repo.find_reference("refs/heads/feature").unwrap().delete().unwrap();
+        graph_layout.refresh(&fixture.test_repo.repo, RevwalkRoots::default()).unwrap();
+        graph_layout.add_rows(row_loader, loaded_commits);
+        assert!(graph_layout.get_rows().is_empty());
+        graph_layout.load_rows(&fixture.test_repo.repo, usize::MAX).unwrap();
+        assert_eq!(describe(graph_layout.get_rows()), vec![("c2", 0, vec![(0, 0)]), ("c1", 0, vec![])]);
+    }
+
+    #[test]
+    fn test_refresh_with_only_label_changes_keeps_rows() {
+        let mut fixture = Fixture::new();
+        let c1 = fixture.commit("c1", &[]);
+        let c2 = fixture.commit("c2", &[c1]);
+        fixture.set_ref("refs/heads/main", c2);
+        let mut graph_layout = GraphLayout::new(&fixture.test_repo.repo, RevwalkRoots::default()).unwrap();
+        graph_layout.load_rows(&fixture.test_repo.repo, usize::MAX).unwrap();
+        // A relayout would recalculate this.
+        graph_layout.rows[1].summary_lane = 99;
+
+        fixture.set_ref("refs/heads/old", c1);
+        fixture.test_repo.repo.tag_lightweight("v1", &fixture.test_repo.repo.find_object(c1, None).unwrap(), false).unwrap();
+        graph_layout.refresh(&fixture.test_repo.repo, RevwalkRoots::default()).unwrap();
+
+        assert_eq!(describe(graph_layout.get_rows()), vec![("c2", 0, vec![(0, 0)]), ("c1", 0, vec![])]);
+        assert_eq!(graph_layout.rows[1].summary_lane, 99);
+        let ref_names: Vec<&str> = graph_layout.rows[1].refs.iter().map(|graph_ref| graph_ref.shorthand.as_str()).collect();
+        assert_eq!(ref_names, vec!["old", "v1"]);
+    }
+
+    #[test]
+    fn test_refresh_after_fetch_adds_new_commits_on_top() {
+        let mut fixture = Fixture::new();
+        let c1 = fixture.commit("c1", &[]);
+        let c2 = fixture.commit("c2", &[c1]);
+        let local = fixture.commit("local", &[c2]);
+        fixture.set_ref("refs/heads/main", local);
+        fixture.set_ref("refs/remotes/origin/main", c2);
+        let mut graph_layout = GraphLayout::new(&fixture.test_repo.repo, RevwalkRoots::default()).unwrap();
+        // Only some of the rows are loaded, so the rest have to be loaded after the new ones are added.
+        graph_layout.load_rows(&fixture.test_repo.repo, 2).unwrap();
+
+        let r1 = fixture.commit("r1", &[c2]);
+        let r2 = fixture.commit("r2", &[r1]);
+        fixture.set_ref("refs/remotes/origin/main", r2);
+        graph_layout.refresh(&fixture.test_repo.repo, RevwalkRoots::default()).unwrap();
+        assert_eq!(graph_layout.get_rows().len(), 4);
+
+        graph_layout.load_rows(&fixture.test_repo.repo, usize::MAX).unwrap();
+        // The walk started before the fetch, so it only knows about the new commits from the refresh.
+        assert_eq!(graph_layout.get_estimated_total_count(), Some(5));
+        assert_edges_reach_parents(&fixture.test_repo.repo, graph_layout.get_rows());
+        assert_eq!(describe(graph_layout.get_rows()), vec![
+            ("r2", 0, vec![(0, 0)]),
+            ("r1", 0, vec![(0, 0)]),
+            ("local", 1, vec![(0, 0), (1, 0)]),
+            ("c2", 0, vec![(0, 0)]),
+            ("c1", 0, vec![]),
+        ]);
+        assert_eq!(graph_layout.get_rows()[0].refs[0].shorthand, "origin/main");
+        assert_eq!(graph_layout.get_row_index(c1), Some(4));
+    }
+
+    #[test]
+    fn test_refresh_rebuilds_when_commits_are_removed() {
+        let mut fixture = Fixture::new();
+        let c1 = fixture.commit("c1", &[]);
+        let c2 = fixture.commit("c2", &[c1]);
+        let feature = fixture.commit("feature", &[c1]);
+        fixture.set_ref("refs/heads/main", c2);
+        fixture.set_ref("refs/heads/feature", feature);
+        let mut graph_layout = GraphLayout::new(&fixture.test_repo.repo, RevwalkRoots::default()).unwrap();
+        graph_layout.load_rows(&fixture.test_repo.repo, usize::MAX).unwrap();
+        assert_eq!(graph_layout.get_rows().len(), 3);
+
+        fixture.test_repo.repo.find_reference("refs/heads/feature").unwrap().delete().unwrap();
+        graph_layout.refresh(&fixture.test_repo.repo, RevwalkRoots::default()).unwrap();
+        assert_eq!(describe(graph_layout.get_rows()), vec![("c2", 0, vec![(0, 0)]), ("c1", 0, vec![])]);
+        assert_eq!(graph_layout.get_row_index(feature), None);
+    }
+}
@@ -0,0 +1,278 @@
+// Times GraphLayout against the layout CommitGraph::get_graph_rows used to do, on generated repos. It's ignored since
+// it takes a while, so run it with `cargo test --release graph_layout_benchmark -- --ignored --nocapture`.
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use git2::{Commit, Oid, Repository, Signature, Time};
+use crate::backend::git_functions::{get_revwalk_root_oids, RevwalkRoots};
+use crate::backend::graph_layout::GraphLayout;
+use crate::backend::test_repo::{TestRepo, TEST_EMAIL, TEST_NAME};
+
+// A small xorshift generator so the generated histories are the same every run without pulling in a crate for it.
+struct Random(u64);
+
+impl Random {
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn choose(&mut self, len: usize) -> usize {
+        ((self.next_f64() * len as f64) as usize).min(len - 1)
+    }
+}
+
+struct Generator {
+    test_repo: TestRepo,
+    tree_oid: Oid,
+    time: i64,
+}
+
+impl Generator {
+    fn new() -> Self {
+        let test_repo = TestRepo::new();
+        let tree_oid = test_repo.repo.treebuilder(None).unwrap().write().unwrap();
+        Self {
+            test_repo,
+            tree_oid,
+            time: 1_600_000_000,
+        }
+    }
+
+    fn commit(&mut self, message: &str, parent_oids: &[Oid]) -> Oid {
+        self.time += 60;
+        let repo = &self.test_repo.repo;
+        let signature = Signature::new(TEST_NAME, TEST_EMAIL, &Time::new(self.time, 0)).unwrap();
+        let tree = repo.find_tree(self.tree_oid).unwrap();
+        let parents: Vec<Commit> = parent_oids.iter().map(|oid| repo.find_commit(*oid).unwrap()).collect();
+        let parent_refs: Vec<&Commit> = parents.iter().collect();
+        repo.commit(None, &signature, &signature, message, &tree, &parent_refs).unwrap()
+    }
+
+    fn set_branch(&self, name: &str, oid: Oid) {
+        self.test_repo.repo.reference(&format!("refs/heads/{}", name), oid, true, "").unwrap();
+    }
+
+    // Up to max_branch_count branches are worked on at once, each starting from main and eventually merged back in
+    // with a chance of merge_chance per commit. Branches that are still open at the end are left as they are, and with
+    // no branches this is just a line of commits on main.
+    fn generate(commit_count: usize, max_branch_count: usize, merge_chance: f64) -> Self {
+        let mut generator = Generator::new();
+        let mut random = Random(42);
+        let mut main_oid = generator.commit("root", &[]);
+        let mut branches: Vec<(usize, Oid)> = vec![];
+        let mut next_branch_id = 0;
+        for i in 0..commit_count {
+            let r = random.next_f64();
+            if branches.len() < max_branch_count && r < 0.1 {
+                next_branch_id += 1;
+                branches.push((next_branch_id, generator.commit(&format!("start b{}", next_branch_id), &[main_oid])));
+            } else if !branches.is_empty() && r < 0.1 + merge_chance {
+                let (branch_id, branch_oid) = branches.remove(random.choose(branches.len()));
+                main_oid = generator.commit(&format!("merge b{}", branch_id), &[main_oid, branch_oid]);
+            } else if !branches.is_empty() && r < 0.85 {
+                let j = random.choose(branches.len());
+                branches[j].1 = generator.commit(&format!("work b{} {}", branches[j].0, i), &[branches[j].1]);
+            } else {
+                main_oid = generator.commit(&format!("main {}", i), &[main_oid]);
+            }
+        }
+        generator.set_branch("main", main_oid);
+        for (branch_id, branch_oid) in branches {
+            generator.set_branch(&format!("b{}", branch_id), branch_oid);
+        }
+        generator
+    }
+}
+
+// The layout from before GraphLayout, minus the drawing. Every row is behind its own mutex and lanes are found by
+// searching a table of which lanes are in use in each row.
+mod old_layout {
+    use std::collections::{HashMap, HashSet};
+    use std::sync::{Arc, Mutex};
+    use git2::{Oid, Repository, Sort};
+    use crate::backend::git_utils;
+
+    pub struct LocationIndex {
+        pub x: usize,
+        pub y: usize,
+    }
+
+    pub struct GraphRow {
+        pub oid: Oid,
+        pub circle_location: LocationIndex,
+        pub summary_location: LocationIndex,
+        pub summary: String,
+        pub lines: Vec<(LocationIndex, LocationIndex)>,
+    }
+
+    fn line(start_x: usize, start_y: usize, end_x: usize, end_y: usize) -> (LocationIndex, LocationIndex) {
+        (LocationIndex { x: start_x, y: start_y }, LocationIndex { x: end_x, y: end_y })
+    }
+
+    // The generated repos have no stashes, so this leaves out putting them above their base commits.
+    pub fn git_revwalk(repo: &Repository, root_oids: &[Oid]) -> Vec<Oid> {
+        let mut revwalk = repo.revwalk().unwrap();
+        for oid in root_oids {
+            revwalk.push(*oid).unwrap();
+        }
+        revwalk.set_sorting(Sort::TOPOLOGICAL).unwrap();
+        revwalk.map(|oid_result| oid_result.unwrap()).collect()
+    }
+
+    pub fn get_graph_rows(repo: &Repository, oid_vec: &[Oid]) -> Vec<Arc<Mutex<GraphRow>>> {
+        let mut graph_rows = vec![];
+        let mut commit_map: HashMap<Oid, Arc<Mutex<GraphRow>>> = HashMap::new();
+        let mut commit_parent_oid_map: HashMap<Oid, Vec<Oid>> = HashMap::new();
+        let stash_oids: HashSet<Oid> = git_utils::get_stashes(repo).unwrap().iter().map(|stash| stash.oid).collect();
+
+        for (i, oid) in oid_vec.iter().enumerate() {
+            let git_commit = repo.find_commit(*oid).unwrap();
+            if stash_oids.contains(oid) {
+                commit_parent_oid_map.insert(*oid, vec![git_commit.parent_id(0).unwrap()]);
+            } else {
+                commit_parent_oid_map.insert(*oid, git_commit.parents().map(|p| p.id()).collect());
+            }
+            let graph_row_arc = Arc::new(Mutex::new(GraphRow {
+                oid: *oid,
+                circle_location: LocationIndex { x: 0, y: i },
+                summary_location: LocationIndex { x: 1, y: i },
+                summary: String::from(git_commit.summary().unwrap()),
+                lines: vec![],
+            }));
+            commit_map.insert(*oid, graph_row_arc.clone());
+            graph_rows.push(graph_row_arc);
+        }
+
+        let mut occupied_locations_table: Vec<Vec<usize>> = vec![];
+        for graph_row_arc in &graph_rows {
+            let mut graph_row = graph_row_arc.lock().unwrap();
+            if graph_row.circle_location.y < occupied_locations_table.len() {
+                while occupied_locations_table[graph_row.circle_location.y].contains(&graph_row.circle_location.x) {
+                    graph_row.circle_location.x += 1;
+                }
+                occupied_locations_table[graph_row.circle_location.y].push(graph_row.circle_location.x);
+            } else {
+                occupied_locations_table.push(vec![graph_row.circle_location.x]);
+            }
+
+            if let Some(parent_oids) = commit_parent_oid_map.get(&graph_row.oid) {
+                for parent_oid in parent_oids {
+                    if let Some(parent_graph_row_arc) = commit_map.get(parent_oid) {
+                        let mut parent_graph_row = parent_graph_row_arc.lock().unwrap();
+                        let mut moved_x_val = 0;
+                        for i in (graph_row.circle_location.y + 1)..parent_graph_row.circle_location.y {
+                            let mut x_val = graph_row.circle_location.x;
+                            if i < occupied_locations_table.len() {
+                                while occupied_locations_table[i].contains(&x_val) {
+                                    x_val += 1;
+                                    moved_x_val = x_val;
+                                }
+                                occupied_locations_table[i].push(x_val);
+                            } else {
+                                occupied_locations_table.push(vec![x_val]);
+                            }
+                        }
+                        parent_graph_row.circle_location.x = moved_x_val;
+                    }
+                }
+            }
+        }
+
+        for graph_row_arc in &graph_rows {
+            let graph_row = graph_row_arc.lock().unwrap();
+            if let Some(parent_oids) = commit_parent_oid_map.get(&graph_row.oid) {
+                for parent_oid in parent_oids {
+                    if let Some(parent_commit_arc) = commit_map.get(parent_oid) {
+                        let parent_graph_row = parent_commit_arc.lock().unwrap();
+                        if graph_row.circle_location.x < parent_graph_row.circle_location.x {
+                            occupied_locations_table[graph_row.circle_location.y].push(parent_graph_row.circle_location.x);
+                        } else if graph_row.circle_location.x > parent_graph_row.circle_location.x {
+                            occupied_locations_table[parent_graph_row.circle_location.y].push(graph_row.circle_location.x);
+                        }
+                    }
+                }
+            }
+        }
+
+        for graph_row_arc in &graph_rows {
+            let mut graph_row = graph_row_arc.lock().unwrap();
+            graph_row.summary_location.x = *occupied_locations_table[graph_row.circle_location.y].iter().max().unwrap_or(&0) + 1;
+            if let Some(parent_oids) = commit_parent_oid_map.get(&graph_row.oid) {
+                for parent_oid in parent_oids {
+                    if let Some(parent_commit_arc) = commit_map.get(parent_oid) {
+                        let parent_graph_row = parent_commit_arc.lock().unwrap();
+                        let child_x = graph_row.circle_location.x;
+                        let child_y = graph_row.circle_location.y;
+                        let parent_x = parent_graph_row.circle_location.x;
+                        let parent_y = parent_graph_row.circle_location.y;
+                        let before_parent_y = parent_graph_row.circle_location.y - 1;
+                        if before_parent_y != child_y {
+                            let (line_x, start_index, end_index) = if parent_x > child_x {
+                                (parent_x, child_y + 1, before_parent_y)
+                            } else {
+                                (child_x, child_y, before_parent_y - 1)
+                            };
+                            for (i, row_arc) in graph_rows.iter().enumerate().take(end_index + 1).skip(start_index) {
+                                if i == child_y {
+                                    graph_row.lines.push(line(line_x, i, line_x, i + 1));
+                                } else {
+                                    row_arc.lock().unwrap().lines.push(line(line_x, i, line_x, i + 1));
+                                }
+                            }
+                        }
+                        if before_parent_y == child_y {
+                            graph_row.lines.push(line(child_x, before_parent_y, parent_x, parent_y));
+                        } else {
+                            graph_rows[before_parent_y].lock().unwrap().lines.push(line(child_x, before_parent_y, parent_x, parent_y));
+                        }
+                    }
+                }
+            }
+        }
+        graph_rows
+    }
+}
+
+// The old layout is given the full walk like it used to do for itself, so both times include walking and looking up
+// every commit.
+// Returns how long it took and the summaries in row order.
+fn time_old_layout(repo_path: &Path) -> (f64, Vec<String>) {
+    let start = Instant::now();
+    let repo = &Repository::open(repo_path).unwrap();
+    let root_oids = get_revwalk_root_oids(repo, RevwalkRoots::default()).unwrap();
+    let oids = old_layout::git_revwalk(repo, &root_oids);
+    let graph_rows: Vec<Arc<Mutex<old_layout::GraphRow>>> = old_layout::get_graph_rows(repo, &oids);
+    let elapsed = start.elapsed().as_secs_f64();
+    (elapsed, graph_rows.iter().map(|graph_row_arc| graph_row_arc.lock().unwrap().summary.clone()).collect())
+}
+
+// Returns the time to the first page of rows, the time to all of them, and the summaries in row order.
+fn time_new_layout(repo_path: &Path) -> (f64, f64, Vec<String>) {
+    let start = Instant::now();
+    let repo = &Repository::open(repo_path).unwrap();
+    let mut graph_layout = GraphLayout::new(repo, RevwalkRoots::default()).unwrap();
+    graph_layout.load_rows(repo, 2000).unwrap();
+    let first_page_time = start.elapsed().as_secs_f64();
+    graph_layout.load_rows(repo, usize::MAX).unwrap();
+    let elapsed = start.elapsed().as_secs_f64();
+    (first_page_time, elapsed, graph_layout.get_rows().iter().map(|layout_row| layout_row.summary.clone()).collect())
+}
+
+#[test]
+#[ignore]
+fn benchmark_graph_layout() {
+    let histories = [("linear", 100_000, 0, 0.0), ("branchy", 50_000, 8, 0.1), ("wide", 20_000, 40, 0.02)];
+    for (name, commit_count, max_branch_count, merge_chance) in histories {
+        let generator = Generator::generate(commit_count, max_branch_count, merge_chance);
+        // Each one opens the repo itself so neither starts with commits the other already has cached.
+        let repo_path = generator.test_repo.repo.path();
+        let (old_time, old_summaries) = time_old_layout(repo_path);
+        let (first_page_time, new_time, new_summaries) = time_new_layout(repo_path);
+        // Both walk the same way, so they should have the same rows even though they're laid out differently.
+        assert_eq!(old_summaries, new_summaries);
+        println!("{}: {} commits, old {:.3}s, new {:.3}s ({:.3}s to the first page)", name, new_summaries.len(), old_time, new_time, first_page_time);
+    }
+}
@@ -0,0 +1,221 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use anyhow::{bail, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use directories::UserDirs;
+use hmac::{Hmac, Mac};
+use sha1::{Digest, Sha1};
+
+const DEFAULT_SSH_PORT: u16 = 22;
+
+#[derive(PartialEq)]
+pub enum HostKeyStatus {
+    Known,
+    Unknown,
+    // The host is known but with a different key, which could mean someone is impersonating it.
+    Changed,
+    // The key was marked as @revoked, so it can't be trusted even if it's also listed as known.
+    Revoked,
+}
+
+fn hmac_sha1(key: &[u8], message: &[u8]) -> Vec<u8> {
+    // HMAC accepts keys of any length, so this can't fail.
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).unwrap();
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+pub fn sha1_fingerprint(bytes: &[u8]) -> String {
+    Sha1::digest(bytes).iter().map(|b| format!("{:02X}", b)).collect::<Vec<String>>().join(":")
+}
+
+// Supports '*' and '?' wildcards, same as ssh.
+fn matches_pattern(pattern: &[u8], host: &[u8]) -> bool {
+    match (pattern.first(), host.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => matches_pattern(&pattern[1..], host) || (!host.is_empty() && matches_pattern(pattern, &host[1..])),
+        (Some(b'?'), Some(_)) => matches_pattern(&pattern[1..], &host[1..]),
+        (Some(p), Some(h)) if p.eq_ignore_ascii_case(h) => matches_pattern(&pattern[1..], &host[1..]),
+        _ => false,
+    }
+}
+
+fn matches_host_field(host_field: &str, host: &str) -> bool {
+    // Hashed entries look like "|1|<base64 salt>|<base64 HMAC-SHA1 of the host>".
+    if let Some(hashed) = host_field.strip_prefix("|1|") {
+        let (salt, hash) = match hashed.split_once('|') {
+            Some(parts) => parts,
+            None => return false,
+        };
+        return match (STANDARD.decode(salt), STANDARD.decode(hash)) {
+            (Ok(salt), Ok(hash)) => hmac_sha1(&salt, host.as_bytes()) == hash,
+            _ => false,
+        };
+    }
+    let mut is_match = false;
+    for pattern in host_field.split(',') {
+        if let Some(negated_pattern) = pattern.strip_prefix('!') {
+            if matches_pattern(negated_pattern.as_bytes(), host.as_bytes()) {
+                return false;
+            }
+        } else if matches_pattern(pattern.as_bytes(), host.as_bytes()) {
+            is_match = true;
+        }
+    }
+    is_match
+}
+
+// Only ssh:// style URLs can have a port, scp-like ones (e.g. git@host:path) always use the default.
+pub fn get_ssh_port(url: &str) -> u16 {
+    let authority = match url.split_once("://") {
+        Some((_, rest)) => rest.split('/').next().unwrap_or_default(),
+        None => return DEFAULT_SSH_PORT,
+    };
+    let host_and_port = authority.rsplit_once('@').map_or(authority, |(_, host_and_port)| host_and_port);
+    // IPv6 addresses are in brackets since they have colons in them, e.g. [::1]:2222.
+    let port_opt = match host_and_port.rsplit_once(']') {
+        Some((_, after_host)) => after_host.strip_prefix(':'),
+        None => host_and_port.split_once(':').map(|(_, port)| port),
+    };
+    port_opt.and_then(|port| port.parse().ok()).unwrap_or(DEFAULT_SSH_PORT)
+}
+
+// This is how ssh names hosts in known_hosts, with the port only when it isn't the default.
+fn get_host_name(host: &str, port: u16) -> String {
+    if port == DEFAULT_SSH_PORT {
+        String::from(host)
+    } else {
+        format!("[{}]:{}", host, port)
+    }
+}
+
+fn get_known_hosts_path() -> Result<PathBuf> {
+    match UserDirs::new() {
+        Some(ud) => Ok(ud.home_dir().join(".ssh").join("known_hosts")),
+        None => bail!("Couldn't find your home directory to look for ~/.ssh/known_hosts!"),
+    }
+}
+
+pub fn check_host_key(host: &str, port: u16, key_type: &str, key: &[u8]) -> Result<HostKeyStatus> {
+    check_host_key_in(&get_known_hosts_path()?, host, port, key_type, key)
+}
+
+fn check_host_key_in(known_hosts_path: &Path, host: &str, port: u16, key_type: &str, key: &[u8]) -> Result<HostKeyStatus> {
+    let known_hosts = match std::fs::read_to_string(known_hosts_path) {
+        Ok(k) => k,
+        Err(_) => return Ok(HostKeyStatus::Unknown),
+    };
+    let host_name = get_host_name(host, port);
+    let encoded_key = STANDARD.encode(key);
+    let mut status = HostKeyStatus::Unknown;
+    for line in known_hosts.lines() {
+        let mut fields: Vec<&str> = line.split_whitespace().collect();
+        let is_revoked = fields.first() == Some(&"@revoked");
+        if is_revoked {
+            fields.remove(0);
+        }
+        // Skip comments, blank lines and lines with other markers like @cert-authority.
+        if fields.len() < 3 || fields[0].starts_with('#') || fields[0].starts_with('@') {
+            continue;
+        }
+        if fields[1] != key_type || !matches_host_field(fields[0], &host_name) {
+            continue;
+        }
+        // A revoked key is rejected wherever it's listed, so keep looking after finding it as known.
+        if fields[2] == encoded_key {
+            if is_revoked {
+                return Ok(HostKeyStatus::Revoked);
+            }
+            status = HostKeyStatus::Known;
+        } else if !is_revoked && status != HostKeyStatus::Known {
+            status = HostKeyStatus::Changed;
+        }
+    }
+    Ok(status)
+}
+
+pub fn add_host_key(host: &str, port: u16, key_type: &str, key: &[u8]) -> Result<()> {
+    add_host_key_to(&get_known_hosts_path()?, host, port, key_type, key)
+}
+
+fn add_host_key_to(known_hosts_path: &Path, host: &str, port: u16, key_type: &str, key: &[u8]) -> Result<()> {
+    if let Some(ssh_dir) = known_hosts_path.parent() {
+        std::fs::create_dir_all(ssh_dir)?;
+    }
+    // Make sure the new entry doesn't get added onto the end of the last line.
+    let needs_newline = std::fs::read_to_string(known_hosts_path).is_ok_and(|k| !k.is_empty() && !k.ends_with('\n'));
+    let mut known_hosts_file = OpenOptions::new().create(true).append(true).open(known_hosts_path)?;
+    if needs_newline {
+        writeln!(known_hosts_file)?;
+    }
+    writeln!(known_hosts_file, "{} {} {}", get_host_name(host, port), key_type, STANDARD.encode(key))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use tempfile::TempDir;
+    use super::{add_host_key_to, check_host_key_in, get_ssh_port, hmac_sha1, HostKeyStatus};
+
+    const KEY_TYPE: &str = "ssh-ed25519";
+    const KEY: &[u8] = b"host key";
+    const OTHER_KEY: &[u8] = b"other host key";
+
+    #[test]
+    fn test_get_ssh_port() {
+        assert_eq!(get_ssh_port("git@example.com:user/repo.git"), 22);
+        assert_eq!(get_ssh_port("ssh://git@example.com/user/repo.git"), 22);
+        assert_eq!(get_ssh_port("ssh://git@example.com:2222/user/repo.git"), 2222);
+        assert_eq!(get_ssh_port("ssh://example.com:2222"), 2222);
+        assert_eq!(get_ssh_port("ssh://git@[::1]:2222/repo.git"), 2222);
+        assert_eq!(get_ssh_port("ssh://git@[::1]/repo.git"), 22);
+    }
+
+    #[test]
+    fn test_added_host_key_is_found_again() {
+        let dir = TempDir::new().unwrap();
+        let known_hosts_path = dir.path().join(".ssh").join("known_hosts");
+        add_host_key_to(&known_hosts_path, "example.com", 22, KEY_TYPE, KEY).unwrap();
+        add_host_key_to(&known_hosts_path, "example.com", 2222, KEY_TYPE, OTHER_KEY).unwrap();
+
+        let known_hosts = std::fs::read_to_string(&known_hosts_path).unwrap();
+        let host_names: Vec<&str> = known_hosts.lines().map(|line| line.split(' ').next().unwrap()).collect();
+        assert_eq!(host_names, vec!["example.com", "[example.com]:2222"]);
+        assert!(check_host_key_in(&known_hosts_path, "example.com", 22, KEY_TYPE, KEY).unwrap() == HostKeyStatus::Known);
+        assert!(check_host_key_in(&known_hosts_path, "example.com", 2222, KEY_TYPE, OTHER_KEY).unwrap() == HostKeyStatus::Known);
+        assert!(check_host_key_in(&known_hosts_path, "example.com", 2222, KEY_TYPE, KEY).unwrap() == HostKeyStatus::Changed);
+        assert!(check_host_key_in(&known_hosts_path, "example.com", 2200, KEY_TYPE, KEY).unwrap() == HostKeyStatus::Unknown);
+    }
+
+    #[test]
+    fn test_hashed_host_with_port() {
+        let dir = TempDir::new().unwrap();
+        let known_hosts_path = dir.path().join("known_hosts");
+        let salt = b"01234567890123456789";
+        let hash = hmac_sha1(salt, b"[example.com]:2222");
+        let line = format!("|1|{}|{} {} {}\n", STANDARD.encode(salt), STANDARD.encode(hash), KEY_TYPE, STANDARD.encode(KEY));
+        std::fs::write(&known_hosts_path, line).unwrap();
+
+        assert!(check_host_key_in(&known_hosts_path, "example.com", 2222, KEY_TYPE, KEY).unwrap() == HostKeyStatus::Known);
+        assert!(check_host_key_in(&known_hosts_path, "example.com", 22, KEY_TYPE, KEY).unwrap() == HostKeyStatus::Unknown);
+    }
+
+    #[test]
+    fn test_revoked_key_is_rejected_even_if_known() {
+        let dir = TempDir::new().unwrap();
+        let known_hosts_path = dir.path().join("known_hosts");
+        add_host_key_to(&known_hosts_path, "example.com", 22, KEY_TYPE, KEY).unwrap();
+        add_host_key_to(&known_hosts_path, "example.com", 22, KEY_TYPE, OTHER_KEY).unwrap();
+        let revoked_line = format!("@revoked *.com {} {}\n", KEY_TYPE, STANDARD.encode(KEY));
+        let known_hosts = std::fs::read_to_string(&known_hosts_path).unwrap();
+        std::fs::write(&known_hosts_path, revoked_line + &known_hosts).unwrap();
+
+        assert!(check_host_key_in(&known_hosts_path, "example.com", 22, KEY_TYPE, KEY).unwrap() == HostKeyStatus::Revoked);
+        assert!(check_host_key_in(&known_hosts_path, "example.com", 22, KEY_TYPE, OTHER_KEY).unwrap() == HostKeyStatus::Known);
+        assert!(check_host_key_in(&known_hosts_path, "example.org", 22, KEY_TYPE, KEY).unwrap() == HostKeyStatus::Unknown);
+    }
+}
@@ -1,2 +1,9 @@
 pub mod git_utils;
 pub mod git_functions;
+pub mod graph_layout;
+pub mod known_hosts;
+pub mod progress;
+#[cfg(test)]
+pub mod test_repo;
+#[cfg(test)]
+mod graph_layout_benchmark;
@@ -0,0 +1,233 @@
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Sender};
+
+pub enum PromptRequest {
+    // The path of the key file.
+    SshPassphrase(String),
+    UserPass { url: String, username_opt: Option<String> },
+    UnknownHostKey { host: String, key_type: String, fingerprint: String },
+    // For TLS certificates that libgit2 couldn't verify.
+    UntrustedCertificate { host: String, fingerprint: String },
+}
+
+pub enum PromptAnswer {
+    Credentials { username: String, secret: String },
+    // Trust the host key or certificate for the rest of the operation.
+    AcceptOnce,
+    // Trust the host key from now on by adding it to known_hosts.
+    AcceptAlways,
+}
+
+// Something the thread doing a git operation needs to ask the user, which the UI answers.
+pub struct Prompt {
+    pub request: PromptRequest,
+    answer_sender: Sender<Option<PromptAnswer>>,
+}
+
+impl Prompt {
+    pub fn answer(self, answer_opt: Option<PromptAnswer>) {
+        // The operation may have already given up waiting, in which case nobody needs the answer.
+        let _ = self.answer_sender.send(answer_opt);
+    }
+}
+
+// Shared between the thread doing a git operation and the UI so the UI can show how far along it is.
+#[derive(Default)]
+pub struct OperationProgress {
+    is_cancelled: bool,
+    operation_name: String,
+    total_objects: usize,
+    received_objects: usize,
+    indexed_objects: usize,
+    received_bytes: usize,
+    is_push: bool,
+    remote_msg: String,
+    prompt_opt: Option<Prompt>,
+    // Host key and certificate fingerprints the user accepted for this operation.
+    trusted_fingerprints: Vec<String>,
+    // The last TLS certificate that was left for libgit2 to verify, as (host, fingerprint).
+    last_certificate_opt: Option<(String, String)>,
+}
+
+impl OperationProgress {
+    pub fn is_transferring(&self) -> bool {
+        !self.operation_name.is_empty()
+    }
+
+    pub fn cancel(&mut self) {
+        self.is_cancelled = true;
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.is_cancelled
+    }
+
+    // libgit2 ignores the return value of the push progress callback, so pushes can't be stopped part way.
+    pub fn can_cancel(&self) -> bool {
+        !self.is_push
+    }
+
+    // Blocks until the UI answers, so this must not be called while progress is locked. Returns None if the user
+    // closed the prompt.
+    pub fn prompt(progress: &Arc<Mutex<OperationProgress>>, request: PromptRequest) -> Option<PromptAnswer> {
+        let (answer_sender, answer_receiver) = mpsc::channel();
+        progress.lock().unwrap().prompt_opt = Some(Prompt { request, answer_sender });
+        answer_receiver.recv().ok().flatten()
+    }
+
+    pub fn take_prompt(&mut self) -> Option<Prompt> {
+        self.prompt_opt.take()
+    }
+
+    pub fn trust_fingerprint(&mut self, fingerprint: String) {
+        self.trusted_fingerprints.push(fingerprint);
+    }
+
+    pub fn is_fingerprint_trusted(&self, fingerprint: &str) -> bool {
+        self.trusted_fingerprints.iter().any(|f| f == fingerprint)
+    }
+
+    pub fn set_last_certificate(&mut self, host: &str, fingerprint: String) {
+        self.last_certificate_opt = Some((String::from(host), fingerprint));
+    }
+
+    pub fn take_last_certificate(&mut self) -> Option<(String, String)> {
+        self.last_certificate_opt.take()
+    }
+
+    // Called at the start of each transfer since one operation can have several (e.g. fetching each remote).
+    pub fn start_transfer(&mut self, operation_name: &str, is_push: bool) {
+        self.operation_name = String::from(operation_name);
+        self.total_objects = 0;
+        self.received_objects = 0;
+        self.indexed_objects = 0;
+        self.received_bytes = 0;
+        self.is_push = is_push;
+        self.remote_msg = String::new();
+    }
+
+    pub fn update_transfer(&mut self, stats: &git2::Progress) {
+        self.total_objects = stats.total_objects();
+        self.received_objects = stats.received_objects();
+        self.indexed_objects = stats.indexed_objects();
+        self.received_bytes = stats.received_bytes();
+    }
+
+    pub fn update_push_transfer(&mut self, current_objects: usize, total_objects: usize, bytes: usize) {
+        self.total_objects = total_objects;
+        self.received_objects = current_objects;
+        self.indexed_objects = current_objects;
+        self.received_bytes = bytes;
+    }
+
+    pub fn set_remote_msg(&mut self, data: &[u8]) {
+        // Remotes redraw their progress lines with carriage returns, so only keep the latest one.
+        let text = String::from_utf8_lossy(data);
+        if let Some(line) = text.split(['\r', '\n']).map(str::trim).rfind(|line| !line.is_empty()) {
+            self.remote_msg = String::from(line);
+        }
+    }
+
+    // Receiving and indexing each count for half, since indexing only catches up at the end.
+    pub fn get_fraction_opt(&self) -> Option<f32> {
+        if self.total_objects == 0 {
+            return None;
+        }
+        Some((self.received_objects + self.indexed_objects) as f32 / (self.total_objects * 2) as f32)
+    }
+
+    pub fn get_text(&self) -> String {
+        let mut text = self.operation_name.clone();
+        if self.total_objects > 0 && self.is_push {
+            text += &format!(" - {}/{} objects sent, {:.1} MiB", self.received_objects, self.total_objects, self.received_bytes as f32 / (1024.0 * 1024.0));
+        } else if self.total_objects > 0 {
+            text += &format!(
+                " - {}/{} objects received, {}/{} indexed, {:.1} MiB",
+                self.received_objects,
+                self.total_objects,
+                self.indexed_objects,
+                self.total_objects,
+                self.received_bytes as f32 / (1024.0 * 1024.0)
+            );
+        }
+        if !self.remote_msg.is_empty() {
+            text += &format!(" (remote: {})", self.remote_msg);
+        }
+        text
+    }
+}
+
+// Every operation that's running has its own progress, so starting one can't reset another that's still going (e.g.
+// clearing the cancel flag it's about to check).
+#[derive(Clone, Default)]
+pub struct OperationProgressList {
+    progresses: Arc<Mutex<Vec<Arc<Mutex<OperationProgress>>>>>,
+}
+
+impl OperationProgressList {
+    pub fn start(&self) -> Arc<Mutex<OperationProgress>> {
+        let progress = Arc::new(Mutex::new(OperationProgress::default()));
+        self.progresses.lock().unwrap().push(progress.clone());
+        progress
+    }
+
+    pub fn finish(&self, progress: &Arc<Mutex<OperationProgress>>) {
+        self.progresses.lock().unwrap().retain(|p| !Arc::ptr_eq(p, progress));
+    }
+
+    pub fn is_loading(&self) -> bool {
+        !self.progresses.lock().unwrap().is_empty()
+    }
+
+    pub fn get_progresses(&self) -> Vec<Arc<Mutex<OperationProgress>>> {
+        self.progresses.lock().unwrap().clone()
+    }
+
+    // Prompts are shown one at a time, so the others keep waiting until this one is answered.
+    pub fn take_prompt(&self) -> Option<Prompt> {
+        self.progresses.lock().unwrap().iter().find_map(|progress| progress.lock().unwrap().take_prompt())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+    use super::{OperationProgress, OperationProgressList, PromptRequest};
+
+    #[test]
+    fn test_starting_another_operation_keeps_cancel_flag() {
+        let progress_list = OperationProgressList::default();
+        let progress = progress_list.start();
+        progress.lock().unwrap().cancel();
+
+        let other_progress = progress_list.start();
+        assert!(progress.lock().unwrap().is_cancelled());
+        assert!(!other_progress.lock().unwrap().is_cancelled());
+        assert_eq!(progress_list.get_progresses().len(), 2);
+
+        progress_list.finish(&progress);
+        assert!(progress_list.is_loading());
+        progress_list.finish(&other_progress);
+        assert!(!progress_list.is_loading());
+    }
+
+    #[test]
+    fn test_starting_another_operation_keeps_pending_prompt() {
+        let progress_list = OperationProgressList::default();
+        let progress = progress_list.start();
+        let progress_c = progress.clone();
+        let prompt_thread = thread::spawn(move || {
+            OperationProgress::prompt(&progress_c, PromptRequest::SshPassphrase(String::from("id_ed25519")))
+        });
+        while progress.lock().unwrap().prompt_opt.is_none() {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        progress_list.start();
+        let prompt = progress_list.take_prompt().unwrap();
+        assert!(matches!(&prompt.request, PromptRequest::SshPassphrase(key_path) if key_path == "id_ed25519"));
+        prompt.answer(None);
+        assert!(prompt_thread.join().unwrap().is_none());
+    }
+}
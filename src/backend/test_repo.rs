@@ -0,0 +1,61 @@
+use std::path::Path;
+use git2::{Oid, Repository, RepositoryInitOptions, Signature};
+use tempfile::TempDir;
+use crate::backend::git_utils;
+
+pub const TEST_NAME: &str = "Test";
+pub const TEST_EMAIL: &str = "test@example.com";
+
+// A repo in a temp dir for tests to build their fixtures in. It starts out on main with nothing committed.
+pub(crate) struct TestRepo {
+    // This has to be kept around since the repo is deleted when it's dropped.
+    _dir: TempDir,
+    pub repo: Repository,
+}
+
+impl TestRepo {
+    pub fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init_opts(dir.path(), RepositoryInitOptions::new().initial_head("main")).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", TEST_NAME).unwrap();
+        config.set_str("user.email", TEST_EMAIL).unwrap();
+        Self {
+            _dir: dir,
+            repo,
+        }
+    }
+
+    pub fn write_file(&self, path: &str, content: &str) {
+        std::fs::write(self.repo.workdir().unwrap().join(path), content).unwrap();
+    }
+
+    pub fn read_file(&self, path: &str) -> String {
+        std::fs::read_to_string(self.repo.workdir().unwrap().join(path)).unwrap()
+    }
+
+    pub fn read_head_file(&self, path: &str) -> String {
+        let tree = self.repo.head().unwrap().peel_to_tree().unwrap();
+        let blob = tree.get_path(Path::new(path)).unwrap().to_object(&self.repo).unwrap().peel_to_blob().unwrap();
+        String::from_utf8(blob.content().to_vec()).unwrap()
+    }
+
+    // Writes and commits the file on the current branch.
+    pub fn commit_file(&self, path: &str, content: &str, message: &str) -> Oid {
+        self.write_file(path, content);
+        let mut index = self.repo.index().unwrap();
+        index.add_path(Path::new(path)).unwrap();
+        index.write().unwrap();
+        let tree = self.repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now(TEST_NAME, TEST_EMAIL).unwrap();
+        let parent_opt = git_utils::get_head_commit(&self.repo).unwrap();
+        let parents: Vec<_> = parent_opt.iter().collect();
+        self.repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents).unwrap()
+    }
+
+    pub fn get_head_summaries(&self) -> Vec<String> {
+        let mut revwalk = self.repo.revwalk().unwrap();
+        revwalk.push_head().unwrap();
+        revwalk.map(|oid| String::from(self.repo.find_commit(oid.unwrap()).unwrap().summary().unwrap())).collect()
+    }
+}
@@ -1,7 +1,8 @@
 use std::sync::{Arc, Mutex};
 use eframe::Frame;
-use egui::{Button, Context, SelectableLabel, Ui, Vec2, ViewportCommand, Visuals};
-use crate::frontend::modals::{AddTabModal, ErrorModal, Modal};
+use egui::{Button, Context, ProgressBar, SelectableLabel, Ui, Vec2, ViewportCommand, Visuals};
+use crate::backend::progress::OperationProgressList;
+use crate::frontend::modals::{AddTabModal, CloneModal, ErrorModal, InitModal, Modal, PromptModal};
 use crate::frontend::tab::OG2Tab;
 use crate::frontend::utils;
 
@@ -9,11 +10,14 @@ const TAB_HEIGHT: f32 = 20.0;
 const TAB_ADD_BTN_WIDTH: f32 = 20.0;
 
 pub struct OG2App {
-    is_loading: Arc<Mutex<bool>>,
+    progress_list: OperationProgressList,
     tabs: Arc<Mutex<Vec<OG2Tab>>>,
     active_tab: Arc<Mutex<usize>>,
     error_modal: Arc<Mutex<ErrorModal>>,
     add_tab_modal: AddTabModal,
+    init_modal: Arc<Mutex<InitModal>>,
+    clone_modal: Arc<Mutex<CloneModal>>,
+    prompt_modal: PromptModal,
 }
 
 impl OG2App {
@@ -25,21 +29,37 @@ impl OG2App {
         cc.egui_ctx.send_viewport_cmd(ViewportCommand::Maximized(true));
         cc.egui_ctx.set_visuals(Visuals::dark());
         let error_modal = Arc::new(Mutex::new(ErrorModal::new()));
-        let is_loading = Arc::new(Mutex::new(false));
+        let progress_list = OperationProgressList::default();
+        let init_modal = Arc::new(Mutex::new(InitModal::new(error_modal.clone(), progress_list.clone())));
+        let clone_modal = Arc::new(Mutex::new(CloneModal::new(error_modal.clone(), progress_list.clone())));
         Self {
-            is_loading: is_loading.clone(),
+            progress_list: progress_list.clone(),
             tabs: Arc::new(Mutex::new(vec![])),
             active_tab: Arc::new(Mutex::new(0)),
             error_modal: error_modal.clone(),
-            add_tab_modal: AddTabModal::new(error_modal, is_loading),
+            add_tab_modal: AddTabModal::new(error_modal, init_modal.clone(), clone_modal.clone(), progress_list),
+            init_modal,
+            clone_modal,
+            prompt_modal: PromptModal::new(),
         }
     }
 
     fn show_modals(&mut self, ui: &mut Ui) {
         let add_tab_modal_res = self.add_tab_modal.show(ui, self.tabs.clone(), self.active_tab.clone());
+        let init_modal_res = self.init_modal.lock().unwrap().show(ui, self.tabs.clone(), self.active_tab.clone());
+        let clone_modal_res = self.clone_modal.lock().unwrap().show(ui, self.tabs.clone(), self.active_tab.clone());
         let mut error_modal = self.error_modal.lock().unwrap();
         error_modal.handle_error(add_tab_modal_res);
+        error_modal.handle_error(init_modal_res);
+        error_modal.handle_error(clone_modal_res);
         error_modal.show(ui);
+
+        if !self.prompt_modal.is_open() {
+            if let Some(prompt) = self.progress_list.take_prompt() {
+                self.prompt_modal.open_with(prompt);
+            }
+        }
+        self.prompt_modal.show(ui);
     }
 
     fn show_app_btns(&self, ui: &mut Ui) {
@@ -48,15 +68,39 @@ impl OG2App {
             if ui.button("BLURG").clicked() {
                 println!("BLURG!");
             }
-            if *self.is_loading.lock().unwrap() {
+            if self.progress_list.is_loading() {
                 ui.add_space(ui.available_width() - mid_width);
                 ui.spinner();
-                // TODO: Insert progress bar here!
+                ui.vertical(|ui| {
+                    for progress in self.progress_list.get_progresses() {
+                        let mut progress = progress.lock().unwrap();
+                        // Only transfers with a remote can be cancelled.
+                        if progress.is_transferring() {
+                            ui.horizontal(|ui| {
+                                let mut cancel_btn = ui.add_enabled(progress.can_cancel() && !progress.is_cancelled(), Button::new("Cancel"));
+                                if !progress.can_cancel() {
+                                    cancel_btn = cancel_btn.on_disabled_hover_text("Pushes can't be cancelled.");
+                                }
+                                if cancel_btn.clicked() {
+                                    progress.cancel();
+                                }
+                                match progress.get_fraction_opt() {
+                                    Some(fraction) => {
+                                        ui.add(ProgressBar::new(fraction).text(progress.get_text()));
+                                    },
+                                    None => {
+                                        ui.label(progress.get_text());
+                                    },
+                                };
+                            });
+                        }
+                    }
+                });
             }
         });
     }
 
-    fn show_tab_btns(&mut self, ui: &mut Ui, tabs: &Vec<OG2Tab>) {
+    fn show_tab_btns(&mut self, ui: &mut Ui, tabs: &[OG2Tab]) {
         ui.horizontal(|ui| {
             let active_tab = *self.active_tab.lock().unwrap();
             let tab_width = ui.available_width() / tabs.len() as f32 - TAB_ADD_BTN_WIDTH;
@@ -75,14 +119,14 @@ impl OG2App {
     fn show_welcome_btns(&mut self, ui: &mut Ui) {
         ui.horizontal(|ui| {
             if ui.button("Init").clicked() {
-                // TODO: Implement Init
+                self.init_modal.lock().unwrap().open();
             }
             if ui.button("Open").clicked() {
-                let res = utils::open_repo_as_tab(self.tabs.clone(), self.active_tab.clone(), self.error_modal.clone(), self.is_loading.clone(), ui.ctx().clone());
+                let res = utils::open_repo_as_tab(self.tabs.clone(), self.active_tab.clone(), self.error_modal.clone(), self.progress_list.clone(), ui.ctx().clone());
                 self.error_modal.lock().unwrap().handle_error(res);
             }
             if ui.button("Clone").clicked() {
-                // TODO: Implement Clone
+                self.clone_modal.lock().unwrap().open();
             }
         });
     }
@@ -97,7 +141,7 @@ impl eframe::App for OG2App {
             // This is done so 'self' doesn't get borrowed twice.
             let tabs_c = self.tabs.clone();
             let mut tabs = tabs_c.lock().unwrap();
-            if tabs.len() > 0 {
+            if !tabs.is_empty() {
                 self.show_tab_btns(ui, &tabs);
                 tabs[*self.active_tab.lock().unwrap()].show(ui);
             } else {
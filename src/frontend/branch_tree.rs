@@ -1,19 +1,20 @@
 use std::path::Path;
 use anyhow::{Error, Result};
 use egui::{Context, Image, Label, Sense, TextureHandle, TextureOptions, Ui};
-use git2::{BranchType, Reference, Repository};
+use git2::{BranchType, Oid, Reference, Repository};
 use crate::backend::git_utils;
 use crate::frontend::utils::load_image_from_path;
 
 const TAB_SIZE: f32 = 20.0;
 
-pub fn get_branch_trees(repo: &Repository, ctx: &Context) -> Result<[BranchTreeNode; 3]> {
+pub fn get_branch_trees(repo: &Repository, ctx: &Context) -> Result<[BranchTreeNode; 4]> {
     let ref_shorthand_types = git_utils::get_all_refs(repo)?;
 
     let mut branch_trees = [
         BranchTreeNode::new(String::from("Local"), false, true),
         BranchTreeNode::new(String::from("Remote"), false, true),
-        BranchTreeNode::new(String::from("Tags"), false, true)
+        BranchTreeNode::new(String::from("Tags"), false, true),
+        BranchTreeNode::new(String::from("Stashes"), false, true),
     ];
 
     // Load the arrow images here so they can be cheaply cloned.
@@ -27,11 +28,59 @@ pub fn get_branch_trees(repo: &Repository, ctx: &Context) -> Result<[BranchTreeN
             branch_trees[i].insert_shorthand(repo, reference, &right_arrow_texture, &down_arrow_texture)?;
         }
     }
+    let remote_names = git_utils::get_remote_names(repo)?;
+    for remote_node in &mut branch_trees[1].children {
+        if remote_names.contains(&remote_node.text) {
+            remote_node.remote_name_opt = Some(remote_node.text.clone());
+        }
+    }
+    for stash in git_utils::get_stashes(repo)? {
+        let mut stash_node = BranchTreeNode::new(format!("{}: {}", stash.get_name(), stash.message), false, false);
+        stash_node.stash_opt = Some((stash.index, stash.oid));
+        branch_trees[3].children.push(stash_node);
+        branch_trees[3].set_arrow_images(&right_arrow_texture, &down_arrow_texture);
+    }
     Ok(branch_trees)
 }
 
+pub fn refresh_branch_trees(branch_trees: &mut [BranchTreeNode; 4], repo: &Repository, ctx: &Context) -> Result<()> {
+    let mut new_branch_trees = get_branch_trees(repo, ctx)?;
+    // Keep whatever the user had expanded or collapsed.
+    for (new_branch_tree, old_branch_tree) in new_branch_trees.iter_mut().zip(branch_trees.iter()) {
+        new_branch_tree.copy_expanded_state(old_branch_tree);
+    }
+    *branch_trees = new_branch_trees;
+    Ok(())
+}
+
+pub enum BranchTreeAction {
+    Checkout(String),
+    CreateBranch(String),
+    Rename(String),
+    Delete(String, bool),
+    SetUpstream(String),
+    UnsetUpstream(String),
+    CheckoutRemote(String),
+    DeleteRemote(String),
+    FetchRemote(String),
+    PruneRemote(String),
+    Merge(String),
+    DeleteTag(String),
+    DeleteRemoteTag(String),
+    // Pushes every tag if there's no ref name.
+    PushTags(Option<String>),
+    SelectStash(Oid),
+    ApplyStash(usize),
+    PopStash(usize),
+    DropStash(usize),
+}
+
 pub struct BranchTreeNode {
     text: String,
+    ref_name: Option<String>,
+    stash_opt: Option<(usize, Oid)>,
+    // Set on the top-level nodes under "Remote".
+    remote_name_opt: Option<String>,
     is_head: bool,
     is_expanded: bool,
     children: Vec<BranchTreeNode>,
@@ -43,6 +92,9 @@ impl BranchTreeNode {
     pub fn new(text: String, is_head: bool, is_expanded: bool) -> Self {
         Self {
             text,
+            ref_name: None,
+            stash_opt: None,
+            remote_name_opt: None,
             is_head,
             is_expanded,
             children: vec![],
@@ -52,19 +104,29 @@ impl BranchTreeNode {
     }
 
     fn set_arrow_images(&mut self, right_arrow_texture: &TextureHandle, down_arrow_texture: &TextureHandle) {
-        if self.right_arrow_texture == None {
+        if self.right_arrow_texture.is_none() {
             self.right_arrow_texture = Some(right_arrow_texture.clone());
         }
-        if self.down_arrow_texture == None {
+        if self.down_arrow_texture.is_none() {
             self.down_arrow_texture = Some(down_arrow_texture.clone());
         }
     }
 
+    pub fn copy_expanded_state(&mut self, old_node: &BranchTreeNode) {
+        self.is_expanded = old_node.is_expanded;
+        for child in &mut self.children {
+            if let Some(old_child) = old_node.children.iter().find(|old_child| old_child.text == child.text) {
+                child.copy_expanded_state(old_child);
+            }
+        }
+    }
+
     pub fn insert_shorthand(&mut self, repo: &Repository, reference: &Reference, right_arrow_texture: &TextureHandle, down_arrow_texture: &TextureHandle) -> Result<()> {
         // NOTE: This function should only be called on a root node!
         let mut current_tree_node = self;
 
         let shorthand = reference.shorthand().ok_or(Error::msg("Branch Shorthand has invalid UTF-8!"))?;
+        let ref_name = reference.name().ok_or(Error::msg("Branch Name has invalid UTF-8!"))?;
         let mut is_head = false;
         if reference.is_branch() && repo.find_branch(shorthand, BranchType::Local)?.is_head() {
             is_head = true;
@@ -80,12 +142,18 @@ impl BranchTreeNode {
             match child_index {
                 Some(j) => {
                     current_tree_node = &mut current_tree_node.children[j];
+                    if i == split_shorthand.len() - 1 {
+                        current_tree_node.ref_name = Some(String::from(ref_name));
+                        current_tree_node.is_head = is_head;
+                    }
                 },
                 None => {
                     if i == split_shorthand.len() - 1 {
                         // If this is a leaf...
                         // This is where branch information can be passed!
-                        current_tree_node.children.push(BranchTreeNode::new(String::from(shorthand_piece), is_head, false));
+                        let mut leaf_node = BranchTreeNode::new(String::from(shorthand_piece), is_head, false);
+                        leaf_node.ref_name = Some(String::from(ref_name));
+                        current_tree_node.children.push(leaf_node);
                         current_tree_node.set_arrow_images(right_arrow_texture, down_arrow_texture);
                     } else {
                         // Otherwise, if this node has children...
@@ -100,7 +168,89 @@ impl BranchTreeNode {
         Ok(())
     }
 
-    pub fn show(&mut self, ui: &mut Ui, rec_depth: f32) {
+    fn show_context_menu(&self, ui: &mut Ui, ref_name: &str, action_opt: &mut Option<BranchTreeAction>) {
+        let mut set_action = |ui: &mut Ui, action: BranchTreeAction| {
+            *action_opt = Some(action);
+            ui.close_menu();
+        };
+        if ref_name.starts_with("refs/heads/") {
+            if ui.button("Checkout").clicked() {
+                set_action(ui, BranchTreeAction::Checkout(String::from(ref_name)));
+            }
+            if ui.button("Create Branch From Here").clicked() {
+                set_action(ui, BranchTreeAction::CreateBranch(String::from(ref_name)));
+            }
+            if ui.button("Rename").clicked() {
+                set_action(ui, BranchTreeAction::Rename(String::from(ref_name)));
+            }
+            if ui.button("Delete").clicked() {
+                set_action(ui, BranchTreeAction::Delete(String::from(ref_name), false));
+            }
+            if ui.button("Force Delete").clicked() {
+                set_action(ui, BranchTreeAction::Delete(String::from(ref_name), true));
+            }
+            ui.separator();
+            if ui.button("Merge Into Current Branch").clicked() {
+                set_action(ui, BranchTreeAction::Merge(String::from(ref_name)));
+            }
+            if ui.button("Set Upstream").clicked() {
+                set_action(ui, BranchTreeAction::SetUpstream(String::from(ref_name)));
+            }
+            if ui.button("Unset Upstream").clicked() {
+                set_action(ui, BranchTreeAction::UnsetUpstream(String::from(ref_name)));
+            }
+        } else if ref_name.starts_with("refs/remotes/") {
+            if ui.button("Checkout As New Tracking Branch").clicked() {
+                set_action(ui, BranchTreeAction::CheckoutRemote(String::from(ref_name)));
+            }
+            if ui.button("Create Branch From Here").clicked() {
+                set_action(ui, BranchTreeAction::CreateBranch(String::from(ref_name)));
+            }
+            if ui.button("Delete On Remote").clicked() {
+                set_action(ui, BranchTreeAction::DeleteRemote(String::from(ref_name)));
+            }
+            ui.separator();
+            if ui.button("Merge Into Current Branch").clicked() {
+                set_action(ui, BranchTreeAction::Merge(String::from(ref_name)));
+            }
+        } else if ref_name.starts_with("refs/tags/") {
+            if ui.button("Create Branch From Here").clicked() {
+                set_action(ui, BranchTreeAction::CreateBranch(String::from(ref_name)));
+            }
+            if ui.button("Delete").clicked() {
+                set_action(ui, BranchTreeAction::DeleteTag(String::from(ref_name)));
+            }
+            if ui.button("Delete On Remote...").clicked() {
+                set_action(ui, BranchTreeAction::DeleteRemoteTag(String::from(ref_name)));
+            }
+            ui.separator();
+            if ui.button("Push...").clicked() {
+                set_action(ui, BranchTreeAction::PushTags(Some(String::from(ref_name))));
+            }
+            if ui.button("Push All Tags...").clicked() {
+                set_action(ui, BranchTreeAction::PushTags(None));
+            }
+        }
+    }
+
+    fn show_stash_context_menu(ui: &mut Ui, stash_index: usize, action_opt: &mut Option<BranchTreeAction>) {
+        let mut set_action = |ui: &mut Ui, action: BranchTreeAction| {
+            *action_opt = Some(action);
+            ui.close_menu();
+        };
+        if ui.button("Apply").clicked() {
+            set_action(ui, BranchTreeAction::ApplyStash(stash_index));
+        }
+        if ui.button("Pop").clicked() {
+            set_action(ui, BranchTreeAction::PopStash(stash_index));
+        }
+        if ui.button("Drop").clicked() {
+            set_action(ui, BranchTreeAction::DropStash(stash_index));
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, rec_depth: f32) -> Option<BranchTreeAction> {
+        let mut action_opt = None;
         ui.horizontal(|ui| {
             ui.add_space(rec_depth * TAB_SIZE);
             let mut row_was_clicked = false;
@@ -121,23 +271,52 @@ impl BranchTreeNode {
             }
 
             // Add text.
-            let text;
-            if self.is_head {
-                text = format!("* {}", self.text);
+            let text = if self.is_head {
+                format!("* {}", self.text)
             } else {
-                text = self.text.clone();
-            }
-            if ui.add(Label::new(text).wrap(false)).interact(Sense::click()).clicked() {
+                self.text.clone()
+            };
+            let label_resp = ui.add(Label::new(text).wrap(false)).interact(Sense::click());
+            if label_resp.clicked() {
                 row_was_clicked = true;
             }
+            if let Some(ref_name) = &self.ref_name {
+                if label_resp.double_clicked() && ref_name.starts_with("refs/heads/") {
+                    action_opt = Some(BranchTreeAction::Checkout(ref_name.clone()));
+                }
+                label_resp.context_menu(|ui| {
+                    self.show_context_menu(ui, ref_name, &mut action_opt);
+                });
+            } else if let Some((stash_index, stash_oid)) = self.stash_opt {
+                if label_resp.clicked() {
+                    action_opt = Some(BranchTreeAction::SelectStash(stash_oid));
+                }
+                label_resp.context_menu(|ui| {
+                    BranchTreeNode::show_stash_context_menu(ui, stash_index, &mut action_opt);
+                });
+            } else if let Some(remote_name) = &self.remote_name_opt {
+                label_resp.context_menu(|ui| {
+                    if ui.button("Fetch This Remote Only").clicked() {
+                        action_opt = Some(BranchTreeAction::FetchRemote(remote_name.clone()));
+                        ui.close_menu();
+                    }
+                    if ui.button("Prune").clicked() {
+                        action_opt = Some(BranchTreeAction::PruneRemote(remote_name.clone()));
+                        ui.close_menu();
+                    }
+                });
+            }
             if row_was_clicked {
                 self.is_expanded = !self.is_expanded;
             }
         });
         if self.is_expanded {
             for child in &mut self.children {
-                child.show(ui, rec_depth + 1.0);
+                if let Some(action) = child.show(ui, rec_depth + 1.0) {
+                    action_opt = Some(action);
+                }
             }
         }
+        action_opt
     }
-}
\ No newline at end of file
+}
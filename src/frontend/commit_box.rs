@@ -0,0 +1,122 @@
+use anyhow::{bail, Result};
+use egui::{Button, Checkbox, CollapsingHeader, Grid, TextEdit, Ui};
+use git2::Repository;
+use crate::backend::git_functions::git_commit;
+use crate::backend::git_utils;
+
+pub struct CommitBox {
+    summary: String,
+    body: String,
+    is_amend: bool,
+    author_name: String,
+    author_email: String,
+    committer_name: String,
+    committer_email: String,
+    default_name: String,
+    default_email: String,
+}
+
+impl CommitBox {
+    pub fn new(repo: &Repository) -> Self {
+        let mut commit_box = Self {
+            summary: String::new(),
+            body: String::new(),
+            is_amend: false,
+            author_name: String::new(),
+            author_email: String::new(),
+            committer_name: String::new(),
+            committer_email: String::new(),
+            default_name: String::new(),
+            default_email: String::new(),
+        };
+        // These are only used as hints, so it's fine if the config doesn't have them.
+        if let Ok(signature) = repo.signature() {
+            commit_box.default_name = String::from(signature.name().unwrap_or_default());
+            commit_box.default_email = String::from(signature.email().unwrap_or_default());
+        }
+        commit_box
+    }
+
+    fn get_override<'a>(name: &'a str, email: &'a str, field: &str) -> Result<Option<(&'a str, &'a str)>> {
+        let name = name.trim();
+        let email = email.trim();
+        if name.is_empty() && email.is_empty() {
+            return Ok(None);
+        }
+        if name.is_empty() || email.is_empty() {
+            bail!("Please fill in both the {} name and email, or leave both empty to use your git config.", field);
+        }
+        Ok(Some((name, email)))
+    }
+
+    fn get_message(&self) -> String {
+        let summary = self.summary.trim();
+        let body = self.body.trim();
+        if body.is_empty() {
+            String::from(summary)
+        } else {
+            format!("{}\n\n{}", summary, body)
+        }
+    }
+
+    fn set_message_from_head(&mut self, repo: &Repository) -> Result<()> {
+        if let Some(head_commit) = git_utils::get_head_commit(repo)? {
+            let message = String::from_utf8_lossy(head_commit.message_bytes()).into_owned();
+            let (summary, body) = message.split_once('\n').unwrap_or((&message, ""));
+            self.summary = String::from(summary.trim());
+            self.body = String::from(body.trim());
+        }
+        Ok(())
+    }
+
+    fn commit(&mut self, repo: &Repository) -> Result<()> {
+        let author_opt = CommitBox::get_override(&self.author_name, &self.author_email, "author")?;
+        let committer_opt = CommitBox::get_override(&self.committer_name, &self.committer_email, "committer")?;
+        git_commit(repo, &self.get_message(), self.is_amend, author_opt, committer_opt)?;
+        self.summary.clear();
+        self.body.clear();
+        self.is_amend = false;
+        Ok(())
+    }
+
+    fn show_signature_fields(ui: &mut Ui, id: &str, name: &mut String, email: &mut String, default_name: &str, default_email: &str) {
+        Grid::new(id).num_columns(2).show(ui, |ui| {
+            ui.label("Name:");
+            ui.add(TextEdit::singleline(name).hint_text(default_name));
+            ui.end_row();
+            ui.label("Email:");
+            ui.add(TextEdit::singleline(email).hint_text(default_email));
+            ui.end_row();
+        });
+    }
+
+    // Returns true if a commit was made.
+    pub fn show(&mut self, ui: &mut Ui, repo: &Repository) -> Result<bool> {
+        ui.add(TextEdit::singleline(&mut self.summary).hint_text("Summary").desired_width(f32::INFINITY));
+        ui.add(TextEdit::multiline(&mut self.body).hint_text("Description").desired_rows(4).desired_width(f32::INFINITY));
+        // During a rebase, the only commit that can be made is amending the one it stopped to edit.
+        let is_rebasing = git_utils::is_rebasing(repo);
+        let can_commit = !is_rebasing || git_utils::is_rebase_edit_stop(repo)?;
+        if is_rebasing && can_commit && !self.is_amend {
+            self.is_amend = true;
+            if self.summary.trim().is_empty() {
+                self.set_message_from_head(repo)?;
+            }
+        }
+        if ui.add_enabled(!is_rebasing, Checkbox::new(&mut self.is_amend, "Amend Last Commit")).changed() && self.is_amend && self.summary.trim().is_empty() {
+            self.set_message_from_head(repo)?;
+        }
+        CollapsingHeader::new("Author/Committer Override").id_source("commit-box-override").show(ui, |ui| {
+            ui.label("Author");
+            CommitBox::show_signature_fields(ui, "commit-box-author-grid", &mut self.author_name, &mut self.author_email, &self.default_name, &self.default_email);
+            ui.label("Committer");
+            CommitBox::show_signature_fields(ui, "commit-box-committer-grid", &mut self.committer_name, &mut self.committer_email, &self.default_name, &self.default_email);
+        });
+        let btn_text = if self.is_amend { "Amend" } else { "Commit" };
+        if ui.add_enabled(can_commit, Button::new(btn_text)).clicked() {
+            self.commit(repo)?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
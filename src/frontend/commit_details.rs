@@ -0,0 +1,130 @@
+use anyhow::Result;
+use egui::{Grid, Label, RichText, ScrollArea, Sense, SidePanel, Ui};
+use git2::{Oid, Repository};
+use crate::backend::git_utils::{self, FileChange};
+use crate::frontend::diff_view::{DiffView, DiffViewKind};
+
+const FILE_LIST_DEFAULT_WIDTH: f32 = 400.0;
+
+pub enum CommitDetailsAction {
+    SelectCommit(Oid),
+    Close,
+}
+
+pub struct CommitDetails {
+    oid: Oid,
+    parent_oids: Vec<Oid>,
+    author: String,
+    author_time: String,
+    committer: String,
+    committer_time: String,
+    message: String,
+    file_changes: Vec<FileChange>,
+    diff_view_opt: Option<DiffView>,
+}
+
+impl CommitDetails {
+    pub fn new(repo: &Repository, oid: Oid) -> Result<Self> {
+        let commit = repo.find_commit(oid)?;
+        let diff = git_utils::get_commit_diff(repo, &commit)?;
+        let author = commit.author();
+        let committer = commit.committer();
+        Ok(Self {
+            oid,
+            parent_oids: commit.parent_ids().collect(),
+            author: git_utils::format_signature(&author),
+            author_time: git_utils::format_time(&author.when()),
+            committer: git_utils::format_signature(&committer),
+            committer_time: git_utils::format_time(&committer.when()),
+            message: String::from_utf8_lossy(commit.message_bytes()).into_owned(),
+            file_changes: git_utils::get_file_changes(&diff)?,
+            diff_view_opt: None,
+        })
+    }
+
+    pub fn get_oid(&self) -> Oid {
+        self.oid
+    }
+
+    fn show_file_diff(&mut self, repo: &Repository, path: &str) -> Result<()> {
+        let commit = repo.find_commit(self.oid)?;
+        let diff = git_utils::get_commit_diff(repo, &commit)?;
+        self.diff_view_opt = git_utils::get_file_diff(&diff, path)?.map(|file_diff| DiffView::new(DiffViewKind::ReadOnly, file_diff));
+        Ok(())
+    }
+
+    fn show_info(&mut self, ui: &mut Ui, action_opt: &mut Option<CommitDetailsAction>) -> Option<String> {
+        let mut clicked_path_opt = None;
+        ScrollArea::both().id_source("commit-details-info-scroll-area").auto_shrink([false, false]).show(ui, |ui| {
+            Grid::new("commit-details-grid").num_columns(2).show(ui, |ui| {
+                ui.label("Commit:");
+                ui.add(Label::new(RichText::new(self.oid.to_string()).monospace()).wrap(false));
+                ui.end_row();
+
+                ui.label("Parents:");
+                ui.horizontal(|ui| {
+                    for parent_oid in &self.parent_oids {
+                        let short_oid: String = parent_oid.to_string().chars().take(7).collect();
+                        if ui.link(RichText::new(short_oid).monospace()).on_hover_text(parent_oid.to_string()).clicked() {
+                            *action_opt = Some(CommitDetailsAction::SelectCommit(*parent_oid));
+                        }
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Author:");
+                ui.label(format!("{}  {}", self.author, self.author_time));
+                ui.end_row();
+
+                ui.label("Committer:");
+                ui.label(format!("{}  {}", self.committer, self.committer_time));
+                ui.end_row();
+            });
+            ui.separator();
+            ui.label(self.message.trim_end());
+            ui.separator();
+
+            ui.heading(format!("Changed Files ({})", self.file_changes.len()));
+            for file_change in &self.file_changes {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new(git_utils::get_delta_label(file_change.status)).strong());
+                    let is_selected = self.diff_view_opt.as_ref().is_some_and(|diff_view| diff_view.get_path() == file_change.path);
+                    let mut path_text = RichText::new(file_change.get_display_path());
+                    if is_selected {
+                        path_text = path_text.underline();
+                    }
+                    if ui.add(Label::new(path_text).wrap(false).sense(Sense::click())).clicked() {
+                        clicked_path_opt = Some(file_change.path.clone());
+                    }
+                });
+            }
+        });
+        clicked_path_opt
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, repo: &Repository) -> Result<Option<CommitDetailsAction>> {
+        let mut action_opt = None;
+        ui.horizontal(|ui| {
+            ui.heading("Commit Details");
+            if ui.button("Close").clicked() {
+                action_opt = Some(CommitDetailsAction::Close);
+            }
+        });
+
+        let mut clicked_path_opt = None;
+        SidePanel::left("commit-details-info-panel").resizable(true).default_width(FILE_LIST_DEFAULT_WIDTH).show_inside(ui, |ui| {
+            clicked_path_opt = self.show_info(ui, &mut action_opt);
+        });
+        if let Some(diff_view) = &mut self.diff_view_opt {
+            // Read only diffs never change anything.
+            diff_view.show(ui, repo)?;
+        } else {
+            ui.label("Select a file to see its changes.");
+        }
+
+        if let Some(path) = clicked_path_opt {
+            self.show_file_diff(repo, &path)?;
+        }
+        Ok(action_opt)
+    }
+}
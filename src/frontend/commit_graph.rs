@@ -1,9 +1,8 @@
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use anyhow::{Error, Result};
-use egui::{Align2, Color32, FontId, Painter, Pos2, Rounding, ScrollArea, Sense, Stroke, Ui, Vec2};
-use git2::{BranchType, Oid, Repository};
-use crate::backend::git_functions::git_revwalk;
+use anyhow::Result;
+use egui::{show_tooltip_at_pointer, Align2, Button, Color32, FontId, Id, Painter, Pos2, Rect, Rounding, ScrollArea, Sense, Stroke, Ui, Vec2};
+use git2::{Oid, Repository};
+use crate::backend::git_functions::RevwalkRoots;
+use crate::backend::graph_layout::{GraphEdge, GraphLayout, GraphLayoutRow, GraphRef, GraphRefKind, GraphRowLoader, LoadedCommit};
 
 const X_OFFSET: f32 = 10.0;
 const X_SPACING: f32 = 15.0;
@@ -18,8 +17,13 @@ const GRAPH_COLORS: [Color32; 4] = [Color32::BLUE, Color32::GREEN, Color32::YELL
 const LOCAL_BRANCH_COLOR: Color32 = Color32::from_rgb(200, 0, 0);
 const REMOTE_BRANCH_COLOR: Color32 = Color32::from_rgb(0, 139, 0);
 const TAG_COLOR: Color32 = Color32::from_rgb(160, 160, 160);
+const STASH_COLOR: Color32 = Color32::from_rgb(180, 100, 0);
 const REF_GAMMA_MULTIPLIER: f32 = 0.3;  // Set higher to make more opaque.
 const VISIBLE_SCROLL_AREA_PADDING: usize = 10;
+const GRAPH_PAGE_SIZE: usize = 2000;
+// More rows get loaded once the user scrolls this close to the last loaded row.
+const LOAD_MORE_ROWS_MARGIN: usize = 500;
+const SELECTED_ROW_COLOR: Color32 = Color32::from_rgb(50, 50, 80);
 
 struct LocationIndex {
     x: usize,
@@ -51,321 +55,309 @@ impl LocationIndex {
     }
 }
 
-#[derive(Clone)]
-struct GraphRowRef {
-    color: Color32,
-    shorthand: String,
-    is_head: bool,
+// Returns the rect the ref was drawn in.
+fn show_ref(graph_ref: &GraphRef, painter: &Painter, next_text_position: Pos2) -> Rect {
+    let text = if graph_ref.is_head {
+        format!("* {}", graph_ref.shorthand)
+    } else {
+        graph_ref.shorthand.clone()
+    };
+    let color = match graph_ref.kind {
+        GraphRefKind::LocalBranch => LOCAL_BRANCH_COLOR,
+        GraphRefKind::RemoteBranch => REMOTE_BRANCH_COLOR,
+        GraphRefKind::Tag => TAG_COLOR,
+        GraphRefKind::Stash => STASH_COLOR,
+    };
+    let ref_rect = painter.text(
+        next_text_position,
+        Align2::LEFT_CENTER,
+        text,
+        FontId::default(),
+        Color32::WHITE
+    ).expand2(REF_RECT_MARGIN);
+    painter.rect_filled(ref_rect, Rounding::same(REF_RECT_ROUNDING), color.gamma_multiply(REF_GAMMA_MULTIPLIER));
+    ref_rect
 }
 
-impl GraphRowRef {
-    fn new(color: Color32, shorthand: String, is_head: bool) -> Self {
-        Self {
-            color,
-            shorthand,
-            is_head,
-        }
-    }
-
-    pub fn get_commit_branch_map(repo: &Repository) -> Result<HashMap<Oid, Vec<GraphRowRef>>> {
-        let mut commit_branch_map: HashMap<Oid, Vec<GraphRowRef>> = HashMap::new();
-        for ref_result in repo.references()? {
-            let reference = ref_result?;
-            let branch_shorthand = reference.shorthand().ok_or(Error::msg("Branch Shorthand has invalid UTF-8!"))?;
-
-            let mut is_head = false;
-            let color;
-            if reference.is_branch() {
-                color = LOCAL_BRANCH_COLOR.gamma_multiply(REF_GAMMA_MULTIPLIER);
-                if repo.find_branch(branch_shorthand, BranchType::Local)?.is_head() {
-                    is_head = true;
-                }
-            } else if reference.is_remote() && !branch_shorthand.ends_with("/HEAD") {
-                color = REMOTE_BRANCH_COLOR.gamma_multiply(REF_GAMMA_MULTIPLIER);
-            } else if reference.is_tag() {
-                color = TAG_COLOR.gamma_multiply(REF_GAMMA_MULTIPLIER);
-            } else {
-                continue;
-            }
-
-            let target_oid = reference.peel_to_commit()?.id();
-            let graph_row_ref = GraphRowRef::new(color, String::from(branch_shorthand), is_head);
-            match commit_branch_map.get_mut(&target_oid) {
-                Some(v) => v.push(graph_row_ref),
-                None => {
-                    commit_branch_map.insert(target_oid, vec![graph_row_ref]);
-                },
-            };
-        }
-        Ok(commit_branch_map)
-    }
-
-    pub fn show(&self, painter: &Painter, next_text_position: Pos2) -> Pos2 {
-        let text;
-        if self.is_head {
-            text = format!("* {}", self.shorthand);
-        } else {
-            text = self.shorthand.clone();
-        }
-        let ref_rect = painter.text(
-            next_text_position,
-            Align2::LEFT_CENTER,
-            text,
-            FontId::default(),
-            Color32::WHITE
-        ).expand2(REF_RECT_MARGIN);
-        painter.rect_filled(ref_rect, Rounding::same(REF_RECT_ROUNDING), self.color);
-        // Return the next text position.
-        ref_rect.right_center() + Vec2::new(REF_X_SPACING, 0.0)
-    }
-}
-
-struct GraphRow {
-    oid: Oid,
-    circle_location: LocationIndex,
-    summary_location: LocationIndex,
-    refs: Vec<GraphRowRef>,
-    summary: String,
-    // These are lines that start in this row.
-    lines: Vec<Line>,
+fn show_edge(edge: &GraphEdge, y: usize, painter: &Painter, scroll_area_top_left: Pos2) {
+    let start = LocationIndex::new(edge.from_lane, y);
+    let end = LocationIndex::new(edge.to_lane, y + 1);
+    let color = if edge.from_lane < edge.to_lane {
+        end.get_color()
+    } else {
+        start.get_color()
+    };
+    painter.line_segment([start.get_relative_pos2(scroll_area_top_left), end.get_relative_pos2(scroll_area_top_left)], Stroke::new(LINE_STROKE_WIDTH, color));
 }
 
-impl GraphRow {
-    pub fn new(commit: git2::Commit, refs: Vec<GraphRowRef>, i: usize) -> Result<Self> {
-        Ok(Self {
-            oid: commit.id(),
-            circle_location: LocationIndex::new(0, i),
-            summary_location: LocationIndex::new(1, i),
-            refs,
-            summary: String::from(commit.summary().ok_or(Error::msg("Commit summary has invalid UTF-8!"))?),
-            lines: vec![],
-        })
+// Returns the tooltip of whichever ref is under hover_pos_opt, if any.
+fn show_row(layout_row: &GraphLayoutRow, y: usize, painter: &Painter, scroll_area_top_left: Pos2, hover_pos_opt: Option<Pos2>) -> Option<String> {
+    for edge in &layout_row.edges {
+        show_edge(edge, y, painter, scroll_area_top_left);
     }
-
-    pub fn show(&self, painter: &Painter, scroll_area_top_left: Pos2) {
-        for line in &self.lines {
-            line.show(painter, scroll_area_top_left);
-        }
-        painter.circle_filled(
-            self.circle_location.get_relative_pos2(scroll_area_top_left),
-            CIRCLE_RADIUS,
-            self.circle_location.get_color()
-        );
-        let mut next_text_position = self.summary_location.get_relative_pos2(scroll_area_top_left);
-        for commit_ref in &self.refs {
-            next_text_position = commit_ref.show(painter, next_text_position);
+    let circle_location = LocationIndex::new(layout_row.lane, y);
+    painter.circle_filled(circle_location.get_relative_pos2(scroll_area_top_left), CIRCLE_RADIUS, circle_location.get_color());
+    let mut next_text_position = LocationIndex::new(layout_row.summary_lane, y).get_relative_pos2(scroll_area_top_left);
+    let mut tooltip_opt = None;
+    for graph_ref in &layout_row.refs {
+        let ref_rect = show_ref(graph_ref, painter, next_text_position);
+        if hover_pos_opt.is_some_and(|hover_pos| ref_rect.contains(hover_pos)) {
+            tooltip_opt = graph_ref.tooltip_opt.clone();
         }
-        painter.text(
-            next_text_position,
-            Align2::LEFT_CENTER,
-            self.summary.clone(),
-            FontId::default(),
-            Color32::WHITE
-        );
+        next_text_position = ref_rect.right_center() + Vec2::new(REF_X_SPACING, 0.0);
     }
+    painter.text(
+        next_text_position,
+        Align2::LEFT_CENTER,
+        layout_row.summary.clone(),
+        FontId::default(),
+        Color32::WHITE
+    );
+    tooltip_opt
 }
 
-struct Line {
-    start: LocationIndex,
-    end: LocationIndex,
-    color: Color32,
+pub enum CommitGraphAction {
+    SelectCommit(Oid),
+    // This has the oldest commit to include in the rebase.
+    InteractiveRebase(Oid),
+    // These have the commits on the selected line of history in graph order, i.e. newest first.
+    CherryPick(Vec<Oid>),
+    Revert(Vec<Oid>),
+    Reset(Oid),
+    CreateTag(Oid),
+    LoadMoreRows,
 }
 
-impl Line {
-    pub fn new(start_x: usize, start_y: usize, end_x: usize, end_y: usize) -> Self {
-        let start = LocationIndex::new(start_x, start_y);
-        let end = LocationIndex::new(end_x, end_y);
-        let color;
-        if start_x < end_x {
-            color = end.get_color();
-        } else {
-            color = start.get_color();
-        }
-        Self {
-            start,
-            end,
-            color,
-        }
-    }
+// Loading more rows is split up so the slow part, looking up the commits, can be done without locking the graph.
+pub struct RowLoad {
+    row_loader: GraphRowLoader,
+    scroll_to_oid_opt: Option<Oid>,
+    loaded_commits: Vec<LoadedCommit>,
+}
 
-    pub fn show(&self, painter: &Painter, scroll_area_top_left: Pos2) {
-        painter.line_segment([self.start.get_relative_pos2(scroll_area_top_left), self.end.get_relative_pos2(scroll_area_top_left)], Stroke::new(LINE_STROKE_WIDTH, self.color));
+impl RowLoad {
+    pub fn load(&mut self, repo: &Repository) -> Result<()> {
+        self.loaded_commits = self.row_loader.load_commits(repo, GRAPH_PAGE_SIZE, self.scroll_to_oid_opt)?;
+        Ok(())
     }
 }
 
 pub struct CommitGraph {
-    graph_rows: Vec<Arc<Mutex<GraphRow>>>,
+    graph_layout: GraphLayout,
+    is_loading_rows: bool,
+    revwalk_roots: RevwalkRoots,
+    head_name: String,
+    selected_oid: Option<Oid>,
+    // Shift-clicking selects everything between selected_oid and this.
+    range_end_oid: Option<Oid>,
+    context_menu_index: Option<usize>,
+    scroll_to_index: Option<usize>,
+    // A commit that was selected before its row was loaded, which gets scrolled to once it is.
+    scroll_to_oid: Option<Oid>,
 }
 
 impl CommitGraph {
     pub fn new(repo: &Repository) -> Result<Self> {
-        let graph_rows = CommitGraph::get_graph_rows(repo)?;
+        let revwalk_roots = RevwalkRoots::default();
+        let mut graph_layout = GraphLayout::new(repo, revwalk_roots)?;
+        graph_layout.load_rows(repo, GRAPH_PAGE_SIZE)?;
         Ok(Self {
-            graph_rows,
+            graph_layout,
+            is_loading_rows: false,
+            revwalk_roots,
+            head_name: CommitGraph::get_head_name(repo),
+            selected_oid: None,
+            range_end_oid: None,
+            context_menu_index: None,
+            scroll_to_index: None,
+            scroll_to_oid: None,
         })
     }
 
-    pub fn refresh_graph(&mut self, repo: &Repository) -> Result<()> {
-        self.graph_rows = CommitGraph::get_graph_rows(repo)?;
-        Ok(())
+    pub fn select_commit(&mut self, oid: Oid) {
+        self.selected_oid = Some(oid);
+        self.range_end_oid = None;
+        self.scroll_to_index = self.get_index(oid);
+        self.scroll_to_oid = if self.scroll_to_index.is_none() && !self.graph_layout.is_fully_loaded() { Some(oid) } else { None };
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selected_oid = None;
+        self.range_end_oid = None;
+    }
+
+    fn get_index(&self, oid: Oid) -> Option<usize> {
+        self.graph_layout.get_row_index(oid).filter(|i| *i < self.graph_layout.get_rows().len())
+    }
+
+    // Gets the first and last row indexes that are selected, inclusive.
+    fn get_selected_range(&self) -> Option<(usize, usize)> {
+        let selected_index = self.get_index(self.selected_oid?)?;
+        let range_end_index = self.range_end_oid.and_then(|oid| self.get_index(oid)).unwrap_or(selected_index);
+        Some((selected_index.min(range_end_index), selected_index.max(range_end_index)))
     }
 
-    fn get_graph_rows(repo: &Repository) -> Result<Vec<Arc<Mutex<GraphRow>>>> {
-        let oid_vec = git_revwalk(repo)?;
-        let commit_branch_map = GraphRowRef::get_commit_branch_map(repo)?;
-        let mut graph_rows = vec![];
-        // commit_map and commit_parent_oid_map are just used to get the parents within this fn.
-        let mut commit_map: HashMap<Oid, Arc<Mutex<GraphRow>>> = HashMap::new();
-        let mut commit_parent_oid_map: HashMap<Oid, Vec<Oid>> = HashMap::new();
-
-        // Loop through once to get all the commits and create a mapping to get the parents later.
-        for (i, oid) in oid_vec.iter().enumerate() {
-            let git_commit = repo.find_commit(*oid)?;
-            commit_parent_oid_map.insert(*oid, git_commit.parents().map(|p| p.id()).collect());
-
-            let mut graph_row_refs = vec![];
-            if let Some(refs) = commit_branch_map.get(&git_commit.id()) {
-                graph_row_refs = refs.clone();
-            }
-
-            let graph_row_arc = Arc::new(Mutex::new(GraphRow::new(git_commit, graph_row_refs, i)?));
-            commit_map.insert(*oid, graph_row_arc.clone());
-            graph_rows.push(graph_row_arc);
+    fn get_row_index(&self, pointer_pos: Pos2, scroll_area_top_left: Pos2) -> Option<usize> {
+        let row_index = ((pointer_pos.y - scroll_area_top_left.y - Y_OFFSET) / Y_SPACING).round();
+        if row_index >= 0.0 && (row_index as usize) < self.graph_layout.get_rows().len() {
+            return Some(row_index as usize);
         }
+        None
+    }
 
-        // Now, loop through a second time to set the parent locations.
-        let mut occupied_locations_table: Vec<Vec<usize>> = vec![];
-        for graph_row_arc in &graph_rows {
-            let mut graph_row = graph_row_arc.lock().unwrap();
+    // Returns None if rows are already being loaded.
+    pub fn start_row_load(&mut self) -> Option<RowLoad> {
+        Some(RowLoad {
+            row_loader: self.graph_layout.take_row_loader()?,
+            scroll_to_oid_opt: self.scroll_to_oid,
+            loaded_commits: vec![],
+        })
+    }
 
-            // Set the current node position as occupied (or find a position that's unoccupied and occupy it).
-            if graph_row.circle_location.y < occupied_locations_table.len() {
-                while occupied_locations_table[graph_row.circle_location.y].contains(&graph_row.circle_location.x) {
-                    graph_row.circle_location.x += 1;
-                }
-                occupied_locations_table[graph_row.circle_location.y].push(graph_row.circle_location.x);
-            } else {
-                occupied_locations_table.push(vec![graph_row.circle_location.x]);
-            }
-
-            if let Some(parent_oids) = commit_parent_oid_map.get(&graph_row.oid) {
-                for parent_oid in parent_oids {
-                    // Set the space of the line from the current node to its parents as occupied.
-                    if let Some(parent_graph_row_arc) = commit_map.get(parent_oid) {
-                        let mut parent_graph_row = parent_graph_row_arc.lock().unwrap();
-                        let mut moved_x_val = 0;
-                        for i in (graph_row.circle_location.y + 1)..parent_graph_row.circle_location.y {
-                            let mut x_val = graph_row.circle_location.x;
-                            if i < occupied_locations_table.len() {
-                                while occupied_locations_table[i].contains(&x_val) {
-                                    x_val += 1;
-                                    // Note: this has to stay in the loop so it's only set when x changes!
-                                    // and not just to graph_row.circle_location.x
-                                    moved_x_val = x_val;
-                                }
-                                occupied_locations_table[i].push(x_val);
-                            } else {
-                                occupied_locations_table.push(vec![x_val]);
-                            }
-                        }
-                        // This is used particularly for merging lines
-                        parent_graph_row.circle_location.x = moved_x_val;
-                    }
-                }
-            }
+    pub fn finish_row_load(&mut self, row_load: RowLoad) {
+        self.is_loading_rows = false;
+        self.graph_layout.add_rows(row_load.row_loader, row_load.loaded_commits);
+        // The commit's row is loaded now unless it isn't in the graph or the load failed, so stop waiting for it.
+        if let Some(oid) = row_load.scroll_to_oid_opt.filter(|oid| self.scroll_to_oid == Some(*oid)) {
+            self.scroll_to_index = self.get_index(oid);
+            self.scroll_to_oid = None;
         }
+    }
 
-        // Loop through after everything's set in order to properly occupy spaces by curved lines just for summary text positions.
-        for graph_row_arc in &graph_rows {
-            let graph_row = graph_row_arc.lock().unwrap();
-            // This is to set summary text positions next to curved lines.
-            if let Some(parent_oids) = commit_parent_oid_map.get(&graph_row.oid) {
-                for parent_oid in parent_oids {
-                    if let Some(parent_commit_arc) = commit_map.get(parent_oid) {
-                        let parent_graph_row = parent_commit_arc.lock().unwrap();
-                        if graph_row.circle_location.x < parent_graph_row.circle_location.x {
-                            let x_val = parent_graph_row.circle_location.x;
-                            occupied_locations_table[graph_row.circle_location.y].push(x_val);
-                        } else if graph_row.circle_location.x > parent_graph_row.circle_location.x {
-                            let x_val = graph_row.circle_location.x;
-                            occupied_locations_table[parent_graph_row.circle_location.y].push(x_val);
-                        }
-                    }
-                }
-            }
+    pub fn get_load_status_opt(&self) -> Option<String> {
+        if self.graph_layout.is_fully_loaded() {
+            return None;
         }
+        let loaded_count = self.graph_layout.get_rows().len();
+        match self.graph_layout.get_estimated_total_count() {
+            Some(total_count) => Some(format!("Loaded {} of ~{} commits", loaded_count, total_count)),
+            None => Some(format!("Loaded {} commits...", loaded_count)),
+        }
+    }
 
-        // Loop through a final time to add lines and set summary text positions.
-        for graph_row_rc in &graph_rows {
-            let mut graph_row = graph_row_rc.lock().unwrap();
-
-            graph_row.summary_location.x = *occupied_locations_table[graph_row.circle_location.y].iter().max().unwrap_or(&0) + 1;
-
-            if let Some(parent_oids) = commit_parent_oid_map.get(&graph_row.oid) {
-                for parent_oid in parent_oids {
-                    if let Some(parent_commit_arc) = commit_map.get(parent_oid) {
-                        let parent_graph_row = parent_commit_arc.lock().unwrap();
-
-                        let child_x = graph_row.circle_location.x;
-                        let child_y = graph_row.circle_location.y;
-                        let parent_x = parent_graph_row.circle_location.x;
-                        let parent_y = parent_graph_row.circle_location.y;
-                        let before_parent_y = parent_graph_row.circle_location.y - 1;
-                        if before_parent_y != child_y {
-                            let start_index;
-                            let end_index;
-                            let line_x;
-                            if parent_x > child_x {
-                                line_x = parent_x;
-                                start_index = child_y + 1;
-                                end_index = before_parent_y;
-                            } else {
-                                line_x = child_x;
-                                start_index = child_y;
-                                end_index = before_parent_y - 1;
-                            }
-                            for i in start_index..=end_index {
-                                if i == child_y {
-                                    // This is so graph_row doesn't get borrowed twice.
-                                    graph_row.lines.push(Line::new(line_x, i, line_x, i + 1));
-                                } else {
-                                    graph_rows[i].lock().unwrap().lines.push(Line::new(line_x, i, line_x, i + 1));
-                                }
-                            }
-                        }
+    pub fn get_revwalk_roots(&self) -> RevwalkRoots {
+        self.revwalk_roots
+    }
 
-                        if before_parent_y == child_y {
-                            // This is so graph_row doesn't get borrowed twice.
-                            graph_row.lines.push(Line::new(child_x, before_parent_y, parent_x, parent_y));
-                        } else {
-                            graph_rows[before_parent_y].lock().unwrap().lines.push(Line::new(child_x, before_parent_y, parent_x, parent_y));
-                        }
-                    }
-                }
-            }
+    // The graph needs to be refreshed afterwards for this to show.
+    pub fn set_revwalk_roots(&mut self, revwalk_roots: RevwalkRoots) {
+        self.revwalk_roots = revwalk_roots;
+    }
+
+    pub fn refresh_graph(&mut self, repo: &Repository) -> Result<()> {
+        self.graph_layout.refresh(repo, self.revwalk_roots)?;
+        self.head_name = CommitGraph::get_head_name(repo);
+        Ok(())
+    }
+
+    fn get_head_name(repo: &Repository) -> String {
+        // HEAD is either detached or unborn if it doesn't have a shorthand.
+        match repo.head() {
+            Ok(head_ref) => String::from(head_ref.shorthand().unwrap_or("HEAD")),
+            Err(_) => String::from("HEAD"),
         }
-        Ok(graph_rows)
     }
 
-    pub fn show(&mut self, ui: &mut Ui) {
+    pub fn show(&mut self, ui: &mut Ui) -> Option<CommitGraphAction> {
         let visible_area_top = ui.min_rect().min.y;
         let visible_area_height = ui.min_rect().max.y - visible_area_top;
-        ScrollArea::both().id_source("graph-scroll-area").auto_shrink([false, false]).show(ui, |ui| {
+        let mut action_opt = None;
+        let mut visible_area_bottom_index = 0;
+        let mut scroll_area = ScrollArea::both().id_source("graph-scroll-area").auto_shrink([false, false]);
+        if let Some(i) = self.scroll_to_index.take() {
+            // Put the row in the middle of the visible area.
+            let scroll_offset = Y_OFFSET + Y_SPACING * i as f32 - visible_area_height / 2.0;
+            scroll_area = scroll_area.vertical_scroll_offset(scroll_offset.max(0.0));
+        }
+        scroll_area.show(ui, |ui| {
             // This ui.vertical is just to keep the contents at the top of the scroll area if they're
             // smaller than it.
             ui.vertical(|ui| {
-                let graph_height = self.graph_rows.len() as f32 * Y_SPACING;
-                let (response, painter) = ui.allocate_painter(Vec2::new(ui.available_width(), graph_height), Sense::hover());
+                let graph_height = self.graph_layout.get_rows().len() as f32 * Y_SPACING;
+                let (response, painter) = ui.allocate_painter(Vec2::new(ui.available_width(), graph_height), Sense::click());
                 let scroll_area_top_left = response.rect.left_top();
 
                 let scroll_position = visible_area_top - scroll_area_top_left.y;
                 let visible_area_top_index = (((scroll_position - Y_OFFSET) / Y_SPACING) as isize - VISIBLE_SCROLL_AREA_PADDING as isize).max(0) as usize;
-                let visible_area_bottom_index = (((scroll_position + visible_area_height - Y_OFFSET) / Y_SPACING) as usize + VISIBLE_SCROLL_AREA_PADDING).min(self.graph_rows.len());
+                visible_area_bottom_index = (((scroll_position + visible_area_height - Y_OFFSET) / Y_SPACING) as usize + VISIBLE_SCROLL_AREA_PADDING).min(self.graph_layout.get_rows().len());
+
+                if response.clicked() || response.secondary_clicked() {
+                    if let Some(i) = response.interact_pointer_pos().and_then(|pos| self.get_row_index(pos, scroll_area_top_left)) {
+                        let oid = self.graph_layout.get_rows()[i].oid;
+                        if response.secondary_clicked() {
+                            self.context_menu_index = Some(i);
+                        } else if ui.input(|input| input.modifiers.shift) && self.selected_oid.is_some() {
+                            self.range_end_oid = Some(oid);
+                        } else {
+                            self.selected_oid = Some(oid);
+                            self.range_end_oid = None;
+                            action_opt = Some(CommitGraphAction::SelectCommit(oid));
+                        }
+                    }
+                }
 
+                let selected_range_opt = self.get_selected_range();
+                let hover_pos_opt = response.hover_pos();
+                let mut tooltip_opt = None;
                 for i in visible_area_top_index..visible_area_bottom_index {
-                    self.graph_rows[i].lock().unwrap().show(&painter, scroll_area_top_left);
+                    let layout_row = &self.graph_layout.get_rows()[i];
+                    if selected_range_opt.is_some_and(|(start, end)| start <= i && i <= end) {
+                        let row_center_y = LocationIndex::new(0, i).get_relative_pos2(scroll_area_top_left).y;
+                        let row_rect = Rect::from_x_y_ranges(response.rect.x_range(), (row_center_y - Y_SPACING / 2.0)..=(row_center_y + Y_SPACING / 2.0));
+                        painter.rect_filled(row_rect, Rounding::ZERO, SELECTED_ROW_COLOR);
+                    }
+                    if let Some(tooltip) = show_row(layout_row, i, &painter, scroll_area_top_left, hover_pos_opt) {
+                        tooltip_opt = Some(tooltip);
+                    }
+                }
+                if let Some(tooltip) = tooltip_opt {
+                    show_tooltip_at_pointer(ui.ctx(), Id::new("graph-ref-tooltip"), |ui| {
+                        ui.label(tooltip);
+                    });
                 }
+
+                response.context_menu(|ui| {
+                    if let Some(i) = self.context_menu_index {
+                        // Act on the whole selection if the menu was opened on it, otherwise just on this commit.
+                        let (start, end) = match selected_range_opt {
+                            Some((start, end)) if start <= i && i <= end => (start, end),
+                            _ => (i, i),
+                        };
+                        // Only the commits on the line of history between the ends of the selection are used.
+                        let oids = self.graph_layout.get_first_parent_line(start, end).unwrap_or_default();
+                        let disabled_text = "The selected commits have to be on one line of history and can't only be stashes.";
+                        let commits_text = if start == end { "Commit" } else { "Selected Commits" };
+                        let cherry_pick_btn = Button::new(format!("Cherry-pick {} Onto Current Branch", commits_text));
+                        if ui.add_enabled(!oids.is_empty(), cherry_pick_btn).on_disabled_hover_text(disabled_text).clicked() {
+                            action_opt = Some(CommitGraphAction::CherryPick(oids.clone()));
+                            ui.close_menu();
+                        }
+                        if ui.add_enabled(!oids.is_empty(), Button::new(format!("Revert {}", commits_text))).on_disabled_hover_text(disabled_text).clicked() {
+                            action_opt = Some(CommitGraphAction::Revert(oids));
+                            ui.close_menu();
+                        }
+                        ui.separator();
+                        if ui.button(format!("Reset {} To Here...", self.head_name)).clicked() {
+                            action_opt = Some(CommitGraphAction::Reset(self.graph_layout.get_rows()[i].oid));
+                            ui.close_menu();
+                        }
+                        if ui.button("Create Tag Here...").clicked() {
+                            action_opt = Some(CommitGraphAction::CreateTag(self.graph_layout.get_rows()[i].oid));
+                            ui.close_menu();
+                        }
+                        if ui.button("Interactive Rebase From Here...").clicked() {
+                            action_opt = Some(CommitGraphAction::InteractiveRebase(self.graph_layout.get_rows()[end].oid));
+                            ui.close_menu();
+                        }
+                    }
+                });
             });
         });
+        let is_near_end = visible_area_bottom_index + LOAD_MORE_ROWS_MARGIN >= self.graph_layout.get_rows().len();
+        if action_opt.is_none() && (is_near_end || self.scroll_to_oid.is_some()) && !self.is_loading_rows && !self.graph_layout.is_fully_loaded() {
+            self.is_loading_rows = true;
+            action_opt = Some(CommitGraphAction::LoadMoreRows);
+        }
+        action_opt
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,156 @@
+use anyhow::Result;
+use egui::{Button, Color32, RichText, ScrollArea, TextEdit, Ui};
+use git2::Repository;
+use crate::backend::git_functions::{git_abort_merge, git_commit, git_rebase_abort, git_rebase_continue, git_rebase_skip, git_resolve_conflict, git_sequence_abort, git_sequence_continue, git_stage, ConflictSide};
+use crate::backend::git_utils::{self, ConflictEntry, RebaseStatus, SequenceStatus};
+
+const CONFLICTED_COLOR: Color32 = Color32::from_rgb(255, 100, 100);
+
+enum ConflictAction {
+    Resolve(String, ConflictSide),
+    MarkResolved(String),
+    Finish,
+    Abort,
+    RebaseContinue,
+    RebaseSkip,
+    RebaseAbort,
+    SequenceContinue,
+    SequenceAbort,
+}
+
+pub struct ConflictView {
+    is_merging: bool,
+    rebase_status_opt: Option<RebaseStatus>,
+    sequence_status_opt: Option<SequenceStatus>,
+    conflicts: Vec<ConflictEntry>,
+    message: String,
+}
+
+impl ConflictView {
+    pub fn new(repo: &Repository) -> Result<Self> {
+        let mut conflict_view = Self {
+            is_merging: false,
+            rebase_status_opt: None,
+            sequence_status_opt: None,
+            conflicts: vec![],
+            message: String::new(),
+        };
+        conflict_view.refresh(repo)?;
+        Ok(conflict_view)
+    }
+
+    pub fn refresh(&mut self, repo: &Repository) -> Result<()> {
+        if repo.is_bare() {
+            return Ok(());
+        }
+        self.is_merging = git_utils::is_merging(repo);
+        self.rebase_status_opt = git_utils::get_rebase_status(repo)?;
+        self.sequence_status_opt = git_utils::get_sequence_status(repo)?;
+        self.conflicts = git_utils::get_conflicts(repo)?;
+        if !self.is_active() || !self.has_message() {
+            self.message.clear();
+        } else if self.message.is_empty() {
+            self.message = git_utils::get_merge_message(repo)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.is_merging || self.rebase_status_opt.is_some() || self.sequence_status_opt.is_some() || !self.conflicts.is_empty()
+    }
+
+    // Rebases, cherry-picks and reverts reuse the messages libgit2 prepares, so there's nothing to edit.
+    fn has_message(&self) -> bool {
+        self.rebase_status_opt.is_none() && self.sequence_status_opt.is_none()
+    }
+
+    // Returns true if anything was changed, so the rest of the tab can be refreshed.
+    pub fn show(&mut self, ui: &mut Ui, repo: &Repository) -> Result<bool> {
+        let mut action_opt = None;
+        if let Some(rebase_status) = &self.rebase_status_opt {
+            ui.horizontal(|ui| {
+                ui.heading("Rebase In Progress");
+                if ui.add_enabled(self.conflicts.is_empty(), Button::new("Continue")).clicked() {
+                    action_opt = Some(ConflictAction::RebaseContinue);
+                }
+                if ui.button("Skip").clicked() {
+                    action_opt = Some(ConflictAction::RebaseSkip);
+                }
+                if ui.button("Abort").clicked() {
+                    action_opt = Some(ConflictAction::RebaseAbort);
+                }
+            });
+            ui.label(format!("Rebasing {} onto {}: step {} of {} ({})", rebase_status.branch_name, rebase_status.onto_name, rebase_status.current_step, rebase_status.total_steps, rebase_status.current_summary));
+            if rebase_status.is_edit_stop {
+                ui.label("Stopped to edit this commit. Amend it with the commit box if you like, then continue.");
+            }
+        } else if let Some(sequence_status) = &self.sequence_status_opt {
+            ui.horizontal(|ui| {
+                ui.heading(format!("{} In Progress", sequence_status.kind.get_title()));
+                if ui.add_enabled(self.conflicts.is_empty(), Button::new("Continue")).clicked() {
+                    action_opt = Some(ConflictAction::SequenceContinue);
+                }
+                if ui.button("Abort").clicked() {
+                    action_opt = Some(ConflictAction::SequenceAbort);
+                }
+            });
+            ui.label(format!("Applying {} ({} more after this)", sequence_status.current_summary, sequence_status.remaining_count));
+        } else {
+            ui.horizontal(|ui| {
+                let heading = if self.is_merging { "Merge In Progress" } else { "Conflicts" };
+                ui.heading(heading);
+                if ui.button("Abort Merge").clicked() {
+                    action_opt = Some(ConflictAction::Abort);
+                }
+            });
+        }
+        ScrollArea::vertical().id_source("conflict-view-scroll-area").max_height(ui.available_height() / 2.0).show(ui, |ui| {
+            if self.conflicts.is_empty() {
+                ui.label("All conflicts are resolved.");
+            }
+            for conflict in &self.conflicts {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new(conflict.get_description()).color(CONFLICTED_COLOR).strong());
+                    ui.label(&conflict.path);
+                    if ui.small_button("Take Ours").clicked() {
+                        action_opt = Some(ConflictAction::Resolve(conflict.path.clone(), ConflictSide::Ours));
+                    }
+                    if ui.small_button("Take Theirs").clicked() {
+                        action_opt = Some(ConflictAction::Resolve(conflict.path.clone(), ConflictSide::Theirs));
+                    }
+                    if ui.small_button("Mark Resolved").clicked() {
+                        action_opt = Some(ConflictAction::MarkResolved(conflict.path.clone()));
+                    }
+                });
+            }
+        });
+        if self.has_message() {
+            ui.add(TextEdit::multiline(&mut self.message).hint_text("Merge message").desired_width(f32::INFINITY));
+            let finish_text = if self.is_merging { "Finish Merge" } else { "Commit" };
+            if ui.add_enabled(self.conflicts.is_empty(), Button::new(finish_text)).clicked() {
+                action_opt = Some(ConflictAction::Finish);
+            }
+        }
+
+        let is_changed = action_opt.is_some();
+        if let Some(action) = action_opt {
+            match action {
+                ConflictAction::Resolve(path, side) => git_resolve_conflict(repo, &path, side)?,
+                ConflictAction::MarkResolved(path) => git_stage(repo, &[path])?,
+                ConflictAction::Finish => {
+                    git_commit(repo, &self.message, false, None, None)?;
+                },
+                ConflictAction::Abort => git_abort_merge(repo)?,
+                ConflictAction::RebaseContinue => git_rebase_continue(repo)?,
+                ConflictAction::RebaseSkip => git_rebase_skip(repo)?,
+                ConflictAction::RebaseAbort => git_rebase_abort(repo)?,
+                ConflictAction::SequenceContinue => {
+                    git_sequence_continue(repo)?;
+                },
+                ConflictAction::SequenceAbort => git_sequence_abort(repo)?,
+            };
+            self.refresh(repo)?;
+        }
+        Ok(is_changed)
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+use anyhow::Result;
+use egui::{Button, Color32, Label, RichText, ScrollArea, Ui};
+use git2::Repository;
+use crate::backend::git_functions::{git_discard_lines, git_stage_lines, git_unstage_lines};
+use crate::backend::git_utils::{FileDiff, FileDiffLine};
+
+const ADDED_LINE_COLOR: Color32 = Color32::from_rgb(0, 70, 0);
+const DELETED_LINE_COLOR: Color32 = Color32::from_rgb(90, 0, 0);
+const HUNK_HEADER_COLOR: Color32 = Color32::from_rgb(100, 150, 255);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DiffViewKind {
+    Unstaged,
+    Staged,
+    ReadOnly,
+}
+
+enum DiffViewAction {
+    Stage(HashSet<(usize, usize)>),
+    Unstage(HashSet<(usize, usize)>),
+    Discard(HashSet<(usize, usize)>),
+}
+
+pub struct DiffView {
+    kind: DiffViewKind,
+    file_diff: FileDiff,
+    selected_lines: HashSet<(usize, usize)>,
+}
+
+impl DiffView {
+    pub fn new(kind: DiffViewKind, file_diff: FileDiff) -> Self {
+        Self {
+            kind,
+            file_diff,
+            selected_lines: HashSet::new(),
+        }
+    }
+
+    pub fn get_kind(&self) -> DiffViewKind {
+        self.kind
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.file_diff.path
+    }
+
+    pub fn set_file_diff(&mut self, file_diff: FileDiff) {
+        self.file_diff = file_diff;
+        // Line indexes aren't meaningful once the diff has changed.
+        self.selected_lines.clear();
+    }
+
+    fn show_hunk_btns(ui: &mut Ui, kind: DiffViewKind, file_diff: &FileDiff, hunk_index: usize, action_opt: &mut Option<DiffViewAction>) {
+        match kind {
+            DiffViewKind::Unstaged => {
+                if ui.small_button("Stage Hunk").clicked() {
+                    *action_opt = Some(DiffViewAction::Stage(file_diff.get_change_lines(hunk_index)));
+                }
+                if ui.small_button("Discard Hunk").clicked() {
+                    *action_opt = Some(DiffViewAction::Discard(file_diff.get_change_lines(hunk_index)));
+                }
+            },
+            DiffViewKind::Staged => {
+                if ui.small_button("Unstage Hunk").clicked() {
+                    *action_opt = Some(DiffViewAction::Unstage(file_diff.get_change_lines(hunk_index)));
+                }
+            },
+            DiffViewKind::ReadOnly => (),
+        }
+    }
+
+    fn show_line(ui: &mut Ui, kind: DiffViewKind, selected_lines: &mut HashSet<(usize, usize)>, hunk_index: usize, line_index: usize, line: &FileDiffLine) {
+        ui.horizontal(|ui| {
+            if kind != DiffViewKind::ReadOnly {
+                if line.is_change() {
+                    let mut is_selected = selected_lines.contains(&(hunk_index, line_index));
+                    if ui.checkbox(&mut is_selected, "").changed() {
+                        if is_selected {
+                            selected_lines.insert((hunk_index, line_index));
+                        } else {
+                            selected_lines.remove(&(hunk_index, line_index));
+                        }
+                    }
+                } else {
+                    ui.add_space(ui.spacing().interact_size.y + ui.spacing().item_spacing.x);
+                }
+            }
+            let old_lineno = line.old_lineno.map(|n| n.to_string()).unwrap_or_default();
+            let new_lineno = line.new_lineno.map(|n| n.to_string()).unwrap_or_default();
+            ui.label(RichText::new(format!("{:>5} {:>5}", old_lineno, new_lineno)).monospace().weak());
+
+            let mut text = RichText::new(format!("{}{}", line.origin, line.content.trim_end_matches(['\n', '\r']))).monospace();
+            if line.origin == '+' {
+                text = text.background_color(ADDED_LINE_COLOR);
+            } else if line.origin == '-' {
+                text = text.background_color(DELETED_LINE_COLOR);
+            }
+            ui.add(Label::new(text).wrap(false));
+        });
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, repo: &Repository) -> Result<bool> {
+        let mut action_opt = None;
+        ui.horizontal(|ui| {
+            ui.heading(self.file_diff.get_display_path());
+            let has_selection = !self.selected_lines.is_empty();
+            match self.kind {
+                DiffViewKind::Unstaged => {
+                    if ui.add_enabled(has_selection, Button::new("Stage Selected Lines")).clicked() {
+                        action_opt = Some(DiffViewAction::Stage(self.selected_lines.clone()));
+                    }
+                    if ui.add_enabled(has_selection, Button::new("Discard Selected Lines")).clicked() {
+                        action_opt = Some(DiffViewAction::Discard(self.selected_lines.clone()));
+                    }
+                },
+                DiffViewKind::Staged => {
+                    if ui.add_enabled(has_selection, Button::new("Unstage Selected Lines")).clicked() {
+                        action_opt = Some(DiffViewAction::Unstage(self.selected_lines.clone()));
+                    }
+                },
+                DiffViewKind::ReadOnly => (),
+            }
+        });
+        ui.separator();
+
+        ScrollArea::both().id_source("diff-view-scroll-area").auto_shrink([false, false]).show(ui, |ui| {
+            if self.file_diff.is_binary {
+                ui.label("Binary file, no diff to show.");
+                return;
+            }
+            if self.file_diff.hunks.is_empty() {
+                ui.label("No changes to show.");
+                return;
+            }
+            for (hunk_index, hunk) in self.file_diff.hunks.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new(&hunk.header).monospace().color(HUNK_HEADER_COLOR));
+                    DiffView::show_hunk_btns(ui, self.kind, &self.file_diff, hunk_index, &mut action_opt);
+                });
+                for (line_index, line) in hunk.lines.iter().enumerate() {
+                    DiffView::show_line(ui, self.kind, &mut self.selected_lines, hunk_index, line_index, line);
+                }
+            }
+        });
+
+        if let Some(action) = action_opt {
+            match action {
+                DiffViewAction::Stage(lines) => git_stage_lines(repo, &self.file_diff, &lines)?,
+                DiffViewAction::Unstage(lines) => git_unstage_lines(repo, &self.file_diff, &lines)?,
+                DiffViewAction::Discard(lines) => git_discard_lines(repo, &self.file_diff, &lines)?,
+            };
+            self.selected_lines.clear();
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
@@ -4,3 +4,9 @@ mod branch_tree;
 mod modals;
 mod utils;
 mod commit_graph;
+mod status_panel;
+mod diff_view;
+mod commit_box;
+mod commit_details;
+mod conflict_view;
+mod rebase_editor;
@@ -1,6 +1,12 @@
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use anyhow::Result;
-use egui::{Align, Align2, Area, Button, Color32, Frame, Layout, Stroke, Ui, Vec2};
+use anyhow::{bail, Result};
+use egui::{Align, Align2, Area, Button, Color32, Context, Frame, Grid, Layout, ScrollArea, Stroke, TextEdit, Ui, Vec2};
+use git2::{Oid, Repository};
+use crate::backend::git_functions::{git_clone, git_init, MergeKind, ResetMode};
+use crate::backend::git_utils;
+use crate::backend::git_utils::{FileChange, RemoteInfo, SequenceKind};
+use crate::backend::progress::{OperationProgressList, Prompt, PromptAnswer, PromptRequest};
 use crate::frontend::tab::OG2Tab;
 use crate::frontend::utils;
 
@@ -12,7 +18,7 @@ pub trait Modal {
     fn close(&mut self);
     fn show_in_modal<R>(&mut self, modal_id: String, ui: &mut Ui, modal_contents: impl FnOnce(&mut Self, &mut Ui) -> R) -> R {
         Area::new(modal_id).anchor(Align2::CENTER_TOP, Vec2::new(0.0, MODAL_Y_OFFSET)).show(ui.ctx(), |ui| {
-            Frame::popup(&*ui.ctx().style()).fill(Color32::BLACK).stroke(Stroke::new(MODAL_BORDER_WIDTH, Color32::WHITE)).show(ui, |ui| {
+            Frame::popup(&ui.ctx().style()).fill(Color32::BLACK).stroke(Stroke::new(MODAL_BORDER_WIDTH, Color32::WHITE)).show(ui, |ui| {
                 ui.allocate_ui_with_layout(ui.max_rect().size() / 3.0, Layout::top_down(Align::Center).with_main_justify(true).with_cross_justify(true), |ui| {
                     ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
                         if ui.add(Button::new("X").fill(Color32::RED)).clicked() {
@@ -75,8 +81,10 @@ impl ErrorModal {
 
 pub struct AddTabModal {
     error_modal: Arc<Mutex<ErrorModal>>,
+    init_modal: Arc<Mutex<InitModal>>,
+    clone_modal: Arc<Mutex<CloneModal>>,
     is_open: bool,
-    is_loading: Arc<Mutex<bool>>,
+    progress_list: OperationProgressList,
 }
 
 impl Modal for AddTabModal {
@@ -90,11 +98,13 @@ impl Modal for AddTabModal {
 }
 
 impl AddTabModal {
-    pub fn new(error_modal: Arc<Mutex<ErrorModal>>, is_loading: Arc<Mutex<bool>>) -> Self {
+    pub fn new(error_modal: Arc<Mutex<ErrorModal>>, init_modal: Arc<Mutex<InitModal>>, clone_modal: Arc<Mutex<CloneModal>>, progress_list: OperationProgressList) -> Self {
         Self {
             error_modal,
+            init_modal,
+            clone_modal,
             is_open: false,
-            is_loading,
+            progress_list,
         }
     }
 
@@ -104,15 +114,15 @@ impl AddTabModal {
                 ui.label("To open a new tab, please initialize, open, or clone another repository.");
                 ui.horizontal(|ui| -> Result<()> {
                     if ui.button("Init").clicked() {
-                        // TODO: Implement Init.
+                        inner_self.init_modal.lock().unwrap().open();
                         inner_self.close();
                     }
                     if ui.button("Open").clicked() {
-                        utils::open_repo_as_tab(tabs, active_tab, inner_self.error_modal.clone(), inner_self.is_loading.clone(), ui.ctx().clone())?;
+                        utils::open_repo_as_tab(tabs, active_tab, inner_self.error_modal.clone(), inner_self.progress_list.clone(), ui.ctx().clone())?;
                         inner_self.close();
                     }
                     if ui.button("Clone").clicked() {
-                        // TODO: Implement Clone.
+                        inner_self.clone_modal.lock().unwrap().open();
                         inner_self.close();
                     }
                     Ok(())
@@ -122,3 +132,1091 @@ impl AddTabModal {
         Ok(())
     }
 }
+
+pub struct InitModal {
+    error_modal: Arc<Mutex<ErrorModal>>,
+    is_open: bool,
+    progress_list: OperationProgressList,
+    destination: String,
+    initial_branch: String,
+    is_bare: bool,
+    template_path: String,
+    create_initial_commit: bool,
+    create_gitignore: bool,
+}
+
+impl Modal for InitModal {
+    fn open(&mut self) {
+        self.is_open = true;
+    }
+
+    fn close(&mut self) {
+        self.is_open = false;
+    }
+}
+
+impl InitModal {
+    pub fn new(error_modal: Arc<Mutex<ErrorModal>>, progress_list: OperationProgressList) -> Self {
+        Self {
+            error_modal,
+            is_open: false,
+            progress_list,
+            destination: String::new(),
+            initial_branch: git_utils::get_default_branch_name(),
+            is_bare: false,
+            template_path: String::new(),
+            create_initial_commit: false,
+            create_gitignore: false,
+        }
+    }
+
+    fn init_repo(&self, tabs: Arc<Mutex<Vec<OG2Tab>>>, active_tab: Arc<Mutex<usize>>, ctx: Context) -> Result<()> {
+        let destination = self.destination.trim();
+        if destination.is_empty() {
+            bail!("Please choose a folder to initialize the repository in.");
+        }
+        let path = PathBuf::from(destination);
+        let initial_branch = String::from(self.initial_branch.trim());
+        if initial_branch.is_empty() {
+            bail!("Please enter an initial branch name.");
+        }
+        let template_path_opt = match self.template_path.trim() {
+            "" => None,
+            t => Some(PathBuf::from(t)),
+        };
+        let is_bare = self.is_bare;
+        let create_initial_commit = self.create_initial_commit;
+        let create_gitignore = self.create_gitignore;
+
+        utils::get_repo_as_tab(move |_| {
+            let repo = git_init(&path, &initial_branch, is_bare, template_path_opt.as_deref(), create_initial_commit, create_gitignore)?;
+            Ok((git_utils::get_repo_name(&path), repo))
+        }, tabs, active_tab, self.error_modal.clone(), self.progress_list.clone(), ctx);
+        Ok(())
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, tabs: Arc<Mutex<Vec<OG2Tab>>>, active_tab: Arc<Mutex<usize>>) -> Result<()> {
+        if self.is_open {
+            return self.show_in_modal(String::from("init-modal"), ui, |inner_self, ui| -> Result<()> {
+                Grid::new("init-modal-grid").num_columns(2).show(ui, |ui| {
+                    ui.label("Folder:");
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut inner_self.destination);
+                        if ui.button("Browse").clicked() {
+                            if let Some(pf) = git_utils::pick_folder() {
+                                inner_self.destination = pf.to_string_lossy().into_owned();
+                            }
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Initial Branch:");
+                    ui.text_edit_singleline(&mut inner_self.initial_branch);
+                    ui.end_row();
+
+                    ui.label("Template (optional):");
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut inner_self.template_path);
+                        if ui.button("Browse").clicked() {
+                            if let Some(pf) = git_utils::pick_folder() {
+                                inner_self.template_path = pf.to_string_lossy().into_owned();
+                            }
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("");
+                    ui.checkbox(&mut inner_self.is_bare, "Bare");
+                    ui.end_row();
+
+                    ui.label("");
+                    ui.checkbox(&mut inner_self.create_initial_commit, "Create Empty Initial Commit");
+                    ui.end_row();
+
+                    ui.label("");
+                    ui.checkbox(&mut inner_self.create_gitignore, "Create Starter .gitignore");
+                    ui.end_row();
+                });
+                if ui.button("Init").clicked() {
+                    inner_self.init_repo(tabs, active_tab, ui.ctx().clone())?;
+                    inner_self.close();
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+}
+
+pub struct CloneModal {
+    error_modal: Arc<Mutex<ErrorModal>>,
+    is_open: bool,
+    progress_list: OperationProgressList,
+    url: String,
+    destination: String,
+    branch: String,
+    depth: String,
+    recurse_submodules: bool,
+}
+
+impl Modal for CloneModal {
+    fn open(&mut self) {
+        self.is_open = true;
+    }
+
+    fn close(&mut self) {
+        self.is_open = false;
+    }
+}
+
+impl CloneModal {
+    pub fn new(error_modal: Arc<Mutex<ErrorModal>>, progress_list: OperationProgressList) -> Self {
+        Self {
+            error_modal,
+            is_open: false,
+            progress_list,
+            url: String::new(),
+            destination: String::new(),
+            branch: String::new(),
+            depth: String::new(),
+            recurse_submodules: true,
+        }
+    }
+
+    fn clone_repo(&self, tabs: Arc<Mutex<Vec<OG2Tab>>>, active_tab: Arc<Mutex<usize>>, ctx: Context) -> Result<()> {
+        let url = String::from(self.url.trim());
+        if url.is_empty() {
+            bail!("Please enter a URL to clone from.");
+        }
+        let destination = self.destination.trim();
+        if destination.is_empty() {
+            bail!("Please choose a destination folder.");
+        }
+        let path = PathBuf::from(destination);
+        let branch_opt = match self.branch.trim() {
+            "" => None,
+            b => Some(String::from(b)),
+        };
+        let depth_opt = match self.depth.trim() {
+            "" => None,
+            d => match d.parse::<i32>() {
+                Ok(depth) if depth > 0 => Some(depth),
+                _ => bail!("Depth must be a positive whole number!"),
+            },
+        };
+        let recurse_submodules = self.recurse_submodules;
+
+        utils::get_repo_as_tab(move |progress| {
+            let repo = git_clone(&url, &path, branch_opt.as_deref(), depth_opt, recurse_submodules, &progress)?;
+            Ok((git_utils::get_repo_name(&path), repo))
+        }, tabs, active_tab, self.error_modal.clone(), self.progress_list.clone(), ctx);
+        Ok(())
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, tabs: Arc<Mutex<Vec<OG2Tab>>>, active_tab: Arc<Mutex<usize>>) -> Result<()> {
+        if self.is_open {
+            return self.show_in_modal(String::from("clone-modal"), ui, |inner_self, ui| -> Result<()> {
+                Grid::new("clone-modal-grid").num_columns(2).show(ui, |ui| {
+                    ui.label("URL:");
+                    ui.text_edit_singleline(&mut inner_self.url);
+                    ui.end_row();
+
+                    ui.label("Destination:");
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut inner_self.destination);
+                        if ui.button("Browse").clicked() {
+                            // Clone into a new folder named after the repo, the same way git does.
+                            if let Some(pf) = git_utils::pick_folder() {
+                                let path = pf.join(git_utils::get_repo_name_from_url(inner_self.url.trim()));
+                                inner_self.destination = path.to_string_lossy().into_owned();
+                            }
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Branch (optional):");
+                    ui.text_edit_singleline(&mut inner_self.branch);
+                    ui.end_row();
+
+                    ui.label("Depth (optional):");
+                    ui.text_edit_singleline(&mut inner_self.depth);
+                    ui.end_row();
+
+                    ui.label("");
+                    ui.checkbox(&mut inner_self.recurse_submodules, "Recurse Submodules");
+                    ui.end_row();
+                });
+                if ui.button("Clone").clicked() {
+                    inner_self.clone_repo(tabs, active_tab, ui.ctx().clone())?;
+                    inner_self.close();
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum BranchModalKind {
+    Create,
+    Rename,
+    SetUpstream,
+    DeleteRemote,
+}
+
+pub enum BranchModalResult {
+    Create(String, String, bool),
+    Rename(String, String),
+    SetUpstream(String, String),
+    DeleteRemote(String),
+}
+
+pub struct BranchModal {
+    is_open: bool,
+    kind: BranchModalKind,
+    ref_name: String,
+    input: String,
+    checkout: bool,
+}
+
+impl Modal for BranchModal {
+    fn open(&mut self) {
+        self.is_open = true;
+    }
+
+    fn close(&mut self) {
+        self.is_open = false;
+    }
+}
+
+impl BranchModal {
+    pub fn new() -> Self {
+        Self {
+            is_open: false,
+            kind: BranchModalKind::Create,
+            ref_name: String::new(),
+            input: String::new(),
+            checkout: true,
+        }
+    }
+
+    fn open_with(&mut self, kind: BranchModalKind, ref_name: &str, input: String) {
+        self.kind = kind;
+        self.ref_name = String::from(ref_name);
+        self.input = input;
+        self.open();
+    }
+
+    pub fn open_create(&mut self, start_ref_name: &str) {
+        self.open_with(BranchModalKind::Create, start_ref_name, String::new());
+    }
+
+    pub fn open_rename(&mut self, ref_name: &str) {
+        let branch_name = ref_name.strip_prefix("refs/heads/").unwrap_or(ref_name);
+        self.open_with(BranchModalKind::Rename, ref_name, String::from(branch_name));
+    }
+
+    pub fn open_set_upstream(&mut self, ref_name: &str) {
+        let branch_name = ref_name.strip_prefix("refs/heads/").unwrap_or(ref_name);
+        self.open_with(BranchModalKind::SetUpstream, ref_name, format!("origin/{}", branch_name));
+    }
+
+    pub fn open_delete_remote(&mut self, remote_ref_name: &str) {
+        self.open_with(BranchModalKind::DeleteRemote, remote_ref_name, String::new());
+    }
+
+    fn get_result(&self) -> BranchModalResult {
+        let input = String::from(self.input.trim());
+        match self.kind {
+            BranchModalKind::Create => BranchModalResult::Create(self.ref_name.clone(), input, self.checkout),
+            BranchModalKind::Rename => BranchModalResult::Rename(self.ref_name.clone(), input),
+            BranchModalKind::SetUpstream => BranchModalResult::SetUpstream(self.ref_name.clone(), input),
+            BranchModalKind::DeleteRemote => BranchModalResult::DeleteRemote(self.ref_name.clone()),
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut Ui) -> Option<BranchModalResult> {
+        if self.is_open {
+            return self.show_in_modal(String::from("branch-modal"), ui, |inner_self, ui| -> Option<BranchModalResult> {
+                let (label, btn_text) = match inner_self.kind {
+                    BranchModalKind::Create => (format!("New branch name (starting from {}):", inner_self.ref_name), "Create"),
+                    BranchModalKind::Rename => (format!("New name for {}:", inner_self.ref_name), "Rename"),
+                    BranchModalKind::SetUpstream => (format!("Upstream for {} (e.g. origin/main):", inner_self.ref_name), "Set Upstream"),
+                    BranchModalKind::DeleteRemote => (format!("Are you sure you want to delete {} on the remote?", inner_self.ref_name), "Delete"),
+                };
+                ui.label(label);
+                if inner_self.kind != BranchModalKind::DeleteRemote {
+                    ui.text_edit_singleline(&mut inner_self.input);
+                }
+                if inner_self.kind == BranchModalKind::Create {
+                    ui.checkbox(&mut inner_self.checkout, "Checkout After Creating");
+                }
+                let is_input_valid = inner_self.kind == BranchModalKind::DeleteRemote || !inner_self.input.trim().is_empty();
+                if ui.add_enabled(is_input_valid, Button::new(btn_text)).clicked() {
+                    inner_self.close();
+                    return Some(inner_self.get_result());
+                }
+                None
+            });
+        }
+        None
+    }
+}
+
+pub struct MergeModal {
+    is_open: bool,
+    ref_name: String,
+    merge_kind: MergeKind,
+}
+
+impl Modal for MergeModal {
+    fn open(&mut self) {
+        self.is_open = true;
+    }
+
+    fn close(&mut self) {
+        self.is_open = false;
+    }
+}
+
+impl MergeModal {
+    pub fn new() -> Self {
+        Self {
+            is_open: false,
+            ref_name: String::new(),
+            merge_kind: MergeKind::FastForwardIfPossible,
+        }
+    }
+
+    pub fn open_merge(&mut self, ref_name: &str) {
+        self.ref_name = String::from(ref_name);
+        self.open();
+    }
+
+    pub fn show(&mut self, ui: &mut Ui) -> Option<(String, MergeKind)> {
+        if self.is_open {
+            return self.show_in_modal(String::from("merge-modal"), ui, |inner_self, ui| -> Option<(String, MergeKind)> {
+                ui.label(format!("Merge {} into the current branch:", inner_self.ref_name));
+                ui.radio_value(&mut inner_self.merge_kind, MergeKind::FastForwardIfPossible, "Fast-forward if possible, otherwise create a merge commit");
+                ui.radio_value(&mut inner_self.merge_kind, MergeKind::FastForwardOnly, "Fast-forward only");
+                ui.radio_value(&mut inner_self.merge_kind, MergeKind::NoFastForward, "Always create a merge commit");
+                ui.radio_value(&mut inner_self.merge_kind, MergeKind::Squash, "Squash (stage the changes without committing)");
+                if ui.button("Merge").clicked() {
+                    inner_self.close();
+                    return Some((inner_self.ref_name.clone(), inner_self.merge_kind));
+                }
+                None
+            });
+        }
+        None
+    }
+}
+
+pub struct MainlineModal {
+    is_open: bool,
+    kind: SequenceKind,
+    oids: Vec<Oid>,
+    parent_summaries: Vec<String>,
+    mainline: u32,
+}
+
+impl Modal for MainlineModal {
+    fn open(&mut self) {
+        self.is_open = true;
+    }
+
+    fn close(&mut self) {
+        self.is_open = false;
+    }
+}
+
+impl MainlineModal {
+    pub fn new() -> Self {
+        Self {
+            is_open: false,
+            kind: SequenceKind::CherryPick,
+            oids: vec![],
+            parent_summaries: vec![],
+            mainline: 1,
+        }
+    }
+
+    pub fn open_with(&mut self, kind: SequenceKind, oids: Vec<Oid>, parent_summaries: Vec<String>) {
+        self.kind = kind;
+        self.oids = oids;
+        self.parent_summaries = parent_summaries;
+        self.mainline = 1;
+        self.open();
+    }
+
+    pub fn show(&mut self, ui: &mut Ui) -> Option<(SequenceKind, Vec<Oid>, u32)> {
+        if self.is_open {
+            return self.show_in_modal(String::from("mainline-modal"), ui, |inner_self, ui| -> Option<(SequenceKind, Vec<Oid>, u32)> {
+                ui.label(format!("This includes a merge commit. Which parent should the {} be relative to?", inner_self.kind.get_label()));
+                for (i, summary) in inner_self.parent_summaries.iter().enumerate() {
+                    ui.radio_value(&mut inner_self.mainline, i as u32 + 1, format!("Parent {}: {}", i + 1, summary));
+                }
+                if ui.button(inner_self.kind.get_title()).clicked() {
+                    inner_self.close();
+                    return Some((inner_self.kind, inner_self.oids.clone(), inner_self.mainline));
+                }
+                None
+            });
+        }
+        None
+    }
+}
+
+pub enum ResetModalAction {
+    Reset(Oid, ResetMode),
+    // The changes a hard reset would throw away need to be (re)loaded.
+    LoadLostChanges,
+}
+
+pub struct ResetModal {
+    is_open: bool,
+    head_name: String,
+    oid: Oid,
+    summary: String,
+    reset_mode: ResetMode,
+    // A hard reset is only done after it's confirmed with the changes it throws away listed.
+    is_confirming_hard: bool,
+    // The changes that a hard reset would throw away, or None if they haven't been loaded since hard was picked.
+    lost_changes_opt: Option<Vec<FileChange>>,
+}
+
+impl Modal for ResetModal {
+    fn open(&mut self) {
+        self.is_open = true;
+    }
+
+    fn close(&mut self) {
+        self.is_open = false;
+    }
+}
+
+impl ResetModal {
+    pub fn new() -> Self {
+        Self {
+            is_open: false,
+            head_name: String::new(),
+            oid: Oid::zero(),
+            summary: String::new(),
+            reset_mode: ResetMode::Mixed,
+            is_confirming_hard: false,
+            lost_changes_opt: None,
+        }
+    }
+
+    pub fn open_with(&mut self, repo: &Repository, oid: Oid) -> Result<()> {
+        self.head_name = match repo.head() {
+            Ok(head_ref) => String::from(head_ref.shorthand().unwrap_or("HEAD")),
+            Err(_) => String::from("HEAD"),
+        };
+        self.oid = oid;
+        self.summary = String::from(repo.find_commit(oid)?.summary().unwrap_or_default());
+        self.reset_mode = ResetMode::Mixed;
+        self.is_confirming_hard = false;
+        self.lost_changes_opt = None;
+        self.open();
+        Ok(())
+    }
+
+    pub fn load_lost_changes(&mut self, repo: &Repository) -> Result<()> {
+        // Untracked files are left alone by a hard reset, so they aren't listed.
+        let [unstaged_changes, _, staged_changes] = match git_utils::get_status_changes(repo) {
+            Ok(changes) => changes,
+            Err(e) => {
+                // Otherwise this would be tried (and fail) again every frame.
+                self.close();
+                return Err(e);
+            },
+        };
+        let mut lost_changes = unstaged_changes;
+        for file_change in staged_changes {
+            if !lost_changes.iter().any(|c| c.path == file_change.path) {
+                lost_changes.push(file_change);
+            }
+        }
+        self.lost_changes_opt = Some(lost_changes);
+        Ok(())
+    }
+
+    pub fn show(&mut self, ui: &mut Ui) -> Option<ResetModalAction> {
+        if self.is_open {
+            return self.show_in_modal(String::from("reset-modal"), ui, |inner_self, ui| -> Option<ResetModalAction> {
+                ui.label(format!("Reset {} to {:.7} ({})", inner_self.head_name, inner_self.oid.to_string(), inner_self.summary));
+                if inner_self.is_confirming_hard {
+                    ui.colored_label(Color32::RED, "Are you sure? A hard reset can't be undone.");
+                } else {
+                    ui.radio_value(&mut inner_self.reset_mode, ResetMode::Soft, "Soft (keep all changes staged)");
+                    ui.radio_value(&mut inner_self.reset_mode, ResetMode::Mixed, "Mixed (keep all changes, but unstage them)");
+                    if ui.radio_value(&mut inner_self.reset_mode, ResetMode::Hard, "Hard (discard all changes)").clicked() {
+                        inner_self.lost_changes_opt = None;
+                    }
+                }
+                if inner_self.reset_mode == ResetMode::Hard {
+                    match &inner_self.lost_changes_opt {
+                        None => {
+                            ui.label("Checking for uncommitted changes...");
+                            return Some(ResetModalAction::LoadLostChanges);
+                        },
+                        Some(lost_changes) if !lost_changes.is_empty() => {
+                            ui.colored_label(Color32::RED, "These uncommitted changes will be lost:");
+                            ScrollArea::vertical().id_source("reset-modal-scroll-area").max_height(200.0).show(ui, |ui| {
+                                for file_change in lost_changes {
+                                    ui.label(format!("{} {}", git_utils::get_delta_label(file_change.status), file_change.get_display_path()));
+                                }
+                            });
+                        },
+                        Some(_) => (),
+                    };
+                }
+                if inner_self.is_confirming_hard {
+                    ui.horizontal(|ui| {
+                        if ui.button("Back").clicked() {
+                            inner_self.is_confirming_hard = false;
+                        }
+                        if ui.add(Button::new("Reset And Discard Changes").fill(Color32::DARK_RED)).clicked() {
+                            inner_self.close();
+                            return Some(ResetModalAction::Reset(inner_self.oid, inner_self.reset_mode));
+                        }
+                        None
+                    }).inner
+                } else {
+                    if ui.button("Reset").clicked() {
+                        if inner_self.reset_mode != ResetMode::Hard {
+                            inner_self.close();
+                            return Some(ResetModalAction::Reset(inner_self.oid, inner_self.reset_mode));
+                        }
+                        // The changes are loaded again so the confirmation lists what's there right now.
+                        inner_self.is_confirming_hard = true;
+                        inner_self.lost_changes_opt = None;
+                    }
+                    None
+                }
+            });
+        }
+        None
+    }
+}
+
+pub struct StashModal {
+    is_open: bool,
+    message: String,
+    include_untracked: bool,
+    keep_index: bool,
+}
+
+impl Modal for StashModal {
+    fn open(&mut self) {
+        self.is_open = true;
+    }
+
+    fn close(&mut self) {
+        self.is_open = false;
+    }
+}
+
+impl StashModal {
+    pub fn new() -> Self {
+        Self {
+            is_open: false,
+            message: String::new(),
+            include_untracked: false,
+            keep_index: false,
+        }
+    }
+
+    pub fn open_stash(&mut self) {
+        self.message = String::new();
+        self.open();
+    }
+
+    pub fn show(&mut self, ui: &mut Ui) -> Option<(String, bool, bool)> {
+        if self.is_open {
+            return self.show_in_modal(String::from("stash-modal"), ui, |inner_self, ui| -> Option<(String, bool, bool)> {
+                Grid::new("stash-modal-grid").num_columns(2).show(ui, |ui| {
+                    ui.label("Message (optional):");
+                    ui.text_edit_singleline(&mut inner_self.message);
+                    ui.end_row();
+
+                    ui.label("");
+                    ui.checkbox(&mut inner_self.include_untracked, "Include Untracked Files");
+                    ui.end_row();
+
+                    ui.label("");
+                    ui.checkbox(&mut inner_self.keep_index, "Keep Staged Changes");
+                    ui.end_row();
+                });
+                if ui.button("Stash").clicked() {
+                    inner_self.close();
+                    return Some((inner_self.message.clone(), inner_self.include_untracked, inner_self.keep_index));
+                }
+                None
+            });
+        }
+        None
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum TagModalKind {
+    Create,
+    Push,
+    DeleteRemote,
+}
+
+pub enum TagModalResult {
+    // The tag message and tagger are only set for annotated tags.
+    Create(Oid, String, Option<String>, Option<(String, String)>),
+    // Pushes every tag if there's no ref name.
+    Push(String, Option<String>),
+    DeleteRemote(String, String),
+}
+
+pub struct TagModal {
+    is_open: bool,
+    kind: TagModalKind,
+    oid: Oid,
+    ref_name_opt: Option<String>,
+    tag_name: String,
+    is_annotated: bool,
+    message: String,
+    tagger_name: String,
+    tagger_email: String,
+    remote_names: Vec<String>,
+    remote_name: String,
+}
+
+impl Modal for TagModal {
+    fn open(&mut self) {
+        self.is_open = true;
+    }
+
+    fn close(&mut self) {
+        self.is_open = false;
+    }
+}
+
+impl TagModal {
+    pub fn new() -> Self {
+        Self {
+            is_open: false,
+            kind: TagModalKind::Create,
+            oid: Oid::zero(),
+            ref_name_opt: None,
+            tag_name: String::new(),
+            is_annotated: false,
+            message: String::new(),
+            tagger_name: String::new(),
+            tagger_email: String::new(),
+            remote_names: vec![],
+            remote_name: String::new(),
+        }
+    }
+
+    pub fn open_create(&mut self, oid: Oid) {
+        self.kind = TagModalKind::Create;
+        self.oid = oid;
+        self.tag_name = String::new();
+        self.message = String::new();
+        self.open();
+    }
+
+    fn open_with_remotes(&mut self, repo: &Repository, kind: TagModalKind, ref_name_opt: Option<&str>) -> Result<()> {
+        self.remote_names = git_utils::get_remote_names(repo)?;
+        if self.remote_names.is_empty() {
+            bail!("There are no remotes set up for this repository.");
+        }
+        self.remote_name = match git_utils::get_default_remote_name(repo) {
+            Ok(remote_name) => remote_name,
+            Err(_) => self.remote_names[0].clone(),
+        };
+        self.kind = kind;
+        self.ref_name_opt = ref_name_opt.map(String::from);
+        self.open();
+        Ok(())
+    }
+
+    pub fn open_push(&mut self, repo: &Repository, ref_name_opt: Option<&str>) -> Result<()> {
+        self.open_with_remotes(repo, TagModalKind::Push, ref_name_opt)
+    }
+
+    pub fn open_delete_remote(&mut self, repo: &Repository, ref_name: &str) -> Result<()> {
+        self.open_with_remotes(repo, TagModalKind::DeleteRemote, Some(ref_name))
+    }
+
+    fn get_result(&self) -> TagModalResult {
+        match self.kind {
+            TagModalKind::Create => {
+                let (message_opt, tagger_opt) = if self.is_annotated {
+                    let tagger_opt = if self.tagger_name.trim().is_empty() || self.tagger_email.trim().is_empty() {
+                        None
+                    } else {
+                        Some((String::from(self.tagger_name.trim()), String::from(self.tagger_email.trim())))
+                    };
+                    (Some(self.message.clone()), tagger_opt)
+                } else {
+                    (None, None)
+                };
+                TagModalResult::Create(self.oid, String::from(self.tag_name.trim()), message_opt, tagger_opt)
+            },
+            TagModalKind::Push => TagModalResult::Push(self.remote_name.clone(), self.ref_name_opt.clone()),
+            TagModalKind::DeleteRemote => TagModalResult::DeleteRemote(self.remote_name.clone(), self.ref_name_opt.clone().unwrap_or_default()),
+        }
+    }
+
+    fn show_create(&mut self, ui: &mut Ui) {
+        Grid::new("tag-modal-grid").num_columns(2).show(ui, |ui| {
+            ui.label("Tag Name:");
+            ui.text_edit_singleline(&mut self.tag_name);
+            ui.end_row();
+
+            ui.label("");
+            ui.checkbox(&mut self.is_annotated, "Annotated");
+            ui.end_row();
+
+            if self.is_annotated {
+                ui.label("Message:");
+                ui.text_edit_multiline(&mut self.message);
+                ui.end_row();
+
+                ui.label("Tagger Name (optional):");
+                ui.text_edit_singleline(&mut self.tagger_name);
+                ui.end_row();
+
+                ui.label("Tagger Email (optional):");
+                ui.text_edit_singleline(&mut self.tagger_email);
+                ui.end_row();
+            }
+        });
+    }
+
+    pub fn show(&mut self, ui: &mut Ui) -> Option<TagModalResult> {
+        if self.is_open {
+            return self.show_in_modal(String::from("tag-modal"), ui, |inner_self, ui| -> Option<TagModalResult> {
+                let tag_text = match &inner_self.ref_name_opt {
+                    Some(ref_name) => String::from(ref_name.strip_prefix("refs/tags/").unwrap_or(ref_name)),
+                    None => String::from("all tags"),
+                };
+                let btn_text = match inner_self.kind {
+                    TagModalKind::Create => {
+                        ui.label(format!("Create a tag on commit {}:", inner_self.oid));
+                        inner_self.show_create(ui);
+                        "Create"
+                    },
+                    TagModalKind::Push => {
+                        ui.label(format!("Push {} to:", tag_text));
+                        "Push"
+                    },
+                    TagModalKind::DeleteRemote => {
+                        ui.label(format!("Delete {} on:", tag_text));
+                        "Delete"
+                    },
+                };
+                if inner_self.kind != TagModalKind::Create {
+                    for remote_name in &inner_self.remote_names {
+                        ui.radio_value(&mut inner_self.remote_name, remote_name.clone(), remote_name);
+                    }
+                }
+                let is_input_valid = match inner_self.kind {
+                    TagModalKind::Create => !inner_self.tag_name.trim().is_empty() && (!inner_self.is_annotated || !inner_self.message.trim().is_empty()),
+                    _ => true,
+                };
+                if ui.add_enabled(is_input_valid, Button::new(btn_text)).clicked() {
+                    inner_self.close();
+                    return Some(inner_self.get_result());
+                }
+                None
+            });
+        }
+        None
+    }
+}
+
+pub enum RemotesModalAction {
+    Add(String, String),
+    Rename(String, String),
+    Remove(String),
+    // An empty push URL means pushes use the regular URL.
+    SetUrls(String, String, String),
+    Fetch(String),
+    Prune(String),
+}
+
+struct RemoteEditor {
+    info: RemoteInfo,
+    new_name: String,
+    url: String,
+    push_url: String,
+    is_confirming_remove: bool,
+}
+
+impl RemoteEditor {
+    fn new(info: RemoteInfo) -> Self {
+        Self {
+            new_name: info.name.clone(),
+            url: info.url.clone(),
+            push_url: info.push_url_opt.clone().unwrap_or_default(),
+            info,
+            is_confirming_remove: false,
+        }
+    }
+
+    fn show(&mut self, ui: &mut Ui) -> Option<RemotesModalAction> {
+        let mut action_opt = None;
+        let remote_name = self.info.name.clone();
+        Grid::new(format!("remote-editor-grid-{}", remote_name)).num_columns(3).show(ui, |ui| {
+            ui.label("Name:");
+            ui.text_edit_singleline(&mut self.new_name);
+            let new_name = self.new_name.trim();
+            if ui.add_enabled(!new_name.is_empty() && new_name != remote_name, Button::new("Rename")).clicked() {
+                action_opt = Some(RemotesModalAction::Rename(remote_name.clone(), String::from(new_name)));
+            }
+            ui.end_row();
+
+            ui.label("URL:");
+            ui.text_edit_singleline(&mut self.url);
+            let is_url_changed = self.url.trim() != self.info.url;
+            let is_push_url_changed = self.push_url.trim() != self.info.push_url_opt.as_deref().unwrap_or_default();
+            if ui.add_enabled(is_url_changed || is_push_url_changed, Button::new("Save URLs")).clicked() {
+                action_opt = Some(RemotesModalAction::SetUrls(remote_name.clone(), String::from(self.url.trim()), String::from(self.push_url.trim())));
+            }
+            ui.end_row();
+
+            ui.label("Push URL (optional):");
+            ui.text_edit_singleline(&mut self.push_url);
+            ui.end_row();
+
+            ui.label("Fetch Refspecs:");
+            ui.label(self.info.fetch_refspecs.join("\n"));
+            ui.end_row();
+
+            ui.label("Push Refspecs:");
+            ui.label(if self.info.push_refspecs.is_empty() { String::from("(Default)") } else { self.info.push_refspecs.join("\n") });
+            ui.end_row();
+        });
+        if self.is_confirming_remove {
+            ui.colored_label(Color32::RED, format!("Are you sure you want to remove {}? Its remote-tracking branches will be deleted too.", remote_name));
+        }
+        ui.horizontal(|ui| {
+            if self.is_confirming_remove {
+                if ui.button("Cancel").clicked() {
+                    self.is_confirming_remove = false;
+                }
+                if ui.add(Button::new("Remove").fill(Color32::DARK_RED)).clicked() {
+                    self.is_confirming_remove = false;
+                    action_opt = Some(RemotesModalAction::Remove(remote_name.clone()));
+                }
+                return;
+            }
+            if ui.button("Fetch").clicked() {
+                action_opt = Some(RemotesModalAction::Fetch(remote_name.clone()));
+            }
+            if ui.button("Prune").clicked() {
+                action_opt = Some(RemotesModalAction::Prune(remote_name.clone()));
+            }
+            if ui.add(Button::new("Remove...").fill(Color32::DARK_RED)).clicked() {
+                self.is_confirming_remove = true;
+            }
+        });
+        action_opt
+    }
+}
+
+pub struct RemotesModal {
+    is_open: bool,
+    remote_editors: Vec<RemoteEditor>,
+    new_remote_name: String,
+    new_remote_url: String,
+}
+
+impl Modal for RemotesModal {
+    fn open(&mut self) {
+        self.is_open = true;
+    }
+
+    fn close(&mut self) {
+        self.is_open = false;
+    }
+}
+
+impl RemotesModal {
+    pub fn new() -> Self {
+        Self {
+            is_open: false,
+            remote_editors: vec![],
+            new_remote_name: String::new(),
+            new_remote_url: String::new(),
+        }
+    }
+
+    pub fn open_with(&mut self, repo: &Repository) -> Result<()> {
+        self.refresh(repo)?;
+        self.open();
+        Ok(())
+    }
+
+    pub fn refresh(&mut self, repo: &Repository) -> Result<()> {
+        self.remote_editors = git_utils::get_remote_infos(repo)?.into_iter().map(RemoteEditor::new).collect();
+        Ok(())
+    }
+
+    pub fn show(&mut self, ui: &mut Ui) -> Option<RemotesModalAction> {
+        if self.is_open {
+            return self.show_in_modal(String::from("remotes-modal"), ui, |inner_self, ui| -> Option<RemotesModalAction> {
+                let mut action_opt = None;
+                ScrollArea::vertical().id_source("remotes-modal-scroll-area").auto_shrink([false, true]).show(ui, |ui| {
+                    if inner_self.remote_editors.is_empty() {
+                        ui.label("This repository doesn't have any remotes yet.");
+                    }
+                    for remote_editor in &mut inner_self.remote_editors {
+                        ui.heading(&remote_editor.info.name);
+                        if let Some(action) = remote_editor.show(ui) {
+                            action_opt = Some(action);
+                        }
+                        ui.separator();
+                    }
+
+                    ui.heading("Add Remote");
+                    Grid::new("add-remote-grid").num_columns(2).show(ui, |ui| {
+                        ui.label("Name:");
+                        ui.text_edit_singleline(&mut inner_self.new_remote_name);
+                        ui.end_row();
+
+                        ui.label("URL:");
+                        ui.text_edit_singleline(&mut inner_self.new_remote_url);
+                        ui.end_row();
+                    });
+                    let is_input_valid = !inner_self.new_remote_name.trim().is_empty() && !inner_self.new_remote_url.trim().is_empty();
+                    if ui.add_enabled(is_input_valid, Button::new("Add")).clicked() {
+                        action_opt = Some(RemotesModalAction::Add(String::from(inner_self.new_remote_name.trim()), String::from(inner_self.new_remote_url.trim())));
+                        inner_self.new_remote_name = String::new();
+                        inner_self.new_remote_url = String::new();
+                    }
+                });
+                action_opt
+            });
+        }
+        None
+    }
+}
+
+// Asks the user for whatever a background git operation needs, like a password.
+pub struct PromptModal {
+    prompt_opt: Option<Prompt>,
+    username: String,
+    secret: String,
+}
+
+impl Modal for PromptModal {
+    fn open(&mut self) {}
+
+    fn close(&mut self) {
+        // Closing it without answering cancels whatever was asking.
+        if let Some(prompt) = self.prompt_opt.take() {
+            prompt.answer(None);
+        }
+    }
+}
+
+impl PromptModal {
+    pub fn new() -> Self {
+        Self {
+            prompt_opt: None,
+            username: String::new(),
+            secret: String::new(),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.prompt_opt.is_some()
+    }
+
+    pub fn open_with(&mut self, prompt: Prompt) {
+        self.username = match &prompt.request {
+            PromptRequest::UserPass { username_opt: Some(username), .. } => username.clone(),
+            _ => String::new(),
+        };
+        self.secret = String::new();
+        self.prompt_opt = Some(prompt);
+    }
+
+    fn show_credentials(&mut self, ui: &mut Ui, is_user_pass: bool) -> Option<Option<PromptAnswer>> {
+        Grid::new("prompt-modal-grid").num_columns(2).show(ui, |ui| {
+            if is_user_pass {
+                ui.label("Username:");
+                ui.text_edit_singleline(&mut self.username);
+                ui.end_row();
+            }
+            ui.label(if is_user_pass { "Password:" } else { "Passphrase:" });
+            ui.add(TextEdit::singleline(&mut self.secret).password(true));
+            ui.end_row();
+        });
+        if ui.button("OK").clicked() {
+            return Some(Some(PromptAnswer::Credentials {
+                username: self.username.clone(),
+                secret: self.secret.clone(),
+            }));
+        }
+        None
+    }
+
+    pub fn show(&mut self, ui: &mut Ui) {
+        if self.is_open() {
+            self.show_in_modal(String::from("prompt-modal"), ui, |inner_self, ui| {
+                // None means it hasn't been answered yet, Some(None) means it was rejected.
+                let answer_opt_opt = match &inner_self.prompt_opt {
+                    Some(Prompt { request: PromptRequest::SshPassphrase(key_path), .. }) => {
+                        ui.label(format!("Enter the passphrase for {}:", key_path));
+                        inner_self.show_credentials(ui, false)
+                    },
+                    Some(Prompt { request: PromptRequest::UserPass { url, .. }, .. }) => {
+                        ui.label(format!("Enter your username and password for {}:", url));
+                        inner_self.show_credentials(ui, true)
+                    },
+                    Some(Prompt { request: PromptRequest::UnknownHostKey { host, key_type, fingerprint }, .. }) => {
+                        ui.label(format!("The authenticity of host {} can't be established.", host));
+                        ui.label(format!("{} key fingerprint is {}", key_type, fingerprint));
+                        ui.label("Accepting it always will add it to ~/.ssh/known_hosts.");
+                        ui.horizontal(|ui| {
+                            if ui.button("Accept Once").clicked() {
+                                return Some(Some(PromptAnswer::AcceptOnce));
+                            }
+                            if ui.button("Accept Always").clicked() {
+                                return Some(Some(PromptAnswer::AcceptAlways));
+                            }
+                            if ui.button("Reject").clicked() {
+                                return Some(None);
+                            }
+                            None
+                        }).inner
+                    },
+                    Some(Prompt { request: PromptRequest::UntrustedCertificate { host, fingerprint }, .. }) => {
+                        ui.label(format!("The TLS certificate for {} couldn't be verified.", host));
+                        ui.label(format!("SHA1 fingerprint is {}", fingerprint));
+                        ui.label("Only accept it if you trust this certificate, e.g. it's from a self-hosted server.");
+                        ui.horizontal(|ui| {
+                            if ui.button("Accept").clicked() {
+                                return Some(Some(PromptAnswer::AcceptOnce));
+                            }
+                            if ui.button("Reject").clicked() {
+                                return Some(None);
+                            }
+                            None
+                        }).inner
+                    },
+                    None => return,
+                };
+                if let Some(answer_opt) = answer_opt_opt {
+                    if let Some(prompt) = inner_self.prompt_opt.take() {
+                        prompt.answer(answer_opt);
+                    }
+                    inner_self.secret = String::new();
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use anyhow::{Error, Result};
+use egui::{Button, ComboBox, CursorIcon, Label, Rect, ScrollArea, Sense, SidePanel, Stroke, TextEdit, Ui};
+use git2::{Oid, Repository};
+use crate::backend::git_utils::{self, RebaseAction, RebaseTodoItem};
+
+const DROP_INDICATOR_STROKE_WIDTH: f32 = 2.0;
+const PREVIEW_PANEL_DEFAULT_WIDTH: f32 = 300.0;
+
+pub enum RebaseEditorAction {
+    Start(Oid, Vec<RebaseTodoItem>),
+    Cancel,
+}
+
+pub struct RebaseEditor {
+    onto_oid: Oid,
+    todo: Vec<RebaseTodoItem>,
+    dragged_index_opt: Option<usize>,
+}
+
+impl RebaseEditor {
+    pub fn new(repo: &Repository, base_oid: Oid) -> Result<Self> {
+        let todo = git_utils::get_rebase_todo(repo, base_oid)?;
+        let onto_oid = repo.find_commit(base_oid)?.parent_id(0).map_err(|_| Error::msg("The selected commit has no parent to rebase onto!"))?;
+        Ok(Self {
+            onto_oid,
+            todo,
+            dragged_index_opt: None,
+        })
+    }
+
+    // Gets the summaries of the commits the rebase will end up with, oldest first.
+    fn get_preview(&self) -> Vec<String> {
+        let mut preview: Vec<String> = vec![];
+        for item in &self.todo {
+            match item.action {
+                RebaseAction::Drop => (),
+                RebaseAction::Squash | RebaseAction::Fixup if !preview.is_empty() => {
+                    if let Some(last) = preview.last_mut() {
+                        last.push_str(&format!(" (+ {})", item.summary));
+                    }
+                },
+                RebaseAction::Reword => preview.push(String::from(item.message.lines().next().unwrap_or_default())),
+                _ => preview.push(item.summary.clone()),
+            };
+        }
+        preview
+    }
+
+    fn show_todo_item(ui: &mut Ui, item: &mut RebaseTodoItem, i: usize) -> bool {
+        let mut is_drag_started = false;
+        ui.horizontal(|ui| {
+            let handle_resp = ui.add(Label::new("☰").sense(Sense::drag())).on_hover_cursor(CursorIcon::Grab);
+            is_drag_started = handle_resp.drag_started();
+            ComboBox::from_id_source(format!("rebase-action-{}", i)).selected_text(item.action.get_label()).show_ui(ui, |ui| {
+                for action in RebaseAction::ALL {
+                    ui.selectable_value(&mut item.action, action, action.get_label());
+                }
+            });
+            ui.label(format!("{:.7}", item.oid.to_string()));
+            ui.label(&item.summary);
+        });
+        if item.action == RebaseAction::Reword {
+            ui.add(TextEdit::multiline(&mut item.message).desired_rows(2).desired_width(f32::INFINITY));
+        }
+        is_drag_started
+    }
+
+    fn show_todo(&mut self, ui: &mut Ui) {
+        let mut row_rects: Vec<Rect> = vec![];
+        for (i, item) in self.todo.iter_mut().enumerate() {
+            let resp = ui.vertical(|ui| RebaseEditor::show_todo_item(ui, item, i));
+            if resp.inner {
+                self.dragged_index_opt = Some(i);
+            }
+            row_rects.push(resp.response.rect);
+        }
+
+        let from_index = match self.dragged_index_opt {
+            Some(i) => i,
+            None => return,
+        };
+        ui.ctx().set_cursor_icon(CursorIcon::Grabbing);
+        let pointer_pos_opt = ui.input(|i| i.pointer.interact_pos());
+        // This is the index the dragged item would be inserted before.
+        let to_index = match pointer_pos_opt {
+            Some(pos) => row_rects.iter().position(|rect| pos.y < rect.center().y).unwrap_or(row_rects.len()),
+            None => from_index,
+        };
+        let indicator_y = match row_rects.get(to_index) {
+            Some(rect) => rect.top(),
+            None => row_rects.last().map(|rect| rect.bottom()).unwrap_or_default(),
+        };
+        ui.painter().hline(ui.max_rect().x_range(), indicator_y, Stroke::new(DROP_INDICATOR_STROKE_WIDTH, ui.visuals().selection.stroke.color));
+
+        if ui.input(|i| i.pointer.any_released()) {
+            let item = self.todo.remove(from_index);
+            let insert_index = if to_index > from_index { to_index - 1 } else { to_index };
+            self.todo.insert(insert_index, item);
+            self.dragged_index_opt = None;
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut Ui) -> Option<RebaseEditorAction> {
+        let mut action_opt = None;
+        ui.vertical(|ui| {
+            ui.horizontal(|ui| {
+                ui.heading("Interactive Rebase");
+                let is_reword_valid = self.todo.iter().all(|item| item.action != RebaseAction::Reword || !item.message.trim().is_empty());
+                if ui.add_enabled(is_reword_valid, Button::new("Start Rebase")).clicked() {
+                    action_opt = Some(RebaseEditorAction::Start(self.onto_oid, self.todo.clone()));
+                }
+                if ui.button("Cancel").clicked() {
+                    action_opt = Some(RebaseEditorAction::Cancel);
+                }
+            });
+            ui.label("Drag commits by their handle to reorder them. They're applied from top to bottom.");
+
+            SidePanel::right("rebase-preview-panel").resizable(true).default_width(PREVIEW_PANEL_DEFAULT_WIDTH).show_inside(ui, |ui| {
+                ui.heading("Resulting History");
+                ScrollArea::both().id_source("rebase-preview-scroll-area").auto_shrink([false, false]).show(ui, |ui| {
+                    // Show the newest commit at the top, the same as the graph.
+                    for summary in self.get_preview().iter().rev() {
+                        ui.label(summary);
+                    }
+                });
+            });
+            ScrollArea::both().id_source("rebase-todo-scroll-area").auto_shrink([false, false]).show(ui, |ui| {
+                self.show_todo(ui);
+            });
+        });
+        action_opt
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::Result;
+use egui::{Color32, Label, RichText, ScrollArea, Sense, Ui};
+use git2::{Delta, Repository};
+use crate::backend::git_functions::{git_stage, git_stage_all, git_unstage, git_unstage_all};
+use crate::backend::git_utils::{self, FileChange, FileDiff};
+use crate::frontend::diff_view::{DiffView, DiffViewKind};
+
+const CONFLICTED_COLOR: Color32 = Color32::from_rgb(255, 100, 100);
+
+enum StatusAction {
+    Refresh,
+    ShowDiff(String, DiffViewKind),
+    Stage(Vec<String>),
+    StageAll,
+    Unstage(Vec<String>),
+    UnstageAll,
+}
+
+pub struct StatusPanel {
+    is_bare: bool,
+    unstaged_changes: Vec<FileChange>,
+    untracked_changes: Vec<FileChange>,
+    staged_changes: Vec<FileChange>,
+    diff_view_opt: Option<DiffView>,
+}
+
+impl StatusPanel {
+    pub fn new(repo: &Repository) -> Result<Self> {
+        let mut status_panel = Self {
+            is_bare: repo.is_bare(),
+            unstaged_changes: vec![],
+            untracked_changes: vec![],
+            staged_changes: vec![],
+            diff_view_opt: None,
+        };
+        status_panel.refresh(repo)?;
+        Ok(status_panel)
+    }
+
+    pub fn refresh(&mut self, repo: &Repository) -> Result<()> {
+        // Bare repos don't have a working directory or index to show.
+        if self.is_bare {
+            return Ok(());
+        }
+        let [unstaged_changes, untracked_changes, staged_changes] = git_utils::get_status_changes(repo)?;
+        self.unstaged_changes = unstaged_changes;
+        self.untracked_changes = untracked_changes;
+        self.staged_changes = staged_changes;
+
+        // Keep the open diff in sync, closing it if there's nothing left to show for the file.
+        if let Some(diff_view) = &mut self.diff_view_opt {
+            match StatusPanel::get_file_diff(repo, diff_view.get_path(), diff_view.get_kind())? {
+                Some(file_diff) => diff_view.set_file_diff(file_diff),
+                None => self.diff_view_opt = None,
+            };
+        }
+        Ok(())
+    }
+
+    fn get_file_diff(repo: &Repository, path: &str, kind: DiffViewKind) -> Result<Option<FileDiff>> {
+        let diff = match kind {
+            DiffViewKind::Staged => git_utils::get_staged_changes(repo)?,
+            _ => git_utils::get_unstaged_changes(repo)?,
+        };
+        git_utils::get_file_diff(&diff, path)
+    }
+
+    pub fn has_diff_view(&self) -> bool {
+        self.diff_view_opt.is_some()
+    }
+
+    pub fn show_diff_view(&mut self, ui: &mut Ui, repo: &Repository) -> Result<()> {
+        let mut is_changed = false;
+        ui.vertical(|ui| -> Result<()> {
+            if ui.button("Close Diff").clicked() {
+                self.diff_view_opt = None;
+                return Ok(());
+            }
+            if let Some(diff_view) = &mut self.diff_view_opt {
+                is_changed = diff_view.show(ui, repo)?;
+            }
+            Ok(())
+        }).inner?;
+        if is_changed {
+            self.refresh(repo)?;
+        }
+        Ok(())
+    }
+
+    fn show_file_changes(ui: &mut Ui, file_changes: &[FileChange], kind: DiffViewKind, action_opt: &mut Option<StatusAction>) {
+        let (btn_text, make_action): (&str, fn(Vec<String>) -> StatusAction) = match kind {
+            DiffViewKind::Staged => ("Unstage", StatusAction::Unstage),
+            _ => ("Stage", StatusAction::Stage),
+        };
+        for file_change in file_changes {
+            ui.horizontal(|ui| {
+                if ui.small_button(btn_text).clicked() {
+                    *action_opt = Some(make_action(file_change.get_all_paths()));
+                }
+                let mut label_text = RichText::new(git_utils::get_delta_label(file_change.status));
+                if file_change.status == Delta::Conflicted {
+                    label_text = label_text.color(CONFLICTED_COLOR);
+                }
+                ui.label(label_text.strong());
+                if ui.add(Label::new(file_change.get_display_path()).wrap(false).sense(Sense::click())).clicked() {
+                    *action_opt = Some(StatusAction::ShowDiff(file_change.path.clone(), kind));
+                }
+            });
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, repo: &Repository) -> Result<()> {
+        if self.is_bare {
+            ui.label("This is a bare repository, so there is no working directory.");
+            return Ok(());
+        }
+
+        let mut action_opt = None;
+        ui.horizontal(|ui| {
+            if ui.button("Refresh").clicked() {
+                action_opt = Some(StatusAction::Refresh);
+            }
+            if ui.button("Stage All").clicked() {
+                action_opt = Some(StatusAction::StageAll);
+            }
+            if ui.button("Unstage All").clicked() {
+                action_opt = Some(StatusAction::UnstageAll);
+            }
+        });
+        ScrollArea::both().id_source("status-panel-scroll-area").auto_shrink([false, false]).show(ui, |ui| {
+            ui.heading(format!("Unstaged ({})", self.unstaged_changes.len()));
+            StatusPanel::show_file_changes(ui, &self.unstaged_changes, DiffViewKind::Unstaged, &mut action_opt);
+            ui.separator();
+            ui.heading(format!("Untracked ({})", self.untracked_changes.len()));
+            StatusPanel::show_file_changes(ui, &self.untracked_changes, DiffViewKind::Unstaged, &mut action_opt);
+            ui.separator();
+            ui.heading(format!("Staged ({})", self.staged_changes.len()));
+            StatusPanel::show_file_changes(ui, &self.staged_changes, DiffViewKind::Staged, &mut action_opt);
+        });
+
+        if let Some(action) = action_opt {
+            match action {
+                StatusAction::Refresh => (),
+                StatusAction::ShowDiff(path, kind) => {
+                    self.diff_view_opt = StatusPanel::get_file_diff(repo, &path, kind)?.map(|file_diff| DiffView::new(kind, file_diff));
+                },
+                StatusAction::Stage(paths) => git_stage(repo, &paths)?,
+                StatusAction::StageAll => git_stage_all(repo)?,
+                StatusAction::Unstage(paths) => git_unstage(repo, &paths)?,
+                StatusAction::UnstageAll => git_unstage_all(repo)?,
+            };
+            self.refresh(repo)?;
+        }
+        Ok(())
+    }
+}
@@ -1,46 +1,208 @@
 use std::sync::{Arc, Mutex};
+use std::thread;
 use anyhow::Result;
-use egui::{Align, Context, CursorIcon, Layout, ScrollArea, Sense, Ui};
-use git2::Repository;
-use crate::backend::git_functions::{git_fetch, git_pull};
-use crate::frontend::branch_tree::{BranchTreeNode, get_branch_trees};
-use crate::frontend::commit_graph::CommitGraph;
-use crate::frontend::modals::ErrorModal;
+use egui::{Align, Context, CursorIcon, Layout, ScrollArea, Sense, SidePanel, TopBottomPanel, Ui};
+use git2::{Oid, Repository};
+use crate::backend::git_functions::{git_checkout_ref, git_checkout_remote_branch, git_cherry_pick, git_create_branch, git_create_tag, git_delete_branch, git_delete_remote_branch, git_delete_remote_tag, git_delete_tag, git_add_remote, git_fetch, git_fetch_remote, git_interactive_rebase, git_merge, git_pull, git_prune_remote, git_push, git_push_tags, git_remove_remote, git_rename_branch, git_rename_remote, git_reset, git_revert, git_set_remote_urls, git_set_upstream, git_stash_apply, git_stash_drop, git_stash_save, MergeKind, PullStrategy};
+use crate::backend::git_utils::{self, SequenceKind};
+use crate::backend::progress::{OperationProgress, OperationProgressList};
+use crate::frontend::branch_tree::{BranchTreeAction, BranchTreeNode, get_branch_trees, refresh_branch_trees};
+use crate::frontend::commit_box::CommitBox;
+use crate::frontend::commit_details::{CommitDetails, CommitDetailsAction};
+use crate::frontend::commit_graph::{CommitGraph, CommitGraphAction};
+use crate::frontend::conflict_view::ConflictView;
+use crate::frontend::modals::{BranchModal, BranchModalResult, ErrorModal, MainlineModal, MergeModal, RemotesModal, RemotesModalAction, ResetModal, ResetModalAction, StashModal, TagModal, TagModalResult};
+use crate::frontend::rebase_editor::{RebaseEditor, RebaseEditorAction};
+use crate::frontend::status_panel::StatusPanel;
 use crate::frontend::utils::perform_fn_in_thread;
 
+const STATUS_PANEL_DEFAULT_WIDTH: f32 = 300.0;
+const COMMIT_DETAILS_DEFAULT_HEIGHT: f32 = 300.0;
+
 pub struct OG2Tab {
     pub(crate) name: String,
     repo: Arc<Mutex<Repository>>,
-    is_loading: Arc<Mutex<bool>>,
+    progress_list: OperationProgressList,
     error_modal: Arc<Mutex<ErrorModal>>,
-    branch_trees: [BranchTreeNode; 3],
+    branch_trees: Arc<Mutex<[BranchTreeNode; 4]>>,
     branch_tree_col_width: f32,
     commit_graph: Arc<Mutex<CommitGraph>>,
+    status_panel: Arc<Mutex<StatusPanel>>,
+    conflict_view: Arc<Mutex<ConflictView>>,
+    commit_box: CommitBox,
+    commit_details_opt: Option<CommitDetails>,
+    rebase_editor_opt: Option<RebaseEditor>,
+    branch_modal: BranchModal,
+    merge_modal: MergeModal,
+    mainline_modal: MainlineModal,
+    reset_modal: ResetModal,
+    stash_modal: StashModal,
+    tag_modal: TagModal,
+    remotes_modal: Arc<Mutex<RemotesModal>>,
+    outcome_msg_opt: Arc<Mutex<Option<String>>>,
+    ctx: Context,
 }
 
 impl OG2Tab {
-    pub fn new(name: String, repo: Repository, is_loading: Arc<Mutex<bool>>, error_modal: Arc<Mutex<ErrorModal>>, ctx: &Context) -> Result<Self> {
+    pub fn new(name: String, repo: Repository, progress_list: OperationProgressList, error_modal: Arc<Mutex<ErrorModal>>, ctx: &Context) -> Result<Self> {
         let branch_trees = get_branch_trees(&repo, ctx)?;
         let commit_graph = CommitGraph::new(&repo)?;
+        let status_panel = StatusPanel::new(&repo)?;
+        let conflict_view = ConflictView::new(&repo)?;
+        let commit_box = CommitBox::new(&repo);
         Ok(Self {
             name,
             repo: Arc::new(Mutex::new(repo)),
-            is_loading,
+            progress_list,
             error_modal,
-            branch_trees,
+            branch_trees: Arc::new(Mutex::new(branch_trees)),
             branch_tree_col_width: 200.0,
             commit_graph: Arc::new(Mutex::new(commit_graph)),
+            status_panel: Arc::new(Mutex::new(status_panel)),
+            conflict_view: Arc::new(Mutex::new(conflict_view)),
+            commit_box,
+            commit_details_opt: None,
+            rebase_editor_opt: None,
+            branch_modal: BranchModal::new(),
+            merge_modal: MergeModal::new(),
+            mainline_modal: MainlineModal::new(),
+            reset_modal: ResetModal::new(),
+            stash_modal: StashModal::new(),
+            tag_modal: TagModal::new(),
+            remotes_modal: Arc::new(Mutex::new(RemotesModal::new())),
+            outcome_msg_opt: Arc::new(Mutex::new(None)),
+            ctx: ctx.clone(),
         })
     }
 
+    fn perform_fn_in_thread<F>(&self, some_fn: F)
+    where
+        F: FnOnce(&Repository) -> Result<()> + Send + 'static
+    {
+        self.perform_remote_fn_in_thread(move |repo, _| some_fn(repo));
+    }
+
+    // For operations that talk to a remote, which report their progress.
+    fn perform_remote_fn_in_thread<F>(&self, some_fn: F)
+    where
+        F: FnOnce(&Repository, &Arc<Mutex<OperationProgress>>) -> Result<()> + Send + 'static
+    {
+        let commit_graph_c = self.commit_graph.clone();
+        let branch_trees_c = self.branch_trees.clone();
+        let status_panel_c = self.status_panel.clone();
+        let conflict_view_c = self.conflict_view.clone();
+        let ctx_c = self.ctx.clone();
+        perform_fn_in_thread(some_fn, self.repo.clone(), self.error_modal.clone(), self.progress_list.clone(), move |repo| {
+            commit_graph_c.lock().unwrap().refresh_graph(repo)?;
+            refresh_branch_trees(&mut branch_trees_c.lock().unwrap(), repo, &ctx_c)?;
+            status_panel_c.lock().unwrap().refresh(repo)?;
+            conflict_view_c.lock().unwrap().refresh(repo)
+        });
+    }
+
+    fn pull(&self, strategy_opt: Option<PullStrategy>) {
+        let outcome_msg_opt_c = self.outcome_msg_opt.clone();
+        self.perform_remote_fn_in_thread(move |repo, progress| {
+            let outcome = git_pull(repo, strategy_opt, progress)?;
+            *outcome_msg_opt_c.lock().unwrap() = Some(format!("Pull: {}", outcome.get_message()));
+            Ok(())
+        });
+    }
+
+    fn merge(&self, ref_name: String, merge_kind: MergeKind) {
+        let outcome_msg_opt_c = self.outcome_msg_opt.clone();
+        self.perform_fn_in_thread(move |repo| {
+            let outcome = git_merge(repo, &ref_name, merge_kind)?;
+            *outcome_msg_opt_c.lock().unwrap() = Some(format!("Merge {}: {}", ref_name, outcome.get_message()));
+            Ok(())
+        });
+    }
+
+    fn handle_branch_tree_action(&mut self, action: BranchTreeAction) {
+        match action {
+            BranchTreeAction::Checkout(ref_name) => self.perform_fn_in_thread(move |repo| git_checkout_ref(repo, &ref_name)),
+            BranchTreeAction::CreateBranch(ref_name) => self.branch_modal.open_create(&ref_name),
+            BranchTreeAction::Rename(ref_name) => self.branch_modal.open_rename(&ref_name),
+            BranchTreeAction::Delete(ref_name, force) => self.perform_fn_in_thread(move |repo| git_delete_branch(repo, &ref_name, force)),
+            BranchTreeAction::SetUpstream(ref_name) => self.branch_modal.open_set_upstream(&ref_name),
+            BranchTreeAction::UnsetUpstream(ref_name) => self.perform_fn_in_thread(move |repo| git_set_upstream(repo, &ref_name, None)),
+            BranchTreeAction::CheckoutRemote(ref_name) => self.perform_fn_in_thread(move |repo| git_checkout_remote_branch(repo, &ref_name)),
+            BranchTreeAction::DeleteRemote(ref_name) => self.branch_modal.open_delete_remote(&ref_name),
+            BranchTreeAction::FetchRemote(remote_name) => self.perform_remote_fn_in_thread(move |repo, progress| git_fetch_remote(repo, &remote_name, progress)),
+            BranchTreeAction::PruneRemote(remote_name) => self.perform_remote_fn_in_thread(move |repo, progress| git_prune_remote(repo, &remote_name, progress)),
+            BranchTreeAction::Merge(ref_name) => self.merge_modal.open_merge(&ref_name),
+            BranchTreeAction::DeleteTag(ref_name) => self.perform_fn_in_thread(move |repo| git_delete_tag(repo, &ref_name)),
+            BranchTreeAction::DeleteRemoteTag(ref_name) => {
+                if let Ok(repo) = self.repo.try_lock() {
+                    let res = self.tag_modal.open_delete_remote(&repo, &ref_name);
+                    self.error_modal.lock().unwrap().handle_error(res);
+                }
+            },
+            BranchTreeAction::PushTags(ref_name_opt) => {
+                if let Ok(repo) = self.repo.try_lock() {
+                    let res = self.tag_modal.open_push(&repo, ref_name_opt.as_deref());
+                    self.error_modal.lock().unwrap().handle_error(res);
+                }
+            },
+            BranchTreeAction::SelectStash(oid) => self.select_commit(oid),
+            BranchTreeAction::ApplyStash(stash_index) => self.perform_fn_in_thread(move |repo| git_stash_apply(repo, stash_index, false)),
+            BranchTreeAction::PopStash(stash_index) => self.perform_fn_in_thread(move |repo| git_stash_apply(repo, stash_index, true)),
+            BranchTreeAction::DropStash(stash_index) => self.perform_fn_in_thread(move |repo| git_stash_drop(repo, stash_index)),
+        };
+    }
+
+    fn handle_branch_modal_result(&mut self, result: BranchModalResult) {
+        match result {
+            BranchModalResult::Create(start_ref_name, branch_name, checkout) => self.perform_fn_in_thread(move |repo| git_create_branch(repo, &branch_name, &start_ref_name, checkout)),
+            BranchModalResult::Rename(ref_name, new_branch_name) => self.perform_fn_in_thread(move |repo| git_rename_branch(repo, &ref_name, &new_branch_name)),
+            BranchModalResult::SetUpstream(ref_name, upstream) => self.perform_fn_in_thread(move |repo| git_set_upstream(repo, &ref_name, Some(&upstream))),
+            BranchModalResult::DeleteRemote(ref_name) => self.perform_remote_fn_in_thread(move |repo, progress| git_delete_remote_branch(repo, &ref_name, progress)),
+        };
+    }
+
+    fn handle_tag_modal_result(&mut self, result: TagModalResult) {
+        match result {
+            TagModalResult::Create(oid, tag_name, message_opt, tagger_opt) => self.perform_fn_in_thread(move |repo| {
+                let tagger_opt = tagger_opt.as_ref().map(|(name, email)| (name.as_str(), email.as_str()));
+                git_create_tag(repo, oid, &tag_name, message_opt.as_deref(), tagger_opt)
+            }),
+            TagModalResult::Push(remote_name, ref_name_opt) => self.perform_remote_fn_in_thread(move |repo, progress| git_push_tags(repo, &remote_name, ref_name_opt.as_deref(), progress)),
+            TagModalResult::DeleteRemote(remote_name, ref_name) => self.perform_remote_fn_in_thread(move |repo, progress| git_delete_remote_tag(repo, &remote_name, &ref_name, progress)),
+        };
+    }
+
+    fn handle_remotes_modal_action(&mut self, action: RemotesModalAction) {
+        let remotes_modal_c = self.remotes_modal.clone();
+        self.perform_remote_fn_in_thread(move |repo, progress| {
+            let res = match action {
+                RemotesModalAction::Add(remote_name, url) => git_add_remote(repo, &remote_name, &url),
+                RemotesModalAction::Rename(remote_name, new_remote_name) => git_rename_remote(repo, &remote_name, &new_remote_name),
+                RemotesModalAction::Remove(remote_name) => git_remove_remote(repo, &remote_name),
+                RemotesModalAction::SetUrls(remote_name, url, push_url) => git_set_remote_urls(repo, &remote_name, &url, &push_url),
+                RemotesModalAction::Fetch(remote_name) => git_fetch_remote(repo, &remote_name, progress),
+                RemotesModalAction::Prune(remote_name) => git_prune_remote(repo, &remote_name, progress),
+            };
+            // Only the modal's own actions reload it, so other operations finishing don't throw away half-typed edits.
+            // It's reloaded when it's opened, so there's no need to keep it up to date while it's closed.
+            let refresh_res = remotes_modal_c.lock().unwrap().refresh(repo);
+            res.and(refresh_res)
+        });
+    }
+
     fn show_branch_tree_col(&mut self, ui: &mut Ui) {
+        let mut action_opt = None;
         ScrollArea::both().id_source("branch-tree-col-scroll-area").max_width(self.branch_tree_col_width).auto_shrink([false, false]).show(ui, |ui| {
             ui.vertical(|ui| {
-                for branch_tree in &mut self.branch_trees {
-                    branch_tree.show(ui, 0.0);
+                for branch_tree in self.branch_trees.lock().unwrap().iter_mut() {
+                    if let Some(action) = branch_tree.show(ui, 0.0) {
+                        action_opt = Some(action);
+                    }
                 }
             })
         });
+        if let Some(action) = action_opt {
+            self.handle_branch_tree_action(action);
+        }
 
         // Add draggable separator.
         let separator_resp = ui.separator().interact(Sense::click_and_drag()).on_hover_and_drag_cursor(CursorIcon::ResizeHorizontal);
@@ -49,24 +211,287 @@ impl OG2Tab {
         }
     }
 
+    fn show_graph_and_details(&mut self, ui: &mut Ui) {
+        ui.vertical(|ui| {
+            let is_conflict_view_active = self.conflict_view.lock().unwrap().is_active();
+            if is_conflict_view_active {
+                let mut is_changed = false;
+                TopBottomPanel::top("conflict-view-panel").show_inside(ui, |ui| {
+                    if let Ok(repo) = self.repo.try_lock() {
+                        let res = self.conflict_view.lock().unwrap().show(ui, &repo);
+                        is_changed = self.error_modal.lock().unwrap().handle_error(res).unwrap_or(false);
+                    } else {
+                        ui.spinner();
+                    }
+                });
+                if is_changed {
+                    self.perform_fn_in_thread(|_repo| Ok(()));
+                }
+            }
+
+            if let Some(commit_details) = &mut self.commit_details_opt {
+                let mut action_res = Ok(None);
+                TopBottomPanel::bottom("commit-details-panel").resizable(true).default_height(COMMIT_DETAILS_DEFAULT_HEIGHT).show_inside(ui, |ui| {
+                    if let Ok(repo) = self.repo.try_lock() {
+                        action_res = commit_details.show(ui, &repo);
+                    } else {
+                        ui.spinner();
+                    }
+                });
+                let action_opt = self.error_modal.lock().unwrap().handle_error(action_res).flatten();
+                match action_opt {
+                    Some(CommitDetailsAction::SelectCommit(oid)) => self.select_commit(oid),
+                    Some(CommitDetailsAction::Close) => {
+                        self.commit_graph.lock().unwrap().clear_selection();
+                        self.commit_details_opt = None;
+                    },
+                    None => (),
+                };
+            }
+
+            let graph_action_opt = self.commit_graph.lock().unwrap().show(ui);
+            match graph_action_opt {
+                Some(CommitGraphAction::SelectCommit(oid)) => self.show_commit_details(oid),
+                Some(CommitGraphAction::InteractiveRebase(base_oid)) => self.open_rebase_editor(base_oid),
+                Some(CommitGraphAction::CherryPick(oids)) => self.start_sequence(SequenceKind::CherryPick, oids),
+                Some(CommitGraphAction::Revert(oids)) => self.start_sequence(SequenceKind::Revert, oids),
+                Some(CommitGraphAction::CreateTag(oid)) => self.tag_modal.open_create(oid),
+                Some(CommitGraphAction::LoadMoreRows) => self.load_more_graph_rows(),
+                Some(CommitGraphAction::Reset(oid)) => {
+                    if let Ok(repo) = self.repo.try_lock() {
+                        let res = self.reset_modal.open_with(&repo, oid);
+                        self.error_modal.lock().unwrap().handle_error(res);
+                    }
+                },
+                None => (),
+            };
+        });
+    }
+
+    // Applies the commits right away, unless there's a merge commit that needs a mainline parent picked first.
+    fn start_sequence(&mut self, kind: SequenceKind, oids: Vec<Oid>) {
+        let res = match self.repo.try_lock() {
+            Ok(repo) => git_utils::get_merge_parent_summaries(&repo, &oids),
+            Err(_) => return,
+        };
+        match self.error_modal.lock().unwrap().handle_error(res) {
+            Some(Some(parent_summaries)) => self.mainline_modal.open_with(kind, oids, parent_summaries),
+            Some(None) => self.apply_sequence(kind, oids, 0),
+            None => (),
+        };
+    }
+
+    fn apply_sequence(&self, kind: SequenceKind, mut oids: Vec<Oid>, mainline: u32) {
+        let outcome_msg_opt_c = self.outcome_msg_opt.clone();
+        self.perform_fn_in_thread(move |repo| {
+            let is_done = match kind {
+                SequenceKind::CherryPick => {
+                    // Cherry-pick the oldest first so they end up in the same order.
+                    oids.reverse();
+                    git_cherry_pick(repo, &oids, mainline)?
+                },
+                SequenceKind::Revert => git_revert(repo, &oids, mainline)?,
+            };
+            let msg = if is_done { "Done." } else { "Stopped because of conflicts. Resolve them and then continue." };
+            *outcome_msg_opt_c.lock().unwrap() = Some(format!("{}: {}", kind.get_title(), msg));
+            Ok(())
+        });
+    }
+
+    fn open_rebase_editor(&mut self, base_oid: Oid) {
+        if let Ok(repo) = self.repo.try_lock() {
+            let res = RebaseEditor::new(&repo, base_oid);
+            self.rebase_editor_opt = self.error_modal.lock().unwrap().handle_error(res);
+        }
+    }
+
+    fn show_rebase_editor(&mut self, ui: &mut Ui) {
+        let action_opt = match &mut self.rebase_editor_opt {
+            Some(rebase_editor) => rebase_editor.show(ui),
+            None => return,
+        };
+        match action_opt {
+            Some(RebaseEditorAction::Start(onto_oid, todo)) => {
+                self.rebase_editor_opt = None;
+                let outcome_msg_opt_c = self.outcome_msg_opt.clone();
+                self.perform_fn_in_thread(move |repo| {
+                    let msg = if git_interactive_rebase(repo, onto_oid, &todo)? {
+                        "Interactive Rebase: Done."
+                    } else {
+                        "Interactive Rebase: Stopped. Resolve any conflicts or edit the commit, then continue."
+                    };
+                    *outcome_msg_opt_c.lock().unwrap() = Some(String::from(msg));
+                    Ok(())
+                });
+            },
+            Some(RebaseEditorAction::Cancel) => self.rebase_editor_opt = None,
+            None => (),
+        };
+    }
+
+    // Loads more of the graph without blocking the UI or showing the loading spinner. The graph is only locked while
+    // starting and finishing the load, not while the commits are looked up.
+    fn load_more_graph_rows(&self) {
+        let repo_c = self.repo.clone();
+        let commit_graph_c = self.commit_graph.clone();
+        let error_modal_c = self.error_modal.clone();
+        let ctx_c = self.ctx.clone();
+        thread::spawn(move || {
+            let repo = repo_c.lock().unwrap();
+            // This is on a separate line so the graph isn't locked while loading.
+            let row_load_opt = commit_graph_c.lock().unwrap().start_row_load();
+            if let Some(mut row_load) = row_load_opt {
+                let res = row_load.load(&repo);
+                commit_graph_c.lock().unwrap().finish_row_load(row_load);
+                error_modal_c.lock().unwrap().handle_error(res);
+            }
+            ctx_c.request_repaint();
+        });
+    }
+
+    // If the commit's row isn't loaded yet, the graph loads it in the background and scrolls to it afterwards.
+    fn select_commit(&mut self, oid: Oid) {
+        self.commit_graph.lock().unwrap().select_commit(oid);
+        self.show_commit_details(oid);
+    }
+
+    fn show_commit_details(&mut self, oid: Oid) {
+        if self.commit_details_opt.as_ref().is_some_and(|commit_details| commit_details.get_oid() == oid) {
+            return;
+        }
+        if let Ok(repo) = self.repo.try_lock() {
+            let res = CommitDetails::new(&repo, oid);
+            self.commit_details_opt = self.error_modal.lock().unwrap().handle_error(res);
+        }
+    }
+
     pub fn show(&mut self, ui: &mut Ui) {
+        if let Some(result) = self.branch_modal.show(ui) {
+            self.handle_branch_modal_result(result);
+        }
+        if let Some((ref_name, merge_kind)) = self.merge_modal.show(ui) {
+            self.merge(ref_name, merge_kind);
+        }
+        if let Some((kind, oids, mainline)) = self.mainline_modal.show(ui) {
+            self.apply_sequence(kind, oids, mainline);
+        }
+        match self.reset_modal.show(ui) {
+            Some(ResetModalAction::Reset(oid, reset_mode)) => self.perform_fn_in_thread(move |repo| git_reset(repo, oid, reset_mode)),
+            // If the repo's busy, this is just tried again next frame.
+            Some(ResetModalAction::LoadLostChanges) => {
+                if let Ok(repo) = self.repo.try_lock() {
+                    let res = self.reset_modal.load_lost_changes(&repo);
+                    self.error_modal.lock().unwrap().handle_error(res);
+                }
+            },
+            None => (),
+        };
+        let remotes_modal_action_opt = self.remotes_modal.lock().unwrap().show(ui);
+        if let Some(action) = remotes_modal_action_opt {
+            self.handle_remotes_modal_action(action);
+        }
+        if let Some(result) = self.tag_modal.show(ui) {
+            self.handle_tag_modal_result(result);
+        }
+        if let Some((message, include_untracked, keep_index)) = self.stash_modal.show(ui) {
+            self.perform_fn_in_thread(move |repo| git_stash_save(repo, &message, include_untracked, keep_index));
+        }
         ui.vertical(|ui| {
             ui.horizontal(|ui| {
                 if ui.button("Fetch").clicked() {
-                    perform_fn_in_thread(git_fetch, self.repo.clone(), self.error_modal.clone(), self.commit_graph.clone(), self.is_loading.clone());
+                    self.perform_remote_fn_in_thread(git_fetch);
                 }
                 if ui.button("Pull").clicked() {
-                    perform_fn_in_thread(git_pull, self.repo.clone(), self.error_modal.clone(), self.commit_graph.clone(), self.is_loading.clone());
+                    self.pull(None);
                 }
+                ui.menu_button("Pull With...", |ui| {
+                    let strategies = [
+                        (PullStrategy::Merge, "Merge"),
+                        (PullStrategy::MergeNoFastForward, "Merge (Always Create A Merge Commit)"),
+                        (PullStrategy::Rebase, "Rebase"),
+                        (PullStrategy::FastForwardOnly, "Fast-Forward Only"),
+                    ];
+                    for (strategy, text) in strategies {
+                        if ui.button(text).clicked() {
+                            self.pull(Some(strategy));
+                            ui.close_menu();
+                        }
+                    }
+                });
                 if ui.button("Push").clicked() {
-                    // TODO: Implement Push
+                    self.perform_remote_fn_in_thread(|repo, progress| git_push(repo, false, progress));
+                }
+                if ui.button("Force Push (With Lease)").clicked() {
+                    self.perform_remote_fn_in_thread(|repo, progress| git_push(repo, true, progress));
+                }
+                if ui.button("Remotes...").clicked() {
+                    if let Ok(repo) = self.repo.try_lock() {
+                        let res = self.remotes_modal.lock().unwrap().open_with(&repo);
+                        self.error_modal.lock().unwrap().handle_error(res);
+                    }
+                }
+                if ui.button("Stash").clicked() {
+                    self.stash_modal.open_stash();
+                }
+                ui.menu_button("Show In Graph", |ui| {
+                    let mut revwalk_roots = self.commit_graph.lock().unwrap().get_revwalk_roots();
+                    let mut is_changed = ui.checkbox(&mut revwalk_roots.include_remote_branches, "Remote Branches").changed();
+                    is_changed |= ui.checkbox(&mut revwalk_roots.include_tags, "Tags").changed();
+                    is_changed |= ui.checkbox(&mut revwalk_roots.include_stashes, "Stashes").changed();
+                    if is_changed {
+                        self.commit_graph.lock().unwrap().set_revwalk_roots(revwalk_roots);
+                        // Nothing to do, this just refreshes the graph with the new roots.
+                        self.perform_fn_in_thread(|_repo| Ok(()));
+                    }
+                });
+                let load_status_opt = self.commit_graph.lock().unwrap().get_load_status_opt();
+                if let Some(load_status) = load_status_opt {
+                    ui.label(load_status);
+                }
+                let mut outcome_msg_opt = self.outcome_msg_opt.lock().unwrap();
+                if let Some(outcome_msg) = &*outcome_msg_opt {
+                    ui.label(outcome_msg);
+                    if ui.small_button("X").clicked() {
+                        *outcome_msg_opt = None;
+                    }
+                }
+            });
+
+            SidePanel::right("status-panel").resizable(true).default_width(STATUS_PANEL_DEFAULT_WIDTH).show_inside(ui, |ui| {
+                // If a background operation is holding the repo, skip showing the status until it's done.
+                let mut is_committed = false;
+                if let Ok(repo) = self.repo.try_lock() {
+                    if !repo.is_bare() {
+                        TopBottomPanel::bottom("commit-box").show_inside(ui, |ui| {
+                            let res = self.commit_box.show(ui, &repo);
+                            is_committed = self.error_modal.lock().unwrap().handle_error(res).unwrap_or(false);
+                        });
+                    }
+                    let res = self.status_panel.lock().unwrap().show(ui, &repo);
+                    self.error_modal.lock().unwrap().handle_error(res);
+                } else {
+                    ui.spinner();
+                }
+                if is_committed {
+                    // The commit is already done, this just refreshes everything.
+                    self.perform_fn_in_thread(|_repo| Ok(()));
                 }
             });
 
             ui.with_layout(Layout::top_down(Align::Min).with_main_justify(true), |ui| {
                 ui.horizontal(|ui| {
                     self.show_branch_tree_col(ui);
-                    self.commit_graph.lock().unwrap().show(ui);
+                    let has_diff_view = self.status_panel.lock().unwrap().has_diff_view();
+                    if has_diff_view {
+                        if let Ok(repo) = self.repo.try_lock() {
+                            let res = self.status_panel.lock().unwrap().show_diff_view(ui, &repo);
+                            self.error_modal.lock().unwrap().handle_error(res);
+                        }
+                    } else if self.rebase_editor_opt.is_some() {
+                        self.show_rebase_editor(ui);
+                    } else {
+                        self.show_graph_and_details(ui);
+                    }
                 });
             });
         });
@@ -3,35 +3,66 @@ use std::sync::{Arc, Mutex};
 use std::thread;
 use anyhow::Result;
 use egui::{ColorImage, Context};
-use git2::Repository;
+use git2::{ErrorCode, Repository};
 use image::io::Reader;
 use crate::backend::git_utils;
-use crate::frontend::commit_graph::CommitGraph;
+use crate::backend::progress::{OperationProgress, OperationProgressList};
 use crate::frontend::modals::ErrorModal;
 use crate::frontend::tab::OG2Tab;
 
-pub fn open_repo_as_tab(tabs_arc: Arc<Mutex<Vec<OG2Tab>>>, active_tab_arc: Arc<Mutex<usize>>, error_modal_arc: Arc<Mutex<ErrorModal>>, is_loading: Arc<Mutex<bool>>, ctx_c: Context) -> Result<()> {
+pub fn open_repo_as_tab(tabs_arc: Arc<Mutex<Vec<OG2Tab>>>, active_tab_arc: Arc<Mutex<usize>>, error_modal_arc: Arc<Mutex<ErrorModal>>, progress_list: OperationProgressList, ctx_c: Context) -> Result<()> {
     let repo_opt = git_utils::open_repo()?;
     // If a repo was actually opened
     if let Some((name, repo)) = repo_opt {
         thread::spawn(move || {
-            *is_loading.lock().unwrap() = true;
-            let new_tab_res = OG2Tab::new(name, repo, is_loading.clone(), error_modal_arc.clone(), &ctx_c);  // This line is slow!
-            // This is on a separate line so it doesn't get locked too early.
-            let new_tab_opt = error_modal_arc.lock().unwrap().handle_error(new_tab_res);
-            if let Some(new_tab) = new_tab_opt {
-                let mut tabs = tabs_arc.lock().unwrap();
-                tabs.push(new_tab);
-
-                let mut active_tab = active_tab_arc.lock().unwrap();
-                *active_tab = tabs.len() - 1;
-            }
-            *is_loading.lock().unwrap() = false;
+            let progress = progress_list.start();
+            add_repo_as_tab(name, repo, tabs_arc, active_tab_arc, error_modal_arc, progress_list.clone(), &ctx_c);
+            progress_list.finish(&progress);
         });
     }
     Ok(())
 }
 
+pub fn get_repo_as_tab<F>(get_repo_fn: F, tabs_arc: Arc<Mutex<Vec<OG2Tab>>>, active_tab_arc: Arc<Mutex<usize>>, error_modal_arc: Arc<Mutex<ErrorModal>>, progress_list: OperationProgressList, ctx_c: Context)
+where
+    F: FnOnce(Arc<Mutex<OperationProgress>>) -> Result<(String, Repository)> + Send + 'static
+{
+    thread::spawn(move || {
+        let progress = progress_list.start();
+        let repo_res = get_repo_fn(progress.clone());
+        // The user already knows about the error if they cancelled it.
+        if repo_res.as_ref().is_err_and(|e| is_cancellation_error(e, &progress)) {
+            progress_list.finish(&progress);
+            return;
+        }
+        // This is on a separate line so it doesn't get locked too early.
+        let repo_opt = error_modal_arc.lock().unwrap().handle_error(repo_res);
+        if let Some((name, repo)) = repo_opt {
+            add_repo_as_tab(name, repo, tabs_arc, active_tab_arc, error_modal_arc, progress_list.clone(), &ctx_c);
+        }
+        progress_list.finish(&progress);
+    });
+}
+
+fn add_repo_as_tab(name: String, repo: Repository, tabs_arc: Arc<Mutex<Vec<OG2Tab>>>, active_tab_arc: Arc<Mutex<usize>>, error_modal_arc: Arc<Mutex<ErrorModal>>, progress_list: OperationProgressList, ctx: &Context) {
+    let new_tab_res = OG2Tab::new(name, repo, progress_list, error_modal_arc.clone(), ctx);  // This line is slow!
+    // This is on a separate line so it doesn't get locked too early.
+    let new_tab_opt = error_modal_arc.lock().unwrap().handle_error(new_tab_res);
+    if let Some(new_tab) = new_tab_opt {
+        let mut tabs = tabs_arc.lock().unwrap();
+        tabs.push(new_tab);
+
+        let mut active_tab = active_tab_arc.lock().unwrap();
+        *active_tab = tabs.len() - 1;
+    }
+}
+
+// Other errors can still happen after cancel is clicked (e.g. the remote rejecting something first), so only the one
+// libgit2 returns for an aborted transfer is left out.
+fn is_cancellation_error(error: &anyhow::Error, progress: &Arc<Mutex<OperationProgress>>) -> bool {
+    progress.lock().unwrap().is_cancelled() && error.downcast_ref::<git2::Error>().is_some_and(|e| e.code() == ErrorCode::User)
+}
+
 pub fn load_image_from_path(path: &Path) -> Result<ColorImage> {
     let image = Reader::open(path)?.decode()?;
     let size = [image.width() as _, image.height() as _];
@@ -43,21 +74,30 @@ pub fn load_image_from_path(path: &Path) -> Result<ColorImage> {
     ))
 }
 
-pub fn perform_fn_in_thread(
-    some_fn: fn(&Repository) -> Result<()>,
+pub fn perform_fn_in_thread<F, R>(
+    some_fn: F,
     repo_c: Arc<Mutex<Repository>>,
     error_modal_c: Arc<Mutex<ErrorModal>>,
-    commit_graph_c: Arc<Mutex<CommitGraph>>,
-    is_loading_c: Arc<Mutex<bool>>
-) {
+    progress_list_c: OperationProgressList,
+    refresh_fn: R
+)
+where
+    F: FnOnce(&Repository, &Arc<Mutex<OperationProgress>>) -> Result<()> + Send + 'static,
+    R: FnOnce(&Repository) -> Result<()> + Send + 'static
+{
     thread::spawn(move || {
-        *is_loading_c.lock().unwrap() = true;
-        let res = some_fn(&repo_c.lock().unwrap());
-        let opt = error_modal_c.lock().unwrap().handle_error(res);
-        if let Some(()) = opt {
-            let res = commit_graph_c.lock().unwrap().refresh_graph(&repo_c.lock().unwrap());
+        let progress = progress_list_c.start();
+        // Always lock the repo before anything else so this can't deadlock with the UI thread.
+        let repo = repo_c.lock().unwrap();
+        let res = some_fn(&repo, &progress);
+        // The user already knows about the error if they cancelled it.
+        if !res.as_ref().is_err_and(|e| is_cancellation_error(e, &progress)) {
             error_modal_c.lock().unwrap().handle_error(res);
         }
-        *is_loading_c.lock().unwrap() = false;
+        // Refresh even if the operation failed, since it may have changed things part way through.
+        let res = refresh_fn(&repo);
+        error_modal_c.lock().unwrap().handle_error(res);
+        drop(repo);
+        progress_list_c.finish(&progress);
     });
-}
\ No newline at end of file
+}